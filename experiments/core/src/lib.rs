@@ -0,0 +1,282 @@
+//! Allocation-free connectome engine for microcontrollers.
+//!
+//! The same packed ROM and sequential `neural_cycle` as the `neuro-rust`
+//! engine, without `std`: the cell counts are const generics, so the whole
+//! state is a fixed-size struct that can live in a `static` next to the ROM.
+//! Neuromodulation, muscle models, observers and the alternative update
+//! orders stay on the host.
+#![no_std]
+
+use core::fmt;
+
+/// Why a ROM image does not fit an engine
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RomError {
+    Empty,
+    /// The ROM declares a different number of neurons than the engine holds
+    Neurons { rom: u16, engine: usize },
+    /// The address table is cut short or points outside the ROM
+    AddressTable { neuron: u16 },
+    /// A connection targets a cell beyond the engine's neurons and muscles
+    Target { neuron: u16, target: u16 },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::Neurons { rom, engine } => write!(
+                f,
+                "ROM declares {} neurons, the engine holds {}",
+                rom, engine
+            ),
+            RomError::AddressTable { neuron } => {
+                write!(f, "ROM address table is invalid at neuron {}", neuron)
+            }
+            RomError::Target { neuron, target } => write!(
+                f,
+                "Neuron {} connects to cell {}, outside the engine",
+                neuron, target
+            ),
+        }
+    }
+}
+
+/// Decode a ROM word into the target cell id and the weight (ctm_parse_rom_word)
+#[inline]
+pub fn parse_rom_word(rom_word: u16) -> (u16, i8) {
+    let [low, high] = rom_word.to_le_bytes();
+    let id = high as u16 + (((low & 0x80) as u16) << 1);
+    let weight_bits = low & 0x7F;
+    let weight = weight_bits.wrapping_add((weight_bits & 0x40) << 1) as i8;
+    (id, weight)
+}
+
+/// Engine over `NEURONS` neurons followed by `MUSCLES` muscles, running on a
+/// borrowed ROM image: `[neurons_tot, address table.., connections..]`.
+///
+/// Neurons discharge when their state is strictly above the threshold (40 by
+/// default) and are updated in id order within a cycle. A neuron whose state
+/// has not changed for more than `max_idle` cycles (100 by default) is reset
+/// to 0.
+pub struct Connectome<'a, const NEURONS: usize, const MUSCLES: usize> {
+    rom: &'a [u16],
+    threshold: i8,
+    max_idle: u8,
+
+    neuron_current: [i8; NEURONS],
+    neuron_next: [i8; NEURONS],
+
+    muscle_current: [i16; MUSCLES],
+    muscle_next: [i16; MUSCLES],
+
+    // meta: [discharged_bit | idle_ticks(7 bits)]
+    meta: [u8; NEURONS],
+}
+
+impl<'a, const NEURONS: usize, const MUSCLES: usize> Connectome<'a, NEURONS, MUSCLES> {
+    /// Engine on a ROM that has already been checked with `check_rom`, for
+    /// initializing a `static`. A ROM that does not fit makes the engine
+    /// panic or misbehave.
+    pub const fn new(rom: &'a [u16]) -> Self {
+        Self {
+            rom,
+            threshold: 40,
+            max_idle: 100,
+            neuron_current: [0; NEURONS],
+            neuron_next: [0; NEURONS],
+            muscle_current: [0; MUSCLES],
+            muscle_next: [0; MUSCLES],
+            meta: [0; NEURONS],
+        }
+    }
+
+    /// Engine on a ROM image, checked to fit (ctm_init)
+    pub fn from_rom(rom: &'a [u16]) -> Result<Self, RomError> {
+        Self::check_rom(rom)?;
+        Ok(Self::new(rom))
+    }
+
+    /// Check that a ROM declares `NEURONS` neurons, that its address table
+    /// is consistent and that every connection stays within the cells.
+    pub fn check_rom(rom: &[u16]) -> Result<(), RomError> {
+        let neurons_tot = *rom.first().ok_or(RomError::Empty)?;
+        if neurons_tot as usize != NEURONS {
+            return Err(RomError::Neurons {
+                rom: neurons_tot,
+                engine: NEURONS,
+            });
+        }
+        let table_end = NEURONS + 2;
+        if rom.len() < table_end {
+            return Err(RomError::AddressTable { neuron: 0 });
+        }
+        for id in 0..NEURONS {
+            let (start, end) = (rom[id + 1] as usize, rom[id + 2] as usize);
+            if start < table_end || start > end || end > rom.len() {
+                return Err(RomError::AddressTable { neuron: id as u16 });
+            }
+            for &word in &rom[start..end] {
+                let (target, _) = parse_rom_word(word);
+                if target as usize >= NEURONS + MUSCLES {
+                    return Err(RomError::Target {
+                        neuron: id as u16,
+                        target,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Clear every state back to the power-on values, keeping the settings
+    pub fn reset(&mut self) {
+        self.neuron_current = [0; NEURONS];
+        self.neuron_next = [0; NEURONS];
+        self.muscle_current = [0; MUSCLES];
+        self.muscle_next = [0; MUSCLES];
+        self.meta = [0; NEURONS];
+    }
+
+    pub fn rom(&self) -> &'a [u16] {
+        self.rom
+    }
+
+    pub fn set_threshold(&mut self, threshold: i8) {
+        self.threshold = threshold;
+    }
+
+    pub fn threshold(&self) -> i8 {
+        self.threshold
+    }
+
+    /// Idle cycles before a neuron is reset, at most 126
+    pub fn set_max_idle(&mut self, max_idle: u8) {
+        self.max_idle = max_idle.min(126);
+    }
+
+    pub fn max_idle(&self) -> u8 {
+        self.max_idle
+    }
+
+    pub const fn neurons_tot(&self) -> u16 {
+        NEURONS as u16
+    }
+
+    pub const fn muscles_tot(&self) -> u16 {
+        MUSCLES as u16
+    }
+
+    /// Complete one neural cycle (ctm_neural_cycle), pinging the stimulated
+    /// neurons first. Stimulus ids that are not neurons are ignored.
+    pub fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>) {
+        if let Some(stim) = stim_neuron {
+            for &id in stim {
+                if (id as usize) < NEURONS {
+                    self.ping_neuron(id);
+                }
+            }
+        }
+        for id in 0..NEURONS as u16 {
+            if self.get_current_state(id) > self.threshold as i16 {
+                self.discharge_neuron(id);
+                self.meta[id as usize] = 0x80;
+            } else {
+                self.meta[id as usize] &= 0x7F;
+            }
+        }
+        self.meta_handle_idle_neurons();
+        self.iterate_state();
+    }
+
+    /// 1 for each neuron that discharged in the last cycle, 0 otherwise
+    pub fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = self.meta.get(id as usize).map_or(0, |m| m >> 7);
+        }
+    }
+
+    /// State of any cell, neuron or muscle, from the last cycle
+    pub fn state_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = self.get_current_state(id);
+        }
+    }
+
+    /// Muscle values from the last cycle, queried by cell id
+    pub fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        self.state_query(input_id, query_result);
+    }
+
+    /// State of a cell from the last cycle; 0 outside the engine
+    fn get_current_state(&self, id: u16) -> i16 {
+        let id = id as usize;
+        if id < NEURONS {
+            self.neuron_current[id] as i16
+        } else {
+            self.muscle_current.get(id - NEURONS).copied().unwrap_or(0)
+        }
+    }
+
+    /// Get next state (ctm_get_next_state)
+    fn get_next_state(&self, id: u16) -> i16 {
+        let id = id as usize;
+        if id < NEURONS {
+            self.neuron_next[id] as i16
+        } else {
+            self.muscle_next[id - NEURONS]
+        }
+    }
+
+    /// Set next state with saturation (ctm_set_next_state)
+    fn set_next_state(&mut self, id: u16, val: i16) {
+        let id = id as usize;
+        if id < NEURONS {
+            self.neuron_next[id] = val.clamp(-128, 127) as i8;
+        } else {
+            self.muscle_next[id - NEURONS] = val;
+        }
+    }
+
+    /// Propagate connections (ctm_ping_neuron)
+    fn ping_neuron(&mut self, id: u16) {
+        let start = self.rom[id as usize + 1] as usize;
+        let end = self.rom[id as usize + 2] as usize;
+        for k in start..end {
+            let (target, weight) = parse_rom_word(self.rom[k]);
+            let next = self.get_next_state(target).wrapping_add(weight as i16);
+            self.set_next_state(target, next);
+        }
+    }
+
+    /// Discharge neuron (ctm_discharge_neuron)
+    fn discharge_neuron(&mut self, id: u16) {
+        self.ping_neuron(id);
+        self.set_next_state(id, 0);
+    }
+
+    /// Handle idle neurons (ctm_meta_handle_idle_neurons)
+    fn meta_handle_idle_neurons(&mut self) {
+        for id in 0..NEURONS {
+            let high = self.meta[id] & 0x80;
+            let mut idle_ticks = self.meta[id] & 0x7F;
+            if self.neuron_next[id] == self.neuron_current[id] {
+                self.meta[id] = self.meta[id].wrapping_add(1);
+                idle_ticks = idle_ticks.wrapping_add(1);
+            } else {
+                self.meta[id] = high;
+            }
+            if idle_ticks > self.max_idle {
+                self.neuron_next[id] = 0;
+                self.meta[id] = high;
+            }
+        }
+    }
+
+    /// Iterate state (ctm_iterate_state)
+    fn iterate_state(&mut self) {
+        self.neuron_current = self.neuron_next;
+        self.muscle_current = self.muscle_next;
+        self.muscle_next = [0; MUSCLES];
+    }
+}
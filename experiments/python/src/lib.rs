@@ -0,0 +1,300 @@
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use engine::connectome::Connectome as RustConnectome;
+use engine::connectome::rom_image::read_rom_file;
+use engine::emulations::c_elegans::neuron_groups::resolve_target;
+use engine::emulations::c_elegans::neuron_ids::{CELLS, NeuronId, cell_name as rust_cell_name};
+use engine::emulations::c_elegans::preset;
+use engine::lify_stuff::first_order_lif_collection::FirstOrderLifCollection as RustLifCollection;
+
+fn value_error(err: String) -> PyErr {
+    PyValueError::new_err(err)
+}
+
+/// A cell id, or a cell, group or sensory modality name
+#[derive(FromPyObject)]
+enum Target {
+    Id(u16),
+    Name(String),
+}
+
+fn resolve(targets: Vec<Target>) -> PyResult<Vec<u16>> {
+    let mut ids = vec![];
+    for target in targets {
+        match target {
+            Target::Id(id) if id < CELLS => ids.push(id),
+            Target::Id(id) => return Err(value_error(format!("No cell {}", id))),
+            Target::Name(name) => ids.extend(resolve_target(&name).map_err(value_error)?),
+        }
+    }
+    Ok(ids)
+}
+
+/// The C. elegans connectome engine.
+#[pyclass]
+struct Connectome {
+    inner: RustConnectome,
+}
+
+#[pymethods]
+impl Connectome {
+    /// The built-in ROM, or a ROM image file written by `export --format rom`.
+    #[new]
+    #[pyo3(signature = (rom = None))]
+    fn new(rom: Option<std::path::PathBuf>) -> PyResult<Self> {
+        let inner = match rom {
+            Some(path) => {
+                RustConnectome::from_rom(read_rom_file(&path).map_err(value_error)?, CELLS)
+                    .and_then(preset::with_names)
+                    .map_err(value_error)?
+            }
+            None => preset::connectome(),
+        };
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    fn neurons_tot(&self) -> u16 {
+        self.inner.neurons_tot()
+    }
+
+    #[getter]
+    fn muscles_tot(&self) -> u16 {
+        self.inner.muscles_tot()
+    }
+
+    #[getter]
+    fn threshold(&self) -> i8 {
+        self.inner.threshold()
+    }
+
+    #[setter]
+    fn set_threshold(&mut self, threshold: i8) {
+        self.inner.set_threshold(threshold);
+    }
+
+    #[getter]
+    fn cycle(&self) -> u64 {
+        self.inner.cycle()
+    }
+
+    /// Lesion cells by id or name
+    fn ablate(&mut self, targets: Vec<Target>) -> PyResult<()> {
+        for id in resolve(targets)? {
+            self.inner.ablate(id);
+        }
+        Ok(())
+    }
+
+    /// Complete one cycle, pinging the stimulus targets first
+    #[pyo3(signature = (stimulus = None))]
+    fn neural_cycle(&mut self, stimulus: Option<Vec<Target>>) -> PyResult<()> {
+        let stimulus = stimulus.map(|s| self.neurons(s)).transpose()?;
+        self.inner.neural_cycle(stimulus.as_deref());
+        Ok(())
+    }
+
+    /// Run `cycles` cycles under `stimulus` and return the discharge trace of
+    /// `record` (all neurons by default) as a `cycles x cells` uint8 array.
+    #[pyo3(signature = (cycles, stimulus = None, record = None))]
+    fn run<'py>(
+        &mut self,
+        py: Python<'py>,
+        cycles: usize,
+        stimulus: Option<Vec<Target>>,
+        record: Option<Vec<Target>>,
+    ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        let stimulus = stimulus.map(|s| self.neurons(s)).transpose()?;
+        let record = self.ids_or_neurons(record)?;
+        let mut trace = Array2::<u8>::zeros((cycles, record.len()));
+        let mut row = vec![0u8; record.len()];
+        for mut trace_row in trace.rows_mut() {
+            self.inner.neural_cycle(stimulus.as_deref());
+            self.inner.discharge_query(&record, &mut row);
+            trace_row.iter_mut().zip(&row).for_each(|(t, &r)| *t = r);
+        }
+        Ok(trace.into_pyarray(py))
+    }
+
+    /// Discharge flags of the last cycle (all neurons by default)
+    #[pyo3(signature = (ids = None))]
+    fn discharges<'py>(
+        &self,
+        py: Python<'py>,
+        ids: Option<Vec<Target>>,
+    ) -> PyResult<Bound<'py, PyArray1<u8>>> {
+        let ids = self.ids_or_neurons(ids)?;
+        let mut result = vec![0u8; ids.len()];
+        self.inner.discharge_query(&ids, &mut result);
+        Ok(PyArray1::from_vec(py, result))
+    }
+
+    /// Muscle values of the last cycle (all muscles by default)
+    #[pyo3(signature = (ids = None))]
+    fn muscles<'py>(
+        &self,
+        py: Python<'py>,
+        ids: Option<Vec<Target>>,
+    ) -> PyResult<Bound<'py, PyArray1<i16>>> {
+        let first = self.inner.neurons_tot();
+        let ids = match ids {
+            Some(ids) => self.kind_only(ids, true)?,
+            None => (first..first + self.inner.muscles_tot()).collect(),
+        };
+        let mut result = vec![0i16; ids.len()];
+        self.inner.muscle_query(&ids, &mut result);
+        Ok(PyArray1::from_vec(py, result))
+    }
+}
+
+impl Connectome {
+    fn ids_or_neurons(&self, ids: Option<Vec<Target>>) -> PyResult<Vec<u16>> {
+        match ids {
+            Some(ids) => self.neurons(ids),
+            None => Ok((0..self.inner.neurons_tot()).collect()),
+        }
+    }
+
+    fn neurons(&self, targets: Vec<Target>) -> PyResult<Vec<u16>> {
+        self.kind_only(targets, false)
+    }
+
+    /// Resolve `targets`, all of which must be muscles or all neurons
+    fn kind_only(&self, targets: Vec<Target>, muscles: bool) -> PyResult<Vec<u16>> {
+        let ids = resolve(targets)?;
+        let wrong = ids
+            .iter()
+            .find(|&&id| (id >= self.inner.neurons_tot()) != muscles);
+        match wrong {
+            Some(&id) => Err(value_error(format!(
+                "{} is not a {}",
+                self.inner.cells().name(id),
+                if muscles { "muscle" } else { "neuron" }
+            ))),
+            None => Ok(ids),
+        }
+    }
+}
+
+/// Id of a cell name, e.g. `neuron_id("AVAL")`
+#[pyfunction]
+fn neuron_id(name: &str) -> PyResult<u16> {
+    NeuronId::from_name(name)
+        .map(|id| id as u16)
+        .ok_or_else(|| value_error(format!("Unknown cell {}", name)))
+}
+
+/// Name of a cell id
+#[pyfunction]
+fn cell_name(id: u16) -> String {
+    rust_cell_name(id)
+}
+
+/// Ids of cells, groups or sensory modalities
+#[pyfunction]
+fn resolve_targets(targets: Vec<Target>) -> PyResult<Vec<u16>> {
+    resolve(targets)
+}
+
+/// An ensemble of first-order LIF neurons with random rates and intercepts.
+#[pyclass]
+struct FirstOrderLifCollection {
+    inner: RustLifCollection,
+}
+
+#[pymethods]
+impl FirstOrderLifCollection {
+    #[new]
+    #[pyo3(signature = (
+        num_neurons,
+        tau_rc = 0.02,
+        tau_ref = 0.002,
+        max_rate_range = (100., 200.),
+        intercept_range = (-1., 1.),
+        encoders = vec![-1, 1],
+    ))]
+    fn new(
+        num_neurons: usize,
+        tau_rc: f64,
+        tau_ref: f64,
+        max_rate_range: (f64, f64),
+        intercept_range: (f64, f64),
+        encoders: Vec<i32>,
+    ) -> PyResult<Self> {
+        let inner = RustLifCollection::new(
+            num_neurons,
+            tau_rc,
+            tau_ref,
+            max_rate_range,
+            intercept_range,
+            encoders,
+        )
+        .map_err(value_error)?;
+        Ok(Self { inner })
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.neurons.len()
+    }
+
+    /// Advance every neuron by `t_step` on a scalar input; returns the outputs
+    fn step<'py>(&mut self, py: Python<'py>, input: f64, t_step: f64) -> Bound<'py, PyArray1<f64>> {
+        PyArray1::from_vec(py, self.inner.step(input, t_step, None))
+    }
+
+    /// Step through `inputs`, returning a `len(inputs) x neurons` output array
+    fn run<'py>(
+        &mut self,
+        py: Python<'py>,
+        inputs: PyReadonlyArray1<'py, f64>,
+        t_step: f64,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let inputs = inputs.as_slice()?;
+        let mut outputs = Array2::<f64>::zeros((inputs.len(), self.inner.neurons.len()));
+        for (mut row, &input) in outputs.rows_mut().into_iter().zip(inputs) {
+            let step = self.inner.step(input, t_step, None);
+            row.iter_mut().zip(step).for_each(|(o, s)| *o = s);
+        }
+        Ok(outputs.into_pyarray(py))
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    /// Analytical firing rates, a `neurons x len(inputs)` array
+    fn tuning_curves<'py>(
+        &self,
+        py: Python<'py>,
+        inputs: PyReadonlyArray1<'py, f64>,
+    ) -> PyResult<Bound<'py, PyArray2<f64>>> {
+        let curves = self.inner.get_tuning_curves(inputs.as_slice()?);
+        Ok(PyArray2::from_vec2(py, &curves)?)
+    }
+
+    /// Regularized least-squares decoders over `inputs`
+    fn decoders<'py>(
+        &self,
+        py: Python<'py>,
+        inputs: PyReadonlyArray1<'py, f64>,
+    ) -> PyResult<Bound<'py, PyArray1<f64>>> {
+        let decoders = self
+            .inner
+            .get_decoders(inputs.as_slice()?)
+            .map_err(value_error)?;
+        Ok(PyArray1::from_vec(py, decoders))
+    }
+}
+
+#[pymodule]
+fn neuro_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Connectome>()?;
+    m.add_class::<FirstOrderLifCollection>()?;
+    m.add_function(wrap_pyfunction!(neuron_id, m)?)?;
+    m.add_function(wrap_pyfunction!(cell_name, m)?)?;
+    m.add_function(wrap_pyfunction!(resolve_targets, m)?)?;
+    Ok(())
+}
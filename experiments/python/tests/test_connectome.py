@@ -0,0 +1,72 @@
+import numpy as np
+import pytest
+
+import neuro_rust
+
+
+def test_neuron_ids_round_trip():
+    aval = neuro_rust.neuron_id("AVAL")
+    assert neuro_rust.cell_name(aval) == "AVAL"
+    with pytest.raises(ValueError):
+        neuro_rust.neuron_id("NOTACELL")
+
+
+def test_groups_resolve_to_ids():
+    touch = neuro_rust.resolve_targets(["nose_touch"])
+    assert len(touch) == 10
+    assert neuro_rust.resolve_targets(["AVAL", 0]) == [neuro_rust.neuron_id("AVAL"), 0]
+
+
+def test_run_returns_discharge_trace():
+    connectome = neuro_rust.Connectome()
+    trace = connectome.run(200, stimulus=["chemotaxis"], record=["motor_ab"])
+    assert trace.shape == (200, 39)
+    assert trace.dtype == np.uint8
+    assert trace.sum() > 0
+    assert connectome.cycle == 200
+
+
+def test_runs_are_deterministic():
+    a = neuro_rust.Connectome().run(300, stimulus=["nose_touch"])
+    b = neuro_rust.Connectome().run(300, stimulus=["nose_touch"])
+    np.testing.assert_array_equal(a, b)
+
+
+def test_readout_matches_last_cycle():
+    connectome = neuro_rust.Connectome()
+    trace = connectome.run(50, stimulus=["chemotaxis"])
+    np.testing.assert_array_equal(connectome.discharges(), trace[-1])
+    muscles = connectome.muscles()
+    assert muscles.shape == (connectome.muscles_tot,)
+    assert muscles.dtype == np.int16
+    assert connectome.muscles(["MDL07"]).shape == (1,)
+
+
+def test_ablated_cells_never_discharge():
+    connectome = neuro_rust.Connectome()
+    connectome.ablate(["AVAL", "AVAR"])
+    trace = connectome.run(500, stimulus=["nose_touch"], record=["AVAL", "AVAR"])
+    assert trace.sum() == 0
+
+
+def test_threshold_is_settable():
+    connectome = neuro_rust.Connectome()
+    connectome.threshold = 30
+    assert connectome.threshold == 30
+
+
+def test_unknown_target_is_a_value_error():
+    with pytest.raises(ValueError):
+        neuro_rust.Connectome().neural_cycle(["NOTACELL"])
+
+
+def test_targets_of_the_wrong_kind_are_value_errors():
+    connectome = neuro_rust.Connectome()
+    with pytest.raises(ValueError):
+        connectome.neural_cycle(["MDL01"])
+    with pytest.raises(ValueError):
+        connectome.discharges([neuro_rust.neuron_id("MDL01")])
+    with pytest.raises(ValueError):
+        connectome.run(10, record=["MVR24"])
+    with pytest.raises(ValueError, match="AVAL is not a muscle"):
+        connectome.muscles(["AVAL"])
@@ -0,0 +1,38 @@
+import numpy as np
+
+import neuro_rust
+
+
+def test_step_returns_one_output_per_neuron():
+    ensemble = neuro_rust.FirstOrderLifCollection(20)
+    assert len(ensemble) == 20
+    out = ensemble.step(0.5, 0.001)
+    assert out.shape == (20,)
+
+
+def test_run_and_reset():
+    ensemble = neuro_rust.FirstOrderLifCollection(10)
+    inputs = np.linspace(-1, 1, 500)
+    outputs = ensemble.run(inputs, 0.001)
+    assert outputs.shape == (500, 10)
+    assert outputs.sum() > 0
+    ensemble.reset()
+    np.testing.assert_array_equal(ensemble.run(inputs, 0.001), outputs)
+
+
+def test_tuning_curves_shape_and_rates():
+    ensemble = neuro_rust.FirstOrderLifCollection(15, max_rate_range=(100.0, 200.0))
+    inputs = np.linspace(-1, 1, 41)
+    curves = ensemble.tuning_curves(inputs)
+    assert curves.shape == (15, 41)
+    assert (curves >= 0).all()
+    assert curves.max() <= 200.0 + 1e-9
+
+
+def test_decoders_reconstruct_the_input():
+    ensemble = neuro_rust.FirstOrderLifCollection(50)
+    inputs = np.linspace(-1, 1, 101)
+    decoders = ensemble.decoders(inputs)
+    assert decoders.shape == (50,)
+    estimate = ensemble.tuning_curves(inputs).T @ decoders
+    assert np.sqrt(np.mean((estimate - inputs) ** 2)) < 0.1
@@ -0,0 +1,116 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use neuro_rust::connectome::Connectome;
+use neuro_rust::connectome::graph_stats::GraphStats;
+use neuro_rust::emulations::c_elegans::behaviour::BehaviourSummary;
+use neuro_rust::emulations::c_elegans::dataset::Dataset;
+use neuro_rust::emulations::c_elegans::metrics::{Synchrony, TravellingWave, smoothed_trains};
+use neuro_rust::emulations::c_elegans::neuron_groups::{
+    MOTOR_NEURON_A, MOTOR_NEURON_B, motor_chain,
+};
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{EngineArgs, load_protocol, open_output, read_trace, run_protocol};
+
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// Analyze an existing trace instead of running a protocol
+    #[arg(long, conflicts_with = "protocol")]
+    trace: Option<PathBuf>,
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    /// Cycles per behaviour window
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+    /// Relative A/B lead needed to call a direction
+    #[arg(long, default_value_t = 0.2)]
+    margin: f64,
+    /// Cycles of smoothing before correlation and spectral analysis
+    #[arg(long, default_value_t = 5)]
+    bin: usize,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Report path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn analyze(args: AnalyzeArgs) -> Result<(), String> {
+    let (mut connectome, dataset) = args.engine.load()?;
+    let report = report(
+        args.trace.as_deref(),
+        &args.protocol,
+        args.window,
+        args.margin,
+        args.bin,
+        &mut connectome,
+        &dataset,
+    )?;
+    let mut out = open_output(&args.output)?;
+    write!(out, "{}", report).map_err(|err| err.to_string())
+}
+
+fn report(
+    trace: Option<&Path>,
+    protocol: &Option<PathBuf>,
+    window: usize,
+    margin: f64,
+    bin: usize,
+    connectome: &mut Connectome,
+    dataset: &Dataset,
+) -> Result<String, String> {
+    let trace = match trace {
+        Some(path) => read_trace(path)?,
+        None => run_protocol(
+            &load_protocol(protocol)?,
+            connectome,
+            dataset,
+            &["motor_ab".to_string()],
+        )?,
+    };
+    // A trace read back names the dataset it was recorded on, which may
+    // differ from the wiring the graph statistics describe
+    let mut source = format!("wiring             {} {}\n", dataset.name, dataset.version);
+    if let Some((name, version)) = &trace.dataset
+        && (name, version) != (&dataset.name, &dataset.version)
+    {
+        source += &format!("trace              {} {}\n", name, version);
+    }
+    let summary = BehaviourSummary::from_traces(
+        &trace.columns(&MOTOR_NEURON_A),
+        &trace.columns(&MOTOR_NEURON_B),
+        window,
+        margin,
+    );
+    let stats = GraphStats::from_edges(
+        connectome.neurons_tot(),
+        connectome.muscles_tot(),
+        &connectome.edges(),
+    );
+    let mut rhythm = String::new();
+    for (name, ids) in [("A", &MOTOR_NEURON_A[..]), ("B", &MOTOR_NEURON_B[..])] {
+        let synchrony = Synchrony::from_trains(&smoothed_trains(&trace, ids, bin));
+        rhythm += &format!(
+            "{} synchrony        correlation {:.3}  chi {:.3}\n",
+            name, synchrony.mean_correlation, synchrony.chi
+        );
+    }
+    for class in ["DB", "VB", "DA", "VA"] {
+        if let Some(wave) = TravellingWave::from_trace(&trace, &motor_chain(class), bin) {
+            rhythm += &format!("{}\n", wave);
+        }
+    }
+    Ok(format!(
+        "# dataset\n{}\n# behaviour ({} cycles)\n{}\n\n# rhythm\n{}\n# graph\n{}\nmost outputs       {}\nmost inputs        {}\n",
+        source,
+        trace.rows.len(),
+        summary,
+        rhythm,
+        stats,
+        cell_name(stats.max_out_degree.0),
+        cell_name(stats.max_in_degree.0)
+    ))
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::edge_list::Circuit;
+
+use crate::commands::open_output;
+
+#[derive(Args)]
+pub struct CircuitArgs {
+    wiring: PathBuf,
+    /// Cells or cell types pinged during the stimulus
+    #[arg(long, num_args = 1..)]
+    stimulate: Vec<String>,
+    /// Cycles under the stimulus, recorded like the rest
+    #[arg(long, default_value_t = 1)]
+    pulse: usize,
+    /// Recorded cycles, including the pulse
+    #[arg(short = 'n', long, default_value_t = 50)]
+    cycles: usize,
+    /// Cells or cell types to record (every cell by default)
+    #[arg(long, num_args = 1..)]
+    record: Vec<String>,
+    /// Override the threshold given in the file
+    #[arg(long)]
+    threshold: Option<i8>,
+    #[arg(long, default_value = "sequential")]
+    mode: String,
+    /// Trace path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn circuit(args: CircuitArgs) -> Result<(), String> {
+    let CircuitArgs {
+        wiring,
+        stimulate,
+        pulse,
+        cycles,
+        record,
+        threshold,
+        mode,
+        output,
+    } = args;
+    let mut connectome = Circuit::from_file(&wiring)?.connectome()?;
+    if let Some(threshold) = threshold {
+        connectome.set_threshold(threshold);
+    }
+    connectome.set_update_mode(mode.parse()?);
+    let cells = connectome.cells().clone();
+    let stimulus = cells.resolve(&stimulate)?;
+    if let Some(&muscle) = stimulus.iter().find(|&&id| cells.is_muscle(id)) {
+        return Err(format!(
+            "{} is a muscle; only neurons can be stimulated",
+            cells.name(muscle)
+        ));
+    }
+    let recorded = if record.is_empty() {
+        (0..cells.len()).collect()
+    } else {
+        cells.resolve(&record)?
+    };
+
+    let mut out = open_output(&output)?;
+    let names: Vec<String> = recorded.iter().map(|&id| cells.name(id)).collect();
+    writeln!(out, "# {}", names.join(" ")).map_err(|err| err.to_string())?;
+    let (muscles, neurons): (Vec<u16>, Vec<u16>) =
+        recorded.iter().partition(|&&id| cells.is_muscle(id));
+    let mut discharged = vec![0; neurons.len()];
+    let mut states = vec![0; muscles.len()];
+    for cycle in 0..cycles {
+        connectome.neural_cycle((cycle < pulse).then_some(stimulus.as_slice()));
+        connectome.discharge_query(&neurons, &mut discharged);
+        connectome.muscle_query(&muscles, &mut states);
+        let row: Vec<String> = recorded
+            .iter()
+            .map(|id| match neurons.iter().position(|n| n == id) {
+                Some(k) => discharged[k].to_string(),
+                None => states[muscles.iter().position(|m| m == id).unwrap()].to_string(),
+            })
+            .collect();
+        writeln!(out, "{}", row.join(" ")).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::edge_list::write_edge_list;
+use neuro_rust::connectome::graph_stats::GraphStats;
+use neuro_rust::emulations::c_elegans::csv_tables::write_csv_edges;
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{CommunityArgs, EngineArgs, create_dir, open_output, write_rom};
+
+#[derive(Args)]
+pub struct CommunitiesArgs {
+    #[command(flatten)]
+    partition: CommunityArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct SubcircuitArgs {
+    /// Cells, groups or modalities to keep
+    #[arg(long, num_args = 1.., required_unless_present = "module", conflicts_with = "module")]
+    cells: Vec<String>,
+    /// Module number as listed by `communities` with the same options
+    #[arg(long)]
+    module: Option<usize>,
+    #[command(flatten)]
+    partition: CommunityArgs,
+    /// Also keep the muscles the kept neurons synapse onto
+    #[arg(long)]
+    with_muscles: bool,
+    /// Keep the full C. elegans layout with every other cell ablated, so
+    /// the ROM runs with --rom and the usual names, groups and protocols
+    #[arg(long)]
+    keep_ids: bool,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(long, default_value = "subcircuit")]
+    output_dir: PathBuf,
+}
+
+pub fn communities(args: CommunitiesArgs) -> Result<(), String> {
+    let CommunitiesArgs {
+        partition,
+        engine,
+        output,
+    } = args;
+    let (wiring, dataset) = engine.load()?;
+    let communities = partition.detect(&wiring);
+    let mut out = open_output(&output)?;
+    let io = |err: std::io::Error| err.to_string();
+    writeln!(out, "dataset {} {}", dataset.name, dataset.version).map_err(io)?;
+    let modules: Vec<Vec<u16>> = (0..communities.modules())
+        .map(|m| communities.members(m))
+        .collect();
+    let shown: Vec<usize> = (0..modules.len())
+        .filter(|&m| modules[m].len() > 1)
+        .collect();
+    writeln!(
+        out,
+        "{} modules, modularity {:.4}",
+        modules.len(),
+        communities.modularity
+    )
+    .map_err(io)?;
+    for &m in &shown {
+        let names: Vec<String> = modules[m].iter().map(|&id| cell_name(id)).collect();
+        writeln!(
+            out,
+            "module {} ({} cells): {}",
+            m,
+            names.len(),
+            names.join(" ")
+        )
+        .map_err(io)?;
+    }
+    let single: Vec<String> = modules
+        .iter()
+        .filter(|cells| cells.len() == 1)
+        .map(|cells| cell_name(cells[0]))
+        .collect();
+    if !single.is_empty() {
+        writeln!(out, "single cells: {}", single.join(" ")).map_err(io)?;
+    }
+    writeln!(
+        out,
+        "connections between modules (edges/net weight, from row to column)"
+    )
+    .map_err(io)?;
+    let links = communities.links(&wiring.edges());
+    write!(out, "{:>6}", "").map_err(io)?;
+    for &b in &shown {
+        write!(out, " {:>10}", b).map_err(io)?;
+    }
+    writeln!(out).map_err(io)?;
+    for &a in &shown {
+        write!(out, "{:>6}", a).map_err(io)?;
+        for &b in &shown {
+            let link = links[a][b];
+            write!(out, " {:>10}", format!("{}/{}", link.edges, link.weight)).map_err(io)?;
+        }
+        writeln!(out).map_err(io)?;
+    }
+    out.flush().map_err(io)
+}
+
+pub fn subcircuit(args: SubcircuitArgs) -> Result<(), String> {
+    let SubcircuitArgs {
+        cells,
+        module,
+        partition,
+        with_muscles,
+        keep_ids,
+        engine,
+        output_dir,
+    } = args;
+    let (wiring, dataset) = engine.load()?;
+    let mut keep = match module {
+        Some(m) => {
+            let communities = partition.detect(&wiring);
+            if m >= communities.modules() {
+                return Err(format!(
+                    "Module {} does not exist, there are {}",
+                    m,
+                    communities.modules()
+                ));
+            }
+            communities.members(m)
+        }
+        None => resolve_targets(&cells)?,
+    };
+    if with_muscles {
+        let driven: Vec<u16> = wiring
+            .edges()
+            .iter()
+            .filter(|e| e.post >= wiring.neurons_tot() && keep.contains(&e.pre))
+            .map(|e| e.post)
+            .collect();
+        keep.extend(driven);
+    }
+    keep.sort_unstable();
+    keep.dedup();
+    let sub = if keep_ids {
+        wiring.subcircuit_keeping_ids(&keep)?
+    } else {
+        wiring.subcircuit(&keep)?
+    };
+    let sub = engine.configure(sub)?;
+    let edges = sub.edges();
+
+    create_dir(&output_dir)?;
+    write_rom(&output_dir.join("rom.rs"), sub.rom(), &dataset)?;
+    let create = |name: &str| {
+        let path = output_dir.join(name);
+        File::create(&path)
+            .map(BufWriter::new)
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    if keep_ids {
+        write_csv_edges(create("wiring.csv")?, &edges)?;
+    } else {
+        // The renumbered ROM no longer lines up with the C. elegans names;
+        // the edge list carries them and runs with `circuit`
+        let mut out = create("circuit.txt")?;
+        writeln!(out, "# dataset {} {}", dataset.name, dataset.version)
+            .map_err(|err| err.to_string())?;
+        write_edge_list(&mut out, &sub).map_err(|err| err.to_string())?;
+        out.flush().map_err(|err| err.to_string())?;
+    }
+    // One name per line: line k is the cell the ROM numbers k, or the cell
+    // of that id in the full layout with --keep-ids
+    let names: Vec<String> = if keep_ids {
+        keep.iter().map(|&id| cell_name(id)).collect()
+    } else {
+        sub.cells()
+            .iter()
+            .map(|(_, cell)| cell.name.clone())
+            .collect()
+    };
+    std::fs::write(output_dir.join("cells.txt"), names.join("\n") + "\n")
+        .map_err(|err| err.to_string())?;
+    println!("dataset {} {}", dataset.name, dataset.version);
+    println!("kept {} cells", keep.len());
+    println!(
+        "{}",
+        GraphStats::from_edges(sub.neurons_tot(), sub.muscles_tot(), &edges)
+    );
+    Ok(())
+}
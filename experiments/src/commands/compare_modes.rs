@@ -0,0 +1,42 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::mode_comparison::compare_update_modes;
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+
+use crate::commands::{EngineArgs, open_output};
+
+#[derive(Args)]
+pub struct CompareModesArgs {
+    /// Stimulus targets (none by default)
+    targets: Vec<String>,
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    cycles: usize,
+    /// Modes to compare, the first is the reference
+    #[arg(long, num_args = 1.., default_values_t = ["sequential".to_string(), "synchronous".to_string(), "random:0".to_string(), "event:2".to_string()])]
+    modes: Vec<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn compare_modes(args: CompareModesArgs) -> Result<(), String> {
+    let stimulus = resolve_targets(&args.targets)?;
+    let modes = args
+        .modes
+        .iter()
+        .map(|m| m.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    let stim = (!stimulus.is_empty()).then_some(stimulus.as_slice());
+    let (wiring, dataset) = args.engine.load()?;
+    let mut out = open_output(&args.output)?;
+    writeln!(out, "dataset {} {}", dataset.name, dataset.version).map_err(|err| err.to_string())?;
+    let build = || args.engine.rebuild(&wiring);
+    for divergence in compare_update_modes(&build, &modes, stim, args.cycles)? {
+        writeln!(out, "{}", divergence).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::emulations::c_elegans::dashboard::{Dashboard, DashboardParams, run_dashboard};
+
+use crate::commands::{EngineArgs, load_protocol};
+
+#[derive(Args)]
+pub struct DashboardArgs {
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    /// Milliseconds between frames
+    #[arg(long, default_value_t = 50)]
+    tick_ms: u64,
+    /// Cycles a key-injected stimulus lasts
+    #[arg(long, default_value_t = 100)]
+    inject_cycles: usize,
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+    #[arg(long, default_value_t = 0.2)]
+    margin: f64,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+pub fn dashboard(args: DashboardArgs) -> Result<(), String> {
+    let params = DashboardParams {
+        window: args.window,
+        margin: args.margin,
+        inject_cycles: args.inject_cycles,
+        tick: std::time::Duration::from_millis(args.tick_ms),
+    };
+    run_dashboard(Dashboard::new(
+        args.engine.build()?,
+        load_protocol(&args.protocol)?,
+        params,
+    ))
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::emulations::c_elegans::dataset::{DatasetDiff, DatasetRegistry};
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+#[derive(Args)]
+pub struct DatasetsArgs {
+    /// Registered names or descriptor paths: none lists, one reports, two
+    /// also compare
+    #[arg(num_args = 0..=2)]
+    names: Vec<String>,
+    /// Directory of dataset descriptors (*.toml) to register
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    /// Edges listed per difference in a comparison
+    #[arg(long, default_value_t = 20)]
+    show: usize,
+}
+
+pub fn datasets(args: DatasetsArgs) -> Result<(), String> {
+    let DatasetsArgs { names, dir, show } = args;
+    let mut registry = DatasetRegistry::default();
+    if let Some(dir) = &dir {
+        registry.add_dir(dir)?;
+    }
+    if names.is_empty() {
+        for spec in registry.specs() {
+            println!(
+                "{:<24} {:<24} {}",
+                spec.name, spec.version, spec.description
+            );
+        }
+        return Ok(());
+    }
+    let datasets = names
+        .iter()
+        .map(|name| registry.load(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    for dataset in &datasets {
+        println!(
+            "{} {} ({} edges)",
+            dataset.name,
+            dataset.version,
+            dataset.edges.len()
+        );
+        println!("{}", dataset.report);
+    }
+    if let [a, b] = datasets.as_slice() {
+        let diff = DatasetDiff::new(&a.edges, &b.edges);
+        let edge = |pre: u16, post: u16| format!("{} -> {}", cell_name(pre), cell_name(post));
+        println!(
+            "{} shared, {} reweighted, {} only in {}, {} only in {}",
+            diff.shared,
+            diff.changed.len(),
+            diff.only_a.len(),
+            a.name,
+            diff.only_b.len(),
+            b.name
+        );
+        for &(pre, post, wa, wb) in diff.changed.iter().take(show) {
+            println!("  ~ {} {} / {}", edge(pre, post), wa, wb);
+        }
+        for e in diff.only_a.iter().take(show) {
+            println!("  - {} {}", edge(e.pre, e.post), e.weight);
+        }
+        for e in diff.only_b.iter().take(show) {
+            println!("  + {} {}", edge(e.pre, e.post), e.weight);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use neuro_rust::connectome::Connectome;
+use neuro_rust::connectome::evolve::{EvolveParams, evolve_weights};
+use neuro_rust::connectome::rom_image::build_rom;
+use neuro_rust::emulations::c_elegans::csv_tables::write_csv_edges;
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+use neuro_rust::emulations::c_elegans::protocol::Protocol;
+use neuro_rust::emulations::c_elegans::screen::Readout;
+
+use crate::commands::{EngineArgs, create_dir, open_output, write_rom};
+
+/// Readout an evolved wiring maximises
+#[derive(Copy, Clone, ValueEnum)]
+enum EvolveObjective {
+    /// Forward minus backward fraction of behaviour windows
+    Chemotaxis,
+    /// B-class over A-class motor discharge balance
+    Ab,
+    /// Sum of both
+    Forward,
+}
+
+impl EvolveObjective {
+    fn score(self, readout: &Readout) -> f64 {
+        match self {
+            EvolveObjective::Chemotaxis => readout.chemotaxis_index,
+            EvolveObjective::Ab => readout.ab_balance,
+            EvolveObjective::Forward => readout.chemotaxis_index + readout.ab_balance,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct EvolveArgs {
+    /// Protocol to score (chemotaxis only by default)
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    #[arg(long, value_enum, default_value = "forward")]
+    objective: EvolveObjective,
+    #[arg(long, default_value_t = 32)]
+    population: usize,
+    #[arg(long, default_value_t = 20)]
+    generations: usize,
+    #[arg(long, default_value_t = 0.02)]
+    mutation_rate: f64,
+    #[arg(long, default_value_t = 4.)]
+    mutation_scale: f64,
+    /// Let weights change sign
+    #[arg(long)]
+    free_signs: bool,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+    #[arg(long, default_value_t = 0.2)]
+    margin: f64,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(long, default_value = "evolved")]
+    output_dir: PathBuf,
+}
+
+pub fn evolve(args: EvolveArgs) -> Result<(), String> {
+    let EvolveArgs {
+        protocol,
+        objective,
+        population,
+        generations,
+        mutation_rate,
+        mutation_scale,
+        free_signs,
+        seed,
+        window,
+        margin,
+        engine,
+        output_dir,
+    } = args;
+    let protocol = match protocol {
+        Some(path) => Protocol::from_file(&path)?,
+        None => Protocol::parse("burn 500 chemotaxis\nstimulate 1000 chemotaxis")?,
+    };
+    let (start, dataset) = engine.load()?;
+    let edges = start.edges();
+    let params = EvolveParams {
+        population,
+        generations,
+        mutation_rate,
+        mutation_scale,
+        keep_signs: !free_signs,
+        seed,
+        ..EvolveParams::default()
+    };
+    let (cells, threshold) = (start.neurons_tot() + start.muscles_tot(), start.threshold());
+    let build = |rom: Vec<u16>| {
+        let mut connectome = engine.build_from_rom(rom, cells)?;
+        connectome.set_threshold(threshold);
+        Ok(connectome)
+    };
+    let fitness = |connectome: &mut Connectome| {
+        let readout = Readout::measure(connectome, &protocol, window, margin)?;
+        Ok(objective.score(&readout))
+    };
+    let evolution = evolve_weights(
+        start.neurons_tot(),
+        &edges,
+        &build,
+        &fitness,
+        &params,
+        |stats| eprintln!("{}", stats),
+    )?;
+
+    create_dir(&output_dir)?;
+    let csv_path = output_dir.join("wiring.csv");
+    let csv = File::create(&csv_path).map_err(|err| format!("{}: {}", csv_path.display(), err))?;
+    write_csv_edges(BufWriter::new(csv), &evolution.best_edges)?;
+    write_rom(
+        &output_dir.join("rom.rs"),
+        &build_rom(start.neurons_tot(), &evolution.best_edges)?,
+        &dataset,
+    )?;
+
+    let mut out = open_output(&Some(output_dir.join("report.txt")))?;
+    let report = |out: &mut Box<dyn Write>| -> std::io::Result<()> {
+        writeln!(out, "dataset {} {}", dataset.name, dataset.version)?;
+        writeln!(out, "seed {}", seed)?;
+        writeln!(out, "initial fitness {:+.4}", evolution.initial_fitness)?;
+        writeln!(out, "best fitness    {:+.4}", evolution.best_fitness)?;
+        for stats in &evolution.history {
+            writeln!(out, "{}", stats)?;
+        }
+        writeln!(out, "# changed weights")?;
+        for (edge, old) in evolution.changed(&edges) {
+            writeln!(
+                out,
+                "{} -> {}  {:+} -> {:+}",
+                cell_name(edge.pre),
+                cell_name(edge.post),
+                old,
+                edge.weight
+            )?;
+        }
+        out.flush()
+    };
+    report(&mut out).map_err(|err| err.to_string())
+}
@@ -0,0 +1,31 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use neuro_rust::emulations::c_elegans::experiment::{ExperimentSpec, run_experiment};
+
+#[derive(Args)]
+pub struct ExperimentArgs {
+    spec: PathBuf,
+    /// Override the spec's output_dir
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+}
+
+pub fn experiment(args: ExperimentArgs) -> Result<(), String> {
+    let ExperimentArgs { spec, output_dir } = args;
+    let dir = match output_dir {
+        None => run_experiment(&spec)?,
+        Some(output_dir) => {
+            let text = std::fs::read_to_string(&spec)
+                .map_err(|err| format!("{}: {}", spec.display(), err))?;
+            let mut parsed = ExperimentSpec::parse(&text)?;
+            parsed.output_dir = std::env::current_dir()
+                .map_err(|err| err.to_string())?
+                .join(output_dir);
+            parsed.run(spec.parent().unwrap_or(Path::new(".")), &text)?
+        }
+    };
+    println!("{}", dir.display());
+    Ok(())
+}
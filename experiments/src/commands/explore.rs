@@ -0,0 +1,76 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use neuro_rust::emulations::c_elegans::explorer::{Layout, write_explorer};
+
+use crate::commands::{EngineArgs, load_protocol, open_output, read_trace, run_protocol};
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ExploreLayout {
+    /// Sensory, inter, motor and muscle columns
+    Layered,
+    Force,
+}
+
+#[derive(Args)]
+pub struct ExploreArgs {
+    #[arg(long, value_enum, default_value = "layered")]
+    layout: ExploreLayout,
+    /// Force layout iterations
+    #[arg(long, default_value_t = 300)]
+    iterations: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Trace to replay instead of running the protocol
+    #[arg(long, conflicts_with = "protocol")]
+    trace: Option<PathBuf>,
+    /// Protocol whose recorded cycles are replayed (the default
+    /// chemotaxis/nose-touch run if neither this nor --trace is given)
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    /// Leave out the replay
+    #[arg(long, conflicts_with_all = ["trace", "protocol"])]
+    no_replay: bool,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long, default_value = "explorer.html")]
+    output: PathBuf,
+}
+
+pub fn explore(args: ExploreArgs) -> Result<(), String> {
+    let ExploreArgs {
+        layout,
+        iterations,
+        seed,
+        trace,
+        protocol,
+        no_replay,
+        engine,
+        output,
+    } = args;
+    let (connectome, dataset) = engine.load()?;
+    let replay = match (&trace, no_replay) {
+        (_, true) => None,
+        (Some(path), _) => Some(read_trace(path)?),
+        (None, _) => {
+            let neurons: Vec<String> = (0..connectome.neurons_tot())
+                .map(|id| connectome.cells().name(id))
+                .collect();
+            Some(run_protocol(
+                &load_protocol(&protocol)?,
+                &mut engine.rebuild(&connectome)?,
+                &dataset,
+                &neurons,
+            )?)
+        }
+    };
+    let layout = match layout {
+        ExploreLayout::Layered => Layout::Layered,
+        ExploreLayout::Force => Layout::Force { iterations, seed },
+    };
+    let mut out = open_output(&Some(output))?;
+    write_explorer(&mut out, &connectome, layout, replay.as_ref())?;
+    out.flush().map_err(|err| err.to_string())
+}
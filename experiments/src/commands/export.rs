@@ -0,0 +1,85 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use neuro_rust::connectome::edge_list::write_edge_list;
+use neuro_rust::connectome::export::write_graphml;
+use neuro_rust::connectome::hybrid::HybridParams;
+use neuro_rust::connectome::neuroml::NeuroMlNetwork;
+use neuro_rust::connectome::rom_image::{build_rom, rom_to_text};
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{EngineArgs, open_output};
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ExportFormat {
+    Graphml,
+    Rom,
+    /// NeuroML 2 with the hybrid engine's LIF and synapse parameters
+    Neuroml,
+    /// Named cells and weighted edges, loadable with `circuit`
+    EdgeList,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum)]
+    format: ExportFormat,
+    /// Lesion these targets before exporting
+    #[arg(long, num_args = 1..)]
+    ablate: Vec<String>,
+    /// Targets given a stimulus input (NeuroML only; defaults to the inputs
+    /// of a --neuroml source)
+    #[arg(long, num_args = 1..)]
+    inputs: Vec<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Output path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn export(args: ExportArgs) -> Result<(), String> {
+    let mut connectome = args.engine.build()?;
+    for id in resolve_targets(&args.ablate)? {
+        connectome.ablate(id);
+    }
+    let mut out = open_output(&args.output)?;
+    match args.format {
+        ExportFormat::Graphml => write_graphml(
+            &mut out,
+            connectome.neurons_tot() + connectome.muscles_tot(),
+            connectome.neurons_tot(),
+            &connectome.edges(),
+            cell_name,
+        )
+        .map_err(|err| err.to_string()),
+        ExportFormat::Rom => {
+            let rom = build_rom(connectome.neurons_tot(), &connectome.edges())?;
+            write!(out, "{}", rom_to_text(&rom)).map_err(|err| err.to_string())
+        }
+        ExportFormat::Neuroml => {
+            // A NeuroML source keeps its LIF parameters and inputs
+            let source = args.engine.neuroml_network()?;
+            let inputs = match &source {
+                Some(network) if args.inputs.is_empty() => network.inputs.clone(),
+                _ => resolve_targets(&args.inputs)?,
+            };
+            NeuroMlNetwork {
+                id: source.as_ref().map_or("c_elegans", |n| &n.id).to_string(),
+                neurons_tot: connectome.neurons_tot(),
+                cells: connectome.neurons_tot() + connectome.muscles_tot(),
+                edges: connectome.edges(),
+                threshold: Some(connectome.threshold()),
+                params: source.map_or_else(HybridParams::default, |n| n.params),
+                inputs,
+            }
+            .write(&mut out, |id| connectome.cells().name(id))
+        }
+        ExportFormat::EdgeList => {
+            write_edge_list(&mut out, &connectome).map_err(|err| err.to_string())
+        }
+    }
+}
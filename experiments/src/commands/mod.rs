@@ -0,0 +1,257 @@
+//! One module per subcommand, plus the options and helpers they share.
+
+pub mod analyze;
+pub mod circuit;
+pub mod communities;
+pub mod compare_modes;
+pub mod dashboard;
+pub mod datasets;
+pub mod evolve;
+pub mod experiment;
+pub mod explore;
+pub mod export;
+pub mod null_models;
+pub mod reach;
+pub mod reservoir;
+pub mod robot;
+pub mod run;
+pub mod screen;
+pub mod serve;
+pub mod spectral;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use neuro_rust::connectome::Connectome;
+use neuro_rust::connectome::community::{Communities, louvain};
+use neuro_rust::connectome::neuroml::NeuroMlNetwork;
+use neuro_rust::connectome::null_model::NullModel;
+use neuro_rust::connectome::rom_image::{build_rom, read_rom_file, rom_to_text};
+use neuro_rust::emulations::c_elegans::csv_tables::{NEURONS_TOT, builtin_gap_junctions};
+use neuro_rust::emulations::c_elegans::dataset::{Dataset, DatasetRegistry};
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::neuron_ids::CELLS;
+use neuro_rust::emulations::c_elegans::preset;
+use neuro_rust::emulations::c_elegans::protocol::Protocol;
+use neuro_rust::emulations::c_elegans::rom::ROM;
+use neuro_rust::emulations::c_elegans::trace::DischargeTrace;
+
+#[derive(Args)]
+pub struct EngineArgs {
+    /// ROM image to load instead of the built-in C. elegans ROM
+    #[arg(long)]
+    rom: Option<PathBuf>,
+    /// NeuroML 2 network (as written by `export --format neuroml`) to load
+    /// instead of the built-in C. elegans ROM
+    #[arg(long, conflicts_with = "rom")]
+    neuroml: Option<PathBuf>,
+    /// Dataset (registered name such as "bundled", or a dataset descriptor)
+    /// to load instead of the built-in C. elegans ROM
+    #[arg(long, conflicts_with_all = ["rom", "neuroml"])]
+    dataset: Option<String>,
+    #[arg(long)]
+    threshold: Option<i8>,
+    /// sequential, synchronous, random:<seed> or event:<delay>
+    #[arg(long, default_value = "sequential")]
+    mode: String,
+    /// Replace the wiring by a null model of it: rewire, rewire:<swaps per
+    /// edge> or erdos_renyi
+    #[arg(long)]
+    null_model: Option<NullModel>,
+    #[arg(long, default_value_t = 0)]
+    null_seed: u64,
+}
+
+impl EngineArgs {
+    pub fn build(&self) -> Result<Connectome, String> {
+        self.load().map(|(connectome, _)| connectome)
+    }
+
+    /// Build the connectome along with the dataset it came from; wirings
+    /// not loaded from a dataset get a stand-in naming their source.
+    pub fn load(&self) -> Result<(Connectome, Dataset), String> {
+        let (rom, cells, threshold, dataset) = match (&self.rom, &self.neuroml, &self.dataset) {
+            (Some(path), _, _) => (
+                read_rom_file(path)?,
+                CELLS,
+                None,
+                Dataset::stand_in(path.display().to_string(), "unversioned"),
+            ),
+            (_, Some(path), _) => {
+                let network = read_neuroml(path)?;
+                (
+                    build_rom(network.neurons_tot, &network.edges)?,
+                    network.cells,
+                    network.threshold,
+                    Dataset::stand_in(path.display().to_string(), "unversioned"),
+                )
+            }
+            (_, _, Some(name)) => {
+                let dataset = DatasetRegistry::default().load(name)?;
+                (
+                    build_rom(NEURONS_TOT, &dataset.edges)?,
+                    CELLS,
+                    None,
+                    dataset,
+                )
+            }
+            (None, None, None) => (
+                ROM.to_vec(),
+                CELLS,
+                None,
+                // The built-in ROM was wired from the bundled tables
+                Dataset {
+                    gap_junctions: builtin_gap_junctions(),
+                    ..Dataset::stand_in("builtin_rom", env!("CARGO_PKG_VERSION"))
+                },
+            ),
+        };
+        let mut connectome = match &self.null_model {
+            Some(model) => {
+                let wiring = Connectome::from_rom(rom, cells)?;
+                let edges =
+                    model.generate(wiring.neurons_tot(), cells, &wiring.edges(), self.null_seed)?;
+                self.build_from_rom(build_rom(wiring.neurons_tot(), &edges)?, cells)?
+            }
+            None => self.build_from_rom(rom, cells)?,
+        };
+        // An explicit --threshold wins over the one stored in the document
+        if let (Some(threshold), None) = (threshold, self.threshold) {
+            connectome.set_threshold(threshold);
+        }
+        Ok((connectome, dataset))
+    }
+
+    /// The NeuroML document given with `--neuroml`, for the LIF parameters
+    /// and inputs the integer engine has no use for
+    pub fn neuroml_network(&self) -> Result<Option<NeuroMlNetwork>, String> {
+        self.neuroml.as_deref().map(read_neuroml).transpose()
+    }
+
+    /// A fresh engine on the ROM, cells and threshold of `wiring`, which
+    /// was loaded already, without reading its source again
+    pub fn rebuild(&self, wiring: &Connectome) -> Result<Connectome, String> {
+        let mut connectome = Connectome::from_rom(
+            wiring.rom().to_vec(),
+            wiring.neurons_tot() + wiring.muscles_tot(),
+        )?
+        .with_cells(wiring.cells().clone())?;
+        connectome.set_threshold(wiring.threshold());
+        connectome.set_update_mode(self.mode.parse()?);
+        Ok(connectome)
+    }
+
+    /// Build a ROM of `cells` cells with these settings
+    pub fn build_from_rom(&self, rom: Vec<u16>, cells: u16) -> Result<Connectome, String> {
+        self.configure(preset::with_names(Connectome::from_rom(rom, cells)?)?)
+    }
+
+    pub fn configure(&self, mut connectome: Connectome) -> Result<Connectome, String> {
+        if let Some(threshold) = self.threshold {
+            connectome.set_threshold(threshold);
+        }
+        connectome.set_update_mode(self.mode.parse()?);
+        Ok(connectome)
+    }
+}
+
+#[derive(Args)]
+pub struct CommunityArgs {
+    /// Louvain resolution; above 1 gives smaller modules
+    #[arg(long, default_value_t = 1.0)]
+    resolution: f64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Partition muscles along with the neurons
+    #[arg(long)]
+    muscles: bool,
+}
+
+impl CommunityArgs {
+    pub fn detect(&self, connectome: &Connectome) -> Communities {
+        let cells = if self.muscles {
+            CELLS
+        } else {
+            connectome.neurons_tot()
+        };
+        let cells: Vec<u16> = (0..cells).collect();
+        louvain(&cells, &connectome.edges(), self.resolution, self.seed)
+    }
+}
+
+#[derive(Args)]
+pub struct TraceArgs {
+    /// Trace path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Neurons to record
+    #[arg(long, num_args = 1.., default_value = "motor_ab")]
+    record: Vec<String>,
+}
+
+pub fn read_neuroml(path: &Path) -> Result<NeuroMlNetwork, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    NeuroMlNetwork::read(&text, |name| preset::cell_registry().id(name))
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn open_output(path: &Option<PathBuf>) -> Result<Box<dyn Write>, String> {
+    match path {
+        Some(path) => File::create(path)
+            .map(|f| Box::new(BufWriter::new(f)) as Box<dyn Write>)
+            .map_err(|err| format!("{}: {}", path.display(), err)),
+        None => Ok(Box::new(std::io::stdout().lock())),
+    }
+}
+
+pub fn run_protocol(
+    protocol: &Protocol,
+    connectome: &mut Connectome,
+    dataset: &Dataset,
+    record: &[String],
+) -> Result<DischargeTrace, String> {
+    let mut trace = DischargeTrace::new(resolve_targets(record)?).with_dataset(dataset);
+    protocol.run(connectome, |c| {
+        trace.record(c);
+        Ok(())
+    })?;
+    Ok(trace)
+}
+
+pub fn write_trace(trace: &DischargeTrace, output: &TraceArgs) -> Result<(), String> {
+    let mut out = open_output(&output.output)?;
+    trace.write(&mut out).map_err(|err| err.to_string())?;
+    out.flush().map_err(|err| err.to_string())
+}
+
+pub fn read_trace(path: &Path) -> Result<DischargeTrace, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    DischargeTrace::read(BufReader::new(file)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn load_protocol(path: &Option<PathBuf>) -> Result<Protocol, String> {
+    path.as_deref().map_or_else(
+        || Ok(Protocol::chemotaxis_then_touch()),
+        Protocol::from_file,
+    )
+}
+
+/// Create an output directory, naming it in the error
+/// Write a `rom.rs`-style ROM image headed by the dataset it came from
+pub fn write_rom(path: &Path, rom: &[u16], dataset: &Dataset) -> Result<(), String> {
+    let text = format!(
+        "// dataset {} {}\n{}",
+        dataset.name,
+        dataset.version,
+        rom_to_text(rom)
+    );
+    std::fs::write(path, text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn create_dir(dir: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))
+}
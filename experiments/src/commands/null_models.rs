@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::null_model::NullModel;
+use neuro_rust::connectome::rom_image::build_rom;
+use neuro_rust::emulations::c_elegans::csv_tables::write_csv_edges;
+
+use crate::commands::{EngineArgs, create_dir, write_rom};
+
+#[derive(Args)]
+pub struct NullModelsArgs {
+    /// rewire, rewire:<swaps per edge> or erdos_renyi
+    #[arg(long, default_value = "rewire")]
+    model: NullModel,
+    #[arg(long, default_value_t = 10)]
+    count: usize,
+    /// Seed of the first model; the others follow on
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(long, default_value = "null_models")]
+    output_dir: PathBuf,
+}
+
+pub fn null_models(args: NullModelsArgs) -> Result<(), String> {
+    let NullModelsArgs {
+        model,
+        count,
+        seed,
+        engine,
+        output_dir,
+    } = args;
+    let (wiring, dataset) = engine.load()?;
+    let cells = wiring.neurons_tot() + wiring.muscles_tot();
+    create_dir(&output_dir)?;
+    let name = model.to_string().replace(':', "");
+    for seed in seed..seed + count as u64 {
+        let edges = model.generate(wiring.neurons_tot(), cells, &wiring.edges(), seed)?;
+        let stem = format!("{}-{}", name, seed);
+        let csv_path = output_dir.join(format!("{}.csv", stem));
+        let csv =
+            File::create(&csv_path).map_err(|err| format!("{}: {}", csv_path.display(), err))?;
+        write_csv_edges(BufWriter::new(csv), &edges)?;
+        write_rom(
+            &output_dir.join(format!("{}.rs", stem)),
+            &build_rom(wiring.neurons_tot(), &edges)?,
+            &dataset,
+        )?;
+    }
+    Ok(())
+}
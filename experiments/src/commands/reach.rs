@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use neuro_rust::connectome::propagation::{Reach, dynamic_reach, structural_reach};
+use neuro_rust::emulations::c_elegans::neuron_groups::{
+    CellClass, cell_classes, resolve_targets, sensory_functions,
+};
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{EngineArgs, create_dir};
+
+#[derive(Args)]
+pub struct ReachArgs {
+    /// Cells, groups or modalities stimulated one at a time (all sensory
+    /// neurons if not given)
+    #[arg(long, num_args = 1..)]
+    sources: Vec<String>,
+    /// Cells, groups or modalities to time (all motor neurons and
+    /// muscles if not given)
+    #[arg(long, num_args = 1..)]
+    targets: Vec<String>,
+    /// Cycles each source is stimulated for before giving up
+    #[arg(long, default_value_t = 1000)]
+    max_cycles: usize,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(long, default_value = "reach")]
+    output_dir: PathBuf,
+}
+
+/// Latency matrix as CSV: one row per source, one column per target, empty
+/// where the target is never reached
+fn write_latencies(path: &Path, reaches: &[Reach], targets: &[u16]) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut writer = csv::Writer::from_writer(BufWriter::new(file));
+    let header = std::iter::once("source".to_string()).chain(targets.iter().map(|&t| cell_name(t)));
+    writer.write_record(header).map_err(|err| err.to_string())?;
+    for reach in reaches {
+        let source = reach
+            .sources
+            .iter()
+            .map(|&s| cell_name(s))
+            .collect::<Vec<_>>()
+            .join("+");
+        let row = std::iter::once(source).chain(
+            targets
+                .iter()
+                .map(|&t| reach.latency[t as usize].map_or(String::new(), |l| l.to_string())),
+        );
+        writer.write_record(row).map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())
+}
+
+pub fn reach(args: ReachArgs) -> Result<(), String> {
+    let ReachArgs {
+        sources,
+        targets,
+        max_cycles,
+        engine,
+        output_dir,
+    } = args;
+    let (wiring, dataset) = engine.load()?;
+    let neurons_tot = wiring.neurons_tot();
+    let cells = neurons_tot + wiring.muscles_tot();
+    let edges = wiring.edges();
+    let classes = cell_classes(neurons_tot, cells, &edges);
+    let sources = if sources.is_empty() {
+        sensory_functions().into_keys().collect()
+    } else {
+        resolve_targets(&sources)?
+    };
+    let targets: Vec<u16> = if targets.is_empty() {
+        (0..cells)
+            .filter(|&id| matches!(classes[id as usize], CellClass::Motor | CellClass::Muscle))
+            .collect()
+    } else {
+        resolve_targets(&targets)?
+    };
+    let muscles: Vec<u16> = targets
+        .iter()
+        .copied()
+        .filter(|&t| t >= neurons_tot)
+        .collect();
+
+    let structural: Vec<Reach> = sources
+        .iter()
+        .map(|&s| structural_reach(cells, &edges, &[s]))
+        .collect();
+    let mut dynamic = vec![];
+    for &s in &sources {
+        dynamic.push(dynamic_reach(
+            &mut engine.rebuild(&wiring)?,
+            &[s],
+            max_cycles,
+        ));
+    }
+
+    create_dir(&output_dir)?;
+    write_latencies(&output_dir.join("structural.csv"), &structural, &targets)?;
+    write_latencies(&output_dir.join("dynamic.csv"), &dynamic, &targets)?;
+    let paths_file = output_dir.join("paths.txt");
+    let mut paths = BufWriter::new(
+        File::create(&paths_file).map_err(|err| format!("{}: {}", paths_file.display(), err))?,
+    );
+    writeln!(paths, "dataset {} {}", dataset.name, dataset.version)
+        .map_err(|err| err.to_string())?;
+    let route = |reach: &Reach, target: u16| match reach.path_to(target) {
+        Some(path) => format!(
+            "{:>4}  {}",
+            reach.latency[target as usize].unwrap_or(0),
+            path.iter()
+                .map(|&id| cell_name(id))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        ),
+        None => "   -  unreached".to_string(),
+    };
+    for (s, d) in structural.iter().zip(&dynamic) {
+        for &m in &muscles {
+            writeln!(
+                paths,
+                "{} -> {}\n  hops    {}\n  cycles  {}",
+                cell_name(s.sources[0]),
+                cell_name(m),
+                route(s, m),
+                route(d, m)
+            )
+            .map_err(|err| err.to_string())?;
+        }
+    }
+    paths.flush().map_err(|err| err.to_string())?;
+
+    let mut finite: Vec<u32> = dynamic
+        .iter()
+        .flat_map(|r| targets.iter().filter_map(|&t| r.latency[t as usize]))
+        .collect();
+    finite.sort_unstable();
+    let pairs = sources.len() * targets.len();
+    println!("dataset {} {}", dataset.name, dataset.version);
+    println!(
+        "{} of {} pairs reached structurally, {} within {} cycles",
+        structural
+            .iter()
+            .map(|r| targets
+                .iter()
+                .filter(|&&t| r.latency[t as usize].is_some())
+                .count())
+            .sum::<usize>(),
+        pairs,
+        finite.len(),
+        max_cycles
+    );
+    if let Some(&max) = finite.last() {
+        let p95 = finite[(finite.len() - 1) * 95 / 100];
+        println!(
+            "dynamic latency: median {}, 95th percentile {}, max {} cycles",
+            finite[finite.len() / 2],
+            p95,
+            max
+        );
+        println!(
+            "suggested burn-in: {} cycles, enough for 95% of reached pairs",
+            p95
+        );
+    }
+    Ok(())
+}
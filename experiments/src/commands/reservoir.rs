@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::reservoir::{
+    ReservoirInput, RidgeReadout, collect_states, low_pass, random_levels,
+};
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{EngineArgs, open_output};
+
+#[derive(Args)]
+pub struct ReservoirArgs {
+    /// Neurons receiving the signal
+    #[arg(long, num_args = 1.., default_value = "chemotaxis")]
+    inputs: Vec<String>,
+    /// input, delay:<cycles> (recall the signal) or product:<cycles>
+    /// (the signal times its delayed self)
+    #[arg(long, default_value = "delay:10")]
+    task: String,
+    #[arg(short = 'n', long, default_value_t = 4000)]
+    cycles: usize,
+    /// Cycles each random signal level is held
+    #[arg(long, default_value_t = 10)]
+    hold: usize,
+    #[arg(long, default_value_t = 200)]
+    washout: usize,
+    /// Fraction of the cycles after washout used for training
+    #[arg(long, default_value_t = 0.7)]
+    train: f64,
+    #[arg(long, default_value_t = 100.)]
+    ridge: f64,
+    /// Time constant in cycles of the low-pass filter on the states
+    #[arg(long, default_value_t = 3.)]
+    tau: f64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Target signal of a reservoir task computed from its input
+fn reservoir_target(task: &str, signal: &[f64]) -> Result<Vec<f64>, String> {
+    let (name, arg) = task.split_once(':').unwrap_or((task, "0"));
+    let delay: usize = arg.parse().map_err(|err| format!("{}: {}", task, err))?;
+    let delayed = |t: usize| t.checked_sub(delay).map_or(0., |d| signal[d]);
+    match name {
+        "input" | "delay" => Ok((0..signal.len()).map(delayed).collect()),
+        "product" => Ok((0..signal.len()).map(|t| signal[t] * delayed(t)).collect()),
+        _ => Err(format!("Unknown reservoir task {}", task)),
+    }
+}
+
+pub fn reservoir(args: ReservoirArgs) -> Result<(), String> {
+    let signal = random_levels(args.cycles, args.hold, args.seed);
+    let target = reservoir_target(&args.task, &signal)?;
+    let (mut connectome, dataset) = args.engine.load()?;
+    let input = ReservoirInput {
+        neurons: resolve_targets(&args.inputs)?,
+        signal,
+    };
+    let states = low_pass(
+        &collect_states(&mut connectome, &[input], args.cycles),
+        args.tau,
+    );
+    let readout = RidgeReadout::train(&states, &target, args.washout, args.train, args.ridge)?;
+    let mut out = open_output(&args.output)?;
+    let report = |out: &mut Box<dyn Write>| -> std::io::Result<()> {
+        writeln!(out, "dataset {} {}", dataset.name, dataset.version)?;
+        writeln!(out, "task {}  {}", args.task, readout)?;
+        writeln!(out, "# weights")?;
+        let (bias, weights) = readout.weights.split_last().expect("bias weight");
+        for (id, w) in weights.iter().enumerate() {
+            writeln!(out, "{},{}", cell_name(id as u16), w)?;
+        }
+        writeln!(out, "bias,{}", bias)?;
+        out.flush()
+    };
+    report(&mut out).map_err(|err| err.to_string())
+}
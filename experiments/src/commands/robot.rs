@@ -0,0 +1,63 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::muscle_model::MuscleParams;
+use neuro_rust::emulations::c_elegans::neuron_groups::body_wall_muscle_model;
+use neuro_rust::emulations::c_elegans::worm_robot::{WormRobot, WormRobotParams};
+use neuro_rust::robot::{Arena, Pose, RobotParams, RobotSim, write_trajectory};
+
+use crate::commands::{EngineArgs, open_output};
+
+#[derive(Args)]
+pub struct RobotArgs {
+    #[arg(short = 'n', long, default_value_t = 2000)]
+    cycles: usize,
+    /// Side of the square arena
+    #[arg(long, default_value_t = 3.)]
+    size: f32,
+    /// Round obstacles as x,y,radius
+    #[arg(long, num_args = 1.., default_values_t = ["2,1.5,0.3".to_string()])]
+    obstacles: Vec<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    /// Trajectory path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn robot(args: RobotArgs) -> Result<(), String> {
+    let RobotArgs {
+        cycles,
+        size,
+        obstacles,
+        engine,
+        output,
+    } = args;
+    let mut arena = Arena::new(size, size);
+    for obstacle in &obstacles {
+        let values = obstacle
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("{}: {}", obstacle, err))?;
+        let [x, y, radius] = values[..] else {
+            return Err(format!("{}: expected x,y,radius", obstacle));
+        };
+        arena = arena.with_obstacle(x, y, radius);
+    }
+    let start = Pose {
+        x: size / 4.,
+        y: size / 2.,
+        heading: 0.,
+    };
+    let mut sim = RobotSim::new(arena, RobotParams::default(), start);
+    let (mut connectome, dataset) = engine.load()?;
+    connectome.set_muscle_model(Some(body_wall_muscle_model(MuscleParams::default())))?;
+    let samples = WormRobot::new(WormRobotParams::default()).run(&mut connectome, &mut sim, cycles);
+    let mut out = open_output(&output)?;
+    writeln!(out, "# dataset {} {}", dataset.name, dataset.version)
+        .map_err(|err| err.to_string())?;
+    write_trajectory(&mut out, &samples).map_err(|err| err.to_string())
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::protocol::{Protocol, ProtocolStep};
+
+use crate::commands::{EngineArgs, TraceArgs, load_protocol, run_protocol, write_trace};
+
+#[derive(Args)]
+pub struct RunArgs {
+    protocol: PathBuf,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: TraceArgs,
+}
+
+#[derive(Args)]
+pub struct StimulateArgs {
+    #[arg(required = true)]
+    targets: Vec<String>,
+    /// Recorded cycles
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    cycles: usize,
+    /// Unrecorded cycles under the same stimulus before recording
+    #[arg(long, default_value_t = 0)]
+    burn_in: usize,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: TraceArgs,
+}
+
+#[derive(Args)]
+pub struct AblateArgs {
+    #[arg(required = true)]
+    targets: Vec<String>,
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: TraceArgs,
+}
+
+pub fn run(args: RunArgs) -> Result<(), String> {
+    let (mut connectome, dataset) = args.engine.load()?;
+    let trace = run_protocol(
+        &Protocol::from_file(&args.protocol)?,
+        &mut connectome,
+        &dataset,
+        &args.output.record,
+    )?;
+    write_trace(&trace, &args.output)
+}
+
+pub fn stimulate(args: StimulateArgs) -> Result<(), String> {
+    let stimulus = resolve_targets(&args.targets)?;
+    let protocol = Protocol {
+        steps: vec![
+            ProtocolStep::Run {
+                cycles: args.burn_in,
+                stimulus: stimulus.clone(),
+                record: false,
+            },
+            ProtocolStep::Run {
+                cycles: args.cycles,
+                stimulus,
+                record: true,
+            },
+        ],
+    };
+    let (mut connectome, dataset) = args.engine.load()?;
+    let trace = run_protocol(&protocol, &mut connectome, &dataset, &args.output.record)?;
+    write_trace(&trace, &args.output)
+}
+
+pub fn ablate(args: AblateArgs) -> Result<(), String> {
+    let mut protocol = load_protocol(&args.protocol)?;
+    protocol
+        .steps
+        .insert(0, ProtocolStep::Ablate(resolve_targets(&args.targets)?));
+    let (mut connectome, dataset) = args.engine.load()?;
+    let trace = run_protocol(&protocol, &mut connectome, &dataset, &args.output.record)?;
+    write_trace(&trace, &args.output)
+}
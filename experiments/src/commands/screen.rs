@@ -0,0 +1,108 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use neuro_rust::emulations::c_elegans::neuron_groups::resolve_targets;
+use neuro_rust::emulations::c_elegans::screen::{
+    RankBy, pair_lesions, perturbation_screen, single_lesions,
+};
+
+use crate::commands::{EngineArgs, load_protocol, open_output};
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ScreenRank {
+    Combined,
+    Ab,
+    Asymmetry,
+    Chemotaxis,
+}
+
+impl From<ScreenRank> for RankBy {
+    fn from(rank: ScreenRank) -> Self {
+        match rank {
+            ScreenRank::Combined => RankBy::Combined,
+            ScreenRank::Ab => RankBy::AbBalance,
+            ScreenRank::Asymmetry => RankBy::MuscleAsymmetry,
+            ScreenRank::Chemotaxis => RankBy::ChemotaxisIndex,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ScreenArgs {
+    /// Neurons to lesion (all neurons by default)
+    candidates: Vec<String>,
+    #[arg(long)]
+    protocol: Option<PathBuf>,
+    /// Also lesion every pair among the top N single lesions
+    #[arg(long, default_value_t = 0)]
+    pairs: usize,
+    #[arg(long, value_enum, default_value = "combined")]
+    rank: ScreenRank,
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+    #[arg(long, default_value_t = 0.2)]
+    margin: f64,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn screen(args: ScreenArgs) -> Result<(), String> {
+    let ScreenArgs {
+        candidates,
+        protocol,
+        pairs,
+        rank,
+        window,
+        margin,
+        engine,
+        output,
+    } = args;
+    let protocol = load_protocol(&protocol)?;
+    let (wiring, dataset) = engine.load()?;
+    let candidates = if candidates.is_empty() {
+        (0..wiring.neurons_tot()).collect()
+    } else {
+        resolve_targets(&candidates)?
+    };
+    let build = || engine.rebuild(&wiring);
+    let mut screen = perturbation_screen(
+        &build,
+        &protocol,
+        &single_lesions(&candidates),
+        rank.into(),
+        window,
+        margin,
+    )?;
+    if pairs > 1 {
+        let top: Vec<u16> = screen
+            .effects
+            .iter()
+            .take(pairs)
+            .map(|e| e.lesion[0])
+            .collect();
+        let paired = perturbation_screen(
+            &build,
+            &protocol,
+            &pair_lesions(&top),
+            rank.into(),
+            window,
+            margin,
+        )?;
+        screen.effects.extend(paired.effects);
+        let rank_by = screen.rank_by;
+        screen
+            .effects
+            .sort_by(|a, b| b.effect(rank_by).total_cmp(&a.effect(rank_by)));
+    }
+    let mut out = open_output(&output)?;
+    write!(
+        out,
+        "dataset        {} {}\n{}",
+        dataset.name, dataset.version, screen
+    )
+    .map_err(|err| err.to_string())
+}
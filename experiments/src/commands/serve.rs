@@ -0,0 +1,17 @@
+use clap::Args;
+
+use neuro_rust::emulations::c_elegans::server;
+
+use crate::commands::EngineArgs;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    addr: String,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+pub fn serve(args: ServeArgs) -> Result<(), String> {
+    server::serve(args.addr.as_str(), args.engine.build()?)
+}
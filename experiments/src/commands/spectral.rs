@@ -0,0 +1,204 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use neuro_rust::connectome::spectral::{
+    GramianSummary, adjacency, controllability_gramian, eigenvalues, eigenvector, laplacian,
+    laplacian_spectrum, observability_gramian, selection,
+};
+use neuro_rust::emulations::c_elegans::csv_tables::gap_junctions_from_csv;
+use neuro_rust::emulations::c_elegans::neuron_groups::{
+    CellClass, cell_classes, resolve_targets, sensory_functions,
+};
+use neuro_rust::emulations::c_elegans::neuron_ids::cell_name;
+
+use crate::commands::{EngineArgs, open_output};
+
+#[derive(Args)]
+pub struct SpectralArgs {
+    /// Cells, groups or modalities of the subnetwork (all neurons if not
+    /// given)
+    #[arg(long, num_args = 1..)]
+    cells: Vec<String>,
+    /// Input cells of the Gramians (all sensory neurons if not given)
+    #[arg(long, num_args = 1..)]
+    inputs: Vec<String>,
+    /// Output cells of the Gramians (all motor neurons if not given)
+    #[arg(long, num_args = 1..)]
+    outputs: Vec<String>,
+    /// Dominant eigenvectors to list
+    #[arg(long, default_value_t = 3)]
+    eigenvectors: usize,
+    /// Cycles summed in the finite-horizon Gramians
+    #[arg(long, default_value_t = 50)]
+    horizon: usize,
+    /// Spectral radius the adjacency is scaled to for the Gramians; 0
+    /// keeps the raw weights
+    #[arg(long, default_value_t = 0.9)]
+    radius: f64,
+    /// Connectome.csv to take gap junctions from (those of the loaded
+    /// dataset if not given)
+    #[arg(long)]
+    gap_csv: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// The `count` cells with the largest `scores`, as "NAME value" pairs
+fn top_cells(cells: &[u16], scores: &[f64], count: usize) -> String {
+    let mut order: Vec<usize> = (0..cells.len()).collect();
+    order.sort_by(|&x, &y| scores[y].total_cmp(&scores[x]));
+    order
+        .iter()
+        .take(count)
+        .map(|&k| format!("{} {:.3}", cell_name(cells[k]), scores[k]))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn spectral(args: SpectralArgs) -> Result<(), String> {
+    let SpectralArgs {
+        cells,
+        inputs,
+        outputs,
+        eigenvectors,
+        horizon,
+        radius,
+        gap_csv,
+        engine,
+        output,
+    } = args;
+    let (wiring, dataset) = engine.load()?;
+    let neurons_tot = wiring.neurons_tot();
+    let edges = wiring.edges();
+    let classes = cell_classes(neurons_tot, neurons_tot + wiring.muscles_tot(), &edges);
+    let cells: Vec<u16> = if cells.is_empty() {
+        (0..neurons_tot).collect()
+    } else {
+        resolve_targets(&cells)?
+    };
+    let inputs: Vec<u16> = if inputs.is_empty() {
+        sensory_functions().into_keys().collect()
+    } else {
+        resolve_targets(&inputs)?
+    };
+    let outputs: Vec<u16> = if outputs.is_empty() {
+        (0..neurons_tot)
+            .filter(|&id| classes[id as usize] == CellClass::Motor)
+            .collect()
+    } else {
+        resolve_targets(&outputs)?
+    };
+    let mut out = open_output(&output)?;
+    let io = |err: std::io::Error| err.to_string();
+
+    let a = adjacency(&cells, &edges);
+    let values = eigenvalues(&a);
+    let rho = values.first().map_or(0., |v| v.norm());
+    writeln!(out, "dataset {} {}", dataset.name, dataset.version).map_err(io)?;
+    writeln!(
+        out,
+        "{} cells, {} edges",
+        cells.len(),
+        a.iter().filter(|&&w| w != 0.).count()
+    )
+    .map_err(io)?;
+    writeln!(out, "spectral radius    {:.4}", rho).map_err(io)?;
+    writeln!(out, "leading eigenvalues").map_err(io)?;
+    for v in values.iter().take(10) {
+        writeln!(out, "  {:>9.4} {:+.4}i  |{:.4}|", v.re, v.im, v.norm()).map_err(io)?;
+    }
+    for (k, &value) in values.iter().take(eigenvectors).enumerate() {
+        let v = eigenvector(&a, value)?;
+        let magnitudes: Vec<f64> = v.iter().map(|c| c.norm()).collect();
+        writeln!(
+            out,
+            "eigenvector {} ({:.4} {:+.4}i): {}",
+            k + 1,
+            value.re,
+            value.im,
+            top_cells(&cells, &magnitudes, 10)
+        )
+        .map_err(io)?;
+    }
+
+    let links = match &gap_csv {
+        Some(path) => gap_junctions_from_csv(path)?,
+        None => dataset.gap_junctions,
+    };
+    writeln!(out, "gap junction Laplacian").map_err(io)?;
+    if links.is_empty() {
+        writeln!(
+            out,
+            "  no gap junctions in {} (see --gap-csv)",
+            dataset.name
+        )
+        .map_err(io)?;
+    } else {
+        let (gap_values, gap_vectors) = laplacian_spectrum(&laplacian(&cells, &links));
+        let zero = 1e-9 * gap_values.last().copied().unwrap_or(0.).max(1.);
+        let components = gap_values.iter().filter(|&&v| v < zero).count();
+        writeln!(out, "  components       {}", components).map_err(io)?;
+        writeln!(
+            out,
+            "  largest          {:.4}",
+            gap_values.last().copied().unwrap_or(0.)
+        )
+        .map_err(io)?;
+        if let Some(&fiedler) = gap_values.get(components) {
+            let vector: Vec<f64> = gap_vectors
+                .column(components)
+                .iter()
+                .map(|x| x.abs())
+                .collect();
+            writeln!(out, "  first nonzero    {:.4}", fiedler).map_err(io)?;
+            writeln!(out, "  its eigenvector  {}", top_cells(&cells, &vector, 10)).map_err(io)?;
+        }
+    }
+
+    let scaled = if radius > 0. && rho > 0. {
+        &a * (radius / rho)
+    } else {
+        a.clone()
+    };
+    let b = selection(&cells, &inputs);
+    let c = selection(&cells, &outputs).transpose();
+    let gramians = [
+        (
+            "controllability",
+            b.ncols(),
+            "inputs",
+            controllability_gramian(&scaled, &b, horizon),
+        ),
+        (
+            "observability",
+            c.nrows(),
+            "outputs",
+            observability_gramian(&scaled, &c, horizon),
+        ),
+    ];
+    for (name, count, role, gramian) in gramians {
+        let summary = GramianSummary::new(&gramian);
+        writeln!(
+            out,
+            "{} Gramian ({} {}, horizon {}, radius {:.3})",
+            name,
+            count,
+            role,
+            horizon,
+            if radius > 0. { radius } else { rho }
+        )
+        .map_err(io)?;
+        writeln!(out, "{}", summary).map_err(io)?;
+        writeln!(
+            out,
+            "  strongest cells  {}",
+            top_cells(&cells, &summary.diagonal, 10)
+        )
+        .map_err(io)?;
+    }
+    out.flush().map_err(io)
+}
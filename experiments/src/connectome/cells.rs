@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+/// Name, type and role of one cell
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellInfo {
+    pub name: String,
+    /// Free-form cell type, e.g. "motor" or "interneuron"; may be empty
+    pub kind: String,
+    pub muscle: bool,
+}
+
+impl CellInfo {
+    pub fn neuron(name: &str, kind: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            muscle: false,
+        }
+    }
+
+    pub fn muscle(name: &str, kind: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            muscle: true,
+        }
+    }
+}
+
+/// The cells of a nervous system in id order: neurons first, then muscles,
+/// matching the layout the engine expects.
+#[derive(Clone, Debug, Default)]
+pub struct CellRegistry {
+    cells: Vec<CellInfo>,
+    index: BTreeMap<String, u16>,
+}
+
+impl CellRegistry {
+    /// Names must be unique and every muscle must follow every neuron.
+    pub fn new(cells: Vec<CellInfo>) -> Result<Self, String> {
+        if cells.len() > 0x200 {
+            return Err(format!(
+                "{} cells do not fit in 9-bit ROM ids (at most 512)",
+                cells.len()
+            ));
+        }
+        let mut index = BTreeMap::new();
+        let mut muscles = false;
+        for (id, cell) in cells.iter().enumerate() {
+            if cell.name.is_empty()
+                || cell.name.starts_with('#')
+                || cell.name.contains(char::is_whitespace)
+            {
+                return Err(format!("Invalid cell name {:?}", cell.name));
+            }
+            if index.insert(cell.name.clone(), id as u16).is_some() {
+                return Err(format!("Cell {} is listed twice", cell.name));
+            }
+            if muscles && !cell.muscle {
+                return Err(format!("Neuron {} is listed after a muscle", cell.name));
+            }
+            muscles |= cell.muscle;
+        }
+        Ok(Self { cells, index })
+    }
+
+    /// Cells named after their ids ("cell0", "cell1", ...)
+    pub fn anonymous(neurons_tot: u16, cells: u16) -> Self {
+        let cells = (0..cells)
+            .map(|id| {
+                let name = format!("cell{}", id);
+                if id < neurons_tot {
+                    CellInfo::neuron(&name, "")
+                } else {
+                    CellInfo::muscle(&name, "")
+                }
+            })
+            .collect();
+        Self::new(cells).expect("anonymous names are unique")
+    }
+
+    pub fn len(&self) -> u16 {
+        self.cells.len() as u16
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn neurons_tot(&self) -> u16 {
+        self.cells.iter().filter(|c| !c.muscle).count() as u16
+    }
+
+    pub fn muscles_tot(&self) -> u16 {
+        self.len() - self.neurons_tot()
+    }
+
+    pub fn get(&self, id: u16) -> Option<&CellInfo> {
+        self.cells.get(id as usize)
+    }
+
+    /// Name of a cell, "#id" when out of range
+    pub fn name(&self, id: u16) -> String {
+        self.get(id)
+            .map_or_else(|| format!("#{}", id), |c| c.name.clone())
+    }
+
+    /// Id of a named cell; "#id" is accepted for any cell in range
+    pub fn id(&self, name: &str) -> Option<u16> {
+        self.index.get(name).copied().or_else(|| {
+            name.strip_prefix('#')
+                .and_then(|n| n.parse().ok())
+                .filter(|&id| id < self.len())
+        })
+    }
+
+    pub fn kind(&self, id: u16) -> Option<&str> {
+        self.get(id).map(|c| c.kind.as_str())
+    }
+
+    pub fn is_muscle(&self, id: u16) -> bool {
+        self.get(id).is_some_and(|c| c.muscle)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &CellInfo)> {
+        self.cells.iter().enumerate().map(|(id, c)| (id as u16, c))
+    }
+
+    /// Ids of the named cells; a name that is no cell selects every cell of
+    /// that type instead.
+    pub fn resolve<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<u16>, String> {
+        let mut ids = vec![];
+        for name in names {
+            let name = name.as_ref();
+            if let Some(id) = self.id(name) {
+                ids.push(id);
+                continue;
+            }
+            let before = ids.len();
+            ids.extend(
+                self.iter()
+                    .filter(|(_, c)| c.kind == name)
+                    .map(|(id, _)| id),
+            );
+            if ids.len() == before {
+                return Err(format!("Unknown cell or cell type: {}", name));
+            }
+        }
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells() -> CellRegistry {
+        CellRegistry::new(vec![
+            CellInfo::neuron("A", "cpg"),
+            CellInfo::neuron("B", "cpg"),
+            CellInfo::neuron("C", ""),
+            CellInfo::muscle("M", "body"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn looks_up_names_ids_and_types() {
+        let cells = cells();
+        assert_eq!((cells.neurons_tot(), cells.muscles_tot()), (3, 1));
+        assert_eq!(cells.id("B"), Some(1));
+        assert_eq!(cells.id("#3"), Some(3));
+        assert_eq!(cells.id("#4"), None);
+        assert_eq!(cells.name(3), "M");
+        assert_eq!(cells.name(9), "#9");
+        assert!(cells.is_muscle(3) && !cells.is_muscle(0));
+        assert_eq!(cells.resolve(&["cpg", "M"]).unwrap(), [0, 1, 3]);
+        assert!(cells.resolve(&["nothing"]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_layouts() {
+        let new = |cells: Vec<CellInfo>| CellRegistry::new(cells).unwrap_err();
+        assert!(new(vec![CellInfo::neuron("A", ""), CellInfo::neuron("A", "")]).contains("twice"));
+        assert!(new(vec![CellInfo::muscle("M", ""), CellInfo::neuron("A", "")]).contains("after"));
+        assert!(new(vec![CellInfo::neuron("A B", "")]).contains("Invalid"));
+        assert!(new(vec![CellInfo::neuron("#1", "")]).contains("Invalid"));
+        let many = (0..513)
+            .map(|i| CellInfo::neuron(&format!("n{}", i), ""))
+            .collect();
+        assert!(new(many).contains("512"));
+    }
+
+    #[test]
+    fn anonymous_names_follow_ids() {
+        let cells = CellRegistry::anonymous(2, 3);
+        assert_eq!(cells.name(2), "cell2");
+        assert!(cells.is_muscle(2) && !cells.is_muscle(1));
+    }
+}
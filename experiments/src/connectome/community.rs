@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::connectome::Edge;
+
+/// Connections from one module to another
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleLink {
+    pub edges: usize,
+    /// Sum of the signed weights
+    pub weight: i64,
+}
+
+/// A partition of a set of cells into modules, numbered from the largest.
+#[derive(Clone, Debug)]
+pub struct Communities {
+    pub cells: Vec<u16>,
+    /// Module of each entry of `cells`
+    pub module: Vec<usize>,
+    pub modularity: f64,
+}
+
+impl Communities {
+    pub fn modules(&self) -> usize {
+        self.module.iter().max().map_or(0, |&m| m + 1)
+    }
+
+    /// Cells of module `module`, in id order
+    pub fn members(&self, module: usize) -> Vec<u16> {
+        self.cells
+            .iter()
+            .zip(&self.module)
+            .filter(|&(_, &m)| m == module)
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    pub fn module_of(&self, id: u16) -> Option<usize> {
+        let k = self.cells.iter().position(|&c| c == id)?;
+        Some(self.module[k])
+    }
+
+    /// Directed connections between modules: entry `[a][b]` gathers the
+    /// edges from module `a` to module `b`. Edges leaving the partitioned
+    /// cells are ignored.
+    pub fn links(&self, edges: &[Edge]) -> Vec<Vec<ModuleLink>> {
+        let n = self.modules();
+        let mut links = vec![vec![ModuleLink::default(); n]; n];
+        for e in edges {
+            if let (Some(a), Some(b)) = (self.module_of(e.pre), self.module_of(e.post)) {
+                links[a][b].edges += 1;
+                links[a][b].weight += e.weight as i64;
+            }
+        }
+        links
+    }
+}
+
+/// Undirected weighted graph as adjacency lists; both directions are listed
+/// and a self-loop appears once.
+type Graph = Vec<Vec<(usize, f64)>>;
+
+fn undirected(cells: &[u16], edges: &[Edge]) -> Graph {
+    let index: BTreeMap<u16, usize> = cells.iter().enumerate().map(|(k, &id)| (id, k)).collect();
+    let mut weights: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+    for e in edges {
+        if let (Some(&i), Some(&j)) = (index.get(&e.pre), index.get(&e.post))
+            && i != j
+        {
+            let w = (e.weight as f64).abs();
+            *weights.entry((i, j)).or_insert(0.) += w;
+            *weights.entry((j, i)).or_insert(0.) += w;
+        }
+    }
+    let mut graph = vec![vec![]; cells.len()];
+    for ((i, j), w) in weights {
+        graph[i].push((j, w));
+    }
+    graph
+}
+
+fn degree(row: &[(usize, f64)]) -> f64 {
+    row.iter().map(|&(_, w)| w).sum()
+}
+
+/// Move single nodes between communities while modularity improves.
+/// Returns the community of each node and whether any node moved.
+fn local_moves(graph: &Graph, resolution: f64, rng: &mut StdRng) -> (Vec<usize>, bool) {
+    let n = graph.len();
+    let k: Vec<f64> = graph.iter().map(|row| degree(row)).collect();
+    let m2: f64 = k.iter().sum();
+    let mut community: Vec<usize> = (0..n).collect();
+    let mut tot = k.clone();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut moved_any = false;
+    if m2 == 0. {
+        return (community, false);
+    }
+    loop {
+        order.shuffle(rng);
+        let mut moved = false;
+        for &i in &order {
+            let own = community[i];
+            let mut neighbours: BTreeMap<usize, f64> = BTreeMap::new();
+            for &(j, w) in &graph[i] {
+                if j != i {
+                    *neighbours.entry(community[j]).or_insert(0.) += w;
+                }
+            }
+            tot[own] -= k[i];
+            let gain = |c: usize, w: f64| w - resolution * tot[c] * k[i] / m2;
+            let mut best = (own, gain(own, neighbours.get(&own).copied().unwrap_or(0.)));
+            for (&c, &w) in &neighbours {
+                let g = gain(c, w);
+                if g > best.1 + 1e-12 {
+                    best = (c, g);
+                }
+            }
+            tot[best.0] += k[i];
+            if best.0 != own {
+                community[i] = best.0;
+                moved = true;
+                moved_any = true;
+            }
+        }
+        if !moved {
+            return (community, moved_any);
+        }
+    }
+}
+
+/// Number communities 0.. in order of first appearance
+fn renumber(community: &mut [usize]) -> usize {
+    let mut ids = BTreeMap::new();
+    for c in community.iter_mut() {
+        let next = ids.len();
+        *c = *ids.entry(*c).or_insert(next);
+    }
+    ids.len()
+}
+
+fn aggregate(graph: &Graph, community: &[usize], count: usize) -> Graph {
+    let mut weights: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+    for (i, row) in graph.iter().enumerate() {
+        for &(j, w) in row {
+            *weights.entry((community[i], community[j])).or_insert(0.) += w;
+        }
+    }
+    let mut next = vec![vec![]; count];
+    for ((a, b), w) in weights {
+        next[a].push((b, w));
+    }
+    next
+}
+
+fn modularity(graph: &Graph, community: &[usize], resolution: f64) -> f64 {
+    let m2: f64 = graph.iter().map(|row| degree(row)).sum();
+    if m2 == 0. {
+        return 0.;
+    }
+    let count = community.iter().max().map_or(0, |&c| c + 1);
+    let mut inside = vec![0.; count];
+    let mut tot = vec![0.; count];
+    for (i, row) in graph.iter().enumerate() {
+        tot[community[i]] += degree(row);
+        for &(j, w) in row {
+            if community[i] == community[j] {
+                inside[community[i]] += w;
+            }
+        }
+    }
+    inside
+        .iter()
+        .zip(&tot)
+        .map(|(&a, &t)| a / m2 - resolution * (t / m2) * (t / m2))
+        .sum()
+}
+
+/// Louvain community detection on `cells`, treating every edge among them
+/// as undirected with the magnitude of its weight (a pair connected both
+/// ways gets the sum). `resolution` above 1 favours smaller modules. The
+/// node order is shuffled with `seed`, so a seed always gives the same
+/// partition. Cells without connections end up alone in their own module.
+pub fn louvain(cells: &[u16], edges: &[Edge], resolution: f64, seed: u64) -> Communities {
+    let original = undirected(cells, edges);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut graph = original.clone();
+    let mut membership: Vec<usize> = (0..cells.len()).collect();
+    loop {
+        let (mut community, moved) = local_moves(&graph, resolution, &mut rng);
+        if !moved {
+            break;
+        }
+        let count = renumber(&mut community);
+        for m in membership.iter_mut() {
+            *m = community[*m];
+        }
+        graph = aggregate(&graph, &community, count);
+    }
+
+    // Number modules by decreasing size, ties by their smallest cell
+    let mut sizes: BTreeMap<usize, (usize, u16)> = BTreeMap::new();
+    for (&m, &id) in membership.iter().zip(cells) {
+        let entry = sizes.entry(m).or_insert((0, id));
+        entry.0 += 1;
+        entry.1 = entry.1.min(id);
+    }
+    let mut ranked: Vec<(usize, (usize, u16))> = sizes.into_iter().collect();
+    ranked.sort_by_key(|&(_, (size, first))| (std::cmp::Reverse(size), first));
+    let rank: BTreeMap<usize, usize> = ranked
+        .iter()
+        .enumerate()
+        .map(|(r, &(m, _))| (m, r))
+        .collect();
+    let module: Vec<usize> = membership.iter().map(|m| rank[m]).collect();
+    Communities {
+        cells: cells.to_vec(),
+        modularity: modularity(&original, &module, resolution),
+        module,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::preset;
+
+    fn edge(pre: u16, post: u16, weight: i8) -> Edge {
+        Edge { pre, post, weight }
+    }
+
+    /// Two 4-cliques (0..4 and 4..8) joined by one weak edge, plus cell 8
+    /// with no connections
+    fn two_cliques() -> Vec<Edge> {
+        let mut edges = vec![];
+        for group in [0u16, 4] {
+            for i in group..group + 4 {
+                for j in group..group + 4 {
+                    if i != j {
+                        edges.push(edge(i, j, 5));
+                    }
+                }
+            }
+        }
+        edges.push(edge(3, 4, -1));
+        edges
+    }
+
+    #[test]
+    fn finds_the_cliques() {
+        let cells: Vec<u16> = (0..9).collect();
+        let communities = louvain(&cells, &two_cliques(), 1.0, 0);
+        assert_eq!(communities.modules(), 3);
+        assert_eq!(communities.members(0), [0, 1, 2, 3]);
+        assert_eq!(communities.members(1), [4, 5, 6, 7]);
+        assert_eq!(communities.members(2), [8]);
+        assert!(communities.modularity > 0.4);
+        assert_eq!(communities.module_of(8), Some(2));
+        assert_eq!(communities.module_of(9), None);
+
+        let links = communities.links(&two_cliques());
+        assert_eq!(
+            links[0][0],
+            ModuleLink {
+                edges: 12,
+                weight: 60
+            }
+        );
+        assert_eq!(
+            links[0][1],
+            ModuleLink {
+                edges: 1,
+                weight: -1
+            }
+        );
+        assert_eq!(links[1][0], ModuleLink::default());
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_partition() {
+        let connectome = preset::connectome();
+        let cells: Vec<u16> = (0..connectome.neurons_tot()).collect();
+        let edges = connectome.edges();
+        let a = louvain(&cells, &edges, 1.0, 7);
+        let b = louvain(&cells, &edges, 1.0, 7);
+        assert_eq!(a.module, b.module);
+        assert_eq!(a.modularity, b.modularity);
+        assert!(a.modules() > 1 && a.modularity > 0.);
+        // modules are numbered from the largest
+        let sizes: Vec<usize> = (0..a.modules()).map(|m| a.members(m).len()).collect();
+        assert!(sizes.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[test]
+    fn higher_resolution_gives_smaller_modules() {
+        let connectome = preset::connectome();
+        let cells: Vec<u16> = (0..connectome.neurons_tot()).collect();
+        let edges = connectome.edges();
+        let coarse = louvain(&cells, &edges, 0.5, 0);
+        let fine = louvain(&cells, &edges, 2.0, 0);
+        assert!(fine.modules() > coarse.modules());
+    }
+}
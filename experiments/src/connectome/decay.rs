@@ -0,0 +1,149 @@
+/// What happens to a neuron's state between discharges.
+#[derive(Clone, Debug)]
+pub enum DecayPolicy {
+    /// Keep the state until it has been unchanged for more than `max_idle`
+    /// cycles, then zero it (ctm_meta_handle_idle_neurons). The idle counter
+    /// is 7 bits wide, so `max_idle` is capped at 126.
+    IdleReset { max_idle: u8 },
+    /// Decay towards each neuron's resting level by its own time constant.
+    Leak(LeakModel),
+    /// State persists until the neuron discharges.
+    Persist,
+}
+
+impl Default for DecayPolicy {
+    fn default() -> Self {
+        DecayPolicy::IdleReset { max_idle: 100 }
+    }
+}
+
+/// Per-neuron leaky integration: every cycle the carried-over state moves
+/// towards `rest` by a factor of `exp(-1 / tau)`, with `tau` in cycles.
+///
+/// Slow integrators (e.g. AIY) get a long `tau`, fast relays a short one.
+/// Sub-integer remainders are carried between cycles so long time constants
+/// still decay an `i8` state instead of rounding back to where they started.
+#[derive(Clone, Debug)]
+pub struct LeakModel {
+    tau: Vec<f32>,
+    rest: Vec<i8>,
+    residue: Vec<f32>,
+}
+
+impl LeakModel {
+    /// Same time constant and resting level for all `neurons`.
+    pub fn uniform(neurons: usize, tau: f32, rest: i8) -> Self {
+        Self {
+            tau: vec![tau; neurons],
+            rest: vec![rest; neurons],
+            residue: vec![0.; neurons],
+        }
+    }
+
+    /// Override the time constant and resting level of one neuron.
+    pub fn with_neuron(mut self, id: u16, tau: f32, rest: i8) -> Self {
+        self.set_neuron(id, tau, rest);
+        self
+    }
+
+    pub fn set_neuron(&mut self, id: u16, tau: f32, rest: i8) {
+        let idx = id as usize;
+        self.tau[idx] = tau;
+        self.rest[idx] = rest;
+        self.residue[idx] = 0.;
+    }
+
+    pub fn tau(&self, id: u16) -> f32 {
+        self.tau[id as usize]
+    }
+
+    pub fn rest(&self, id: u16) -> i8 {
+        self.rest[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tau.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tau.is_empty()
+    }
+
+    /// Decay `state` in place towards the resting levels.
+    pub(crate) fn apply(&mut self, state: &mut [i8]) {
+        for (i, s) in state.iter_mut().enumerate() {
+            let tau = self.tau[i];
+            let rest = self.rest[i] as f32;
+            let factor = if tau > 0. { (-1. / tau).exp() } else { 0. };
+            let exact = (rest + (*s as f32 + self.residue[i] - rest) * factor).clamp(-128., 127.);
+            let rounded = exact.round();
+            self.residue[i] = exact - rounded;
+            *s = rounded as i8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+    use crate::emulations::c_elegans::preset;
+
+    /// State of B over `cycles` after A pings it once, below threshold
+    fn held_state(decay: DecayPolicy, cycles: usize) -> Vec<i16> {
+        let mut connectome = Circuit::parse("threshold 20\nneuron A\nneuron B\nA B 10\n")
+            .unwrap()
+            .connectome()
+            .unwrap();
+        connectome.set_decay_policy(decay).unwrap();
+        let mut states = vec![];
+        for cycle in 0..cycles {
+            connectome.neural_cycle((cycle == 0).then_some(&[0][..]));
+            let mut state = [0];
+            connectome.state_query(&[1], &mut state);
+            states.push(state[0]);
+        }
+        states
+    }
+
+    #[test]
+    fn idle_reset_zeroes_after_max_idle_and_persist_keeps_the_state() {
+        let reset = held_state(DecayPolicy::IdleReset { max_idle: 3 }, 8);
+        assert_eq!(reset[..3], [10, 10, 10]);
+        assert_eq!(reset.last(), Some(&0));
+        assert!(
+            held_state(DecayPolicy::Persist, 200)
+                .iter()
+                .all(|&s| s == 10)
+        );
+    }
+
+    #[test]
+    fn leak_decays_towards_rest_even_with_long_time_constants() {
+        let states = held_state(DecayPolicy::Leak(LeakModel::uniform(2, 40., 0)), 300);
+        assert!(states.windows(2).all(|w| w[1] <= w[0]));
+        assert_eq!(states.last(), Some(&0));
+
+        let mut leak = LeakModel::uniform(1, 2., 0).with_neuron(0, 1., -5);
+        let mut state = [5];
+        for _ in 0..20 {
+            leak.apply(&mut state);
+        }
+        assert_eq!(state, [-5]);
+    }
+
+    #[test]
+    fn leak_must_cover_every_neuron() {
+        let mut connectome = preset::connectome();
+        let err = connectome
+            .set_decay_policy(DecayPolicy::Leak(LeakModel::uniform(10, 5., 0)))
+            .unwrap_err();
+        assert!(err.contains("10 neurons"), "{}", err);
+        let neurons = connectome.neurons_tot() as usize;
+        assert!(
+            connectome
+                .set_decay_policy(DecayPolicy::Leak(LeakModel::uniform(neurons, 5., 0)))
+                .is_ok()
+        );
+    }
+}
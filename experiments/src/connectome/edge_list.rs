@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::connectome::cells::{CellInfo, CellRegistry};
+use crate::connectome::rom_image::build_rom;
+use crate::connectome::{Connectome, Edge};
+
+/// A small nervous system read from an edge list: named cells, their
+/// connections and optionally the discharge threshold.
+///
+/// The text format is line based, with `#` starting a comment:
+///
+/// ```text
+/// threshold 40
+/// neuron A cpg
+/// muscle M1
+/// A M1 50
+/// ```
+///
+/// `neuron NAME [TYPE]` and `muscle NAME [TYPE]` declare cells, every other
+/// line is an edge `PRE POST WEIGHT` between declared cells, with weights in
+/// the ROM range -64..=63. Declarations may come in any order: neurons get
+/// the first ids in the order they are declared, muscles follow.
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    pub cells: CellRegistry,
+    pub edges: Vec<Edge>,
+    pub threshold: Option<i8>,
+}
+
+impl Circuit {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lines: Vec<(usize, Vec<&str>)> = text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| {
+                (
+                    n + 1,
+                    line.split('#')
+                        .next()
+                        .unwrap_or("")
+                        .split_whitespace()
+                        .collect(),
+                )
+            })
+            .filter(|(_, tokens): &(usize, Vec<&str>)| !tokens.is_empty())
+            .collect();
+
+        let mut neurons = vec![];
+        let mut muscles = vec![];
+        let mut threshold = None;
+        for (n, tokens) in &lines {
+            match tokens.as_slice() {
+                ["neuron", name] => neurons.push(CellInfo::neuron(name, "")),
+                ["neuron", name, kind] => neurons.push(CellInfo::neuron(name, kind)),
+                ["muscle", name] => muscles.push(CellInfo::muscle(name, "")),
+                ["muscle", name, kind] => muscles.push(CellInfo::muscle(name, kind)),
+                ["threshold", value] => {
+                    threshold = Some(
+                        value
+                            .parse()
+                            .map_err(|err| format!("line {}: threshold {}: {}", n, value, err))?,
+                    )
+                }
+                ["neuron" | "muscle" | "threshold", ..] => {
+                    return Err(format!("line {}: cannot parse '{}'", n, tokens.join(" ")));
+                }
+                _ => {}
+            }
+        }
+        neurons.extend(muscles);
+        let cells = CellRegistry::new(neurons)?;
+
+        let mut edges = vec![];
+        for (n, tokens) in &lines {
+            match tokens.as_slice() {
+                ["neuron" | "muscle" | "threshold", ..] => {}
+                [pre, post, weight] => {
+                    let id = |name: &str| {
+                        cells
+                            .id(name)
+                            .ok_or_else(|| format!("line {}: undeclared cell {}", n, name))
+                    };
+                    let (pre, post) = (id(pre)?, id(post)?);
+                    if cells.is_muscle(pre) {
+                        return Err(format!(
+                            "line {}: edge starts at muscle {}",
+                            n,
+                            cells.name(pre)
+                        ));
+                    }
+                    let weight = weight
+                        .parse::<i8>()
+                        .ok()
+                        .filter(|w| (-64..=63).contains(w))
+                        .ok_or_else(|| {
+                            format!("line {}: weight {} is outside -64..=63", n, weight)
+                        })?;
+                    edges.push(Edge { pre, post, weight });
+                }
+                _ => return Err(format!("line {}: expected 'PRE POST WEIGHT'", n)),
+            }
+        }
+        Ok(Self {
+            cells,
+            edges,
+            threshold,
+        })
+    }
+
+    /// An engine running this circuit, with its threshold if one is given
+    pub fn connectome(&self) -> Result<Connectome, String> {
+        let neurons_tot = self.cells.neurons_tot();
+        let mut connectome =
+            Connectome::from_rom(build_rom(neurons_tot, &self.edges)?, self.cells.len())?
+                .with_cells(self.cells.clone())?;
+        if let Some(threshold) = self.threshold {
+            connectome.set_threshold(threshold);
+        }
+        Ok(connectome)
+    }
+}
+
+/// Write the wiring of a connectome in the format read by `Circuit::parse`,
+/// leaving out ablated cells.
+pub fn write_edge_list<W: Write>(mut w: W, connectome: &Connectome) -> Result<(), std::io::Error> {
+    let cells = connectome.cells();
+    writeln!(w, "threshold {}", connectome.threshold())?;
+    for (_, cell) in cells.iter().filter(|&(id, _)| !connectome.is_ablated(id)) {
+        let role = if cell.muscle { "muscle" } else { "neuron" };
+        if cell.kind.is_empty() {
+            writeln!(w, "{} {}", role, cell.name)?;
+        } else {
+            writeln!(w, "{} {} {}", role, cell.name, cell.kind)?;
+        }
+    }
+    for e in connectome.edges() {
+        writeln!(
+            w,
+            "{} {} {}",
+            cells.name(e.pre),
+            cells.name(e.post),
+            e.weight
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RING: &str = "# two neurons exciting each other\n\
+        threshold 20\n\
+        muscle M body\n\
+        neuron A cpg\n\
+        neuron B\n\
+        A B 30   # forward\n\
+        B A 30\n\
+        B M -5\n";
+
+    #[test]
+    fn parses_cells_edges_and_threshold() {
+        let circuit = Circuit::parse(RING).unwrap();
+        let names: Vec<&str> = circuit.cells.iter().map(|(_, c)| c.name.as_str()).collect();
+        // neurons come first, in declaration order
+        assert_eq!(names, ["A", "B", "M"]);
+        assert_eq!(circuit.cells.kind(0), Some("cpg"));
+        assert_eq!(circuit.threshold, Some(20));
+        assert_eq!(
+            circuit.edges,
+            vec![
+                Edge {
+                    pre: 0,
+                    post: 1,
+                    weight: 30
+                },
+                Edge {
+                    pre: 1,
+                    post: 0,
+                    weight: 30
+                },
+                Edge {
+                    pre: 1,
+                    post: 2,
+                    weight: -5
+                },
+            ]
+        );
+        let connectome = circuit.connectome().unwrap();
+        assert_eq!(connectome.threshold(), 20);
+        assert_eq!(connectome.cells().name(2), "M");
+    }
+
+    #[test]
+    fn written_edge_list_parses_back() {
+        let mut connectome = Circuit::parse(RING).unwrap().connectome().unwrap();
+        let mut text = vec![];
+        write_edge_list(&mut text, &connectome).unwrap();
+        let read = Circuit::parse(std::str::from_utf8(&text).unwrap()).unwrap();
+        assert_eq!(read.connectome().unwrap().rom(), connectome.rom());
+        assert_eq!(read.cells.kind(2), Some("body"));
+
+        // ablated cells and their edges are left out
+        connectome.ablate(2);
+        let mut text = vec![];
+        write_edge_list(&mut text, &connectome).unwrap();
+        let read = Circuit::parse(std::str::from_utf8(&text).unwrap()).unwrap();
+        assert_eq!((read.cells.len(), read.edges.len()), (2, 2));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = |text: &str| Circuit::parse(text).unwrap_err();
+        assert!(err("neuron A\nA B 3\n").contains("line 2: undeclared cell B"));
+        assert!(err("neuron A\nneuron B\nA B 64\n").contains("line 3: weight 64"));
+        assert!(err("neuron A\nmuscle M\nM A 1\n").contains("edge starts at muscle M"));
+        assert!(err("threshold x\n").contains("line 1"));
+        assert!(err("neuron A\nA A\n").contains("expected"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::connectome::Connectome;
+
+/// Stimulus and readout interface shared by the connectome simulators, so the
+/// same protocol can drive different abstraction levels of the same wiring.
+pub trait NeuralEngine {
+    /// Complete one neural cycle, pinging `stim_neuron` first.
+    fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>);
+
+    /// Write 1 for every neuron in `input_id` that discharged in the last cycle.
+    fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]);
+
+    /// Write the last cycle's value of every muscle in `input_id`.
+    fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]);
+
+    /// Write the force of every muscle in `input_id`; the raw muscle value
+    /// unless the engine models muscle dynamics.
+    fn muscle_force_query(&self, input_id: &[u16], query_result: &mut [f32]) {
+        let mut raw = vec![0i16; input_id.len()];
+        self.muscle_query(input_id, &mut raw);
+        for (result, value) in query_result.iter_mut().zip(raw) {
+            *result = value as f32;
+        }
+    }
+}
+
+impl NeuralEngine for Connectome {
+    fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>) {
+        Connectome::neural_cycle(self, stim_neuron);
+    }
+
+    fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]) {
+        Connectome::discharge_query(self, input_id, query_result);
+    }
+
+    fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        Connectome::muscle_query(self, input_id, query_result);
+    }
+
+    fn muscle_force_query(&self, input_id: &[u16], query_result: &mut [f32]) {
+        Connectome::muscle_force_query(self, input_id, query_result);
+    }
+}
+
+/// Drive `engine` for `cycles` and return the discharge rate (per cycle) of each id.
+pub fn firing_rates<E: NeuralEngine + ?Sized>(
+    engine: &mut E,
+    stim_neuron: Option<&[u16]>,
+    cycles: usize,
+    ids: &[u16],
+) -> Vec<f64> {
+    let mut counts = vec![0usize; ids.len()];
+    let mut result = vec![0u8; ids.len()];
+    for _ in 0..cycles {
+        engine.neural_cycle(stim_neuron);
+        engine.discharge_query(ids, &mut result);
+        for (count, &r) in counts.iter_mut().zip(result.iter()) {
+            *count += r as usize;
+        }
+    }
+    counts
+        .iter()
+        .map(|&c| c as f64 / cycles.max(1) as f64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    /// Discharges every other cycle and reports the cycle count as muscle value
+    struct Blinker {
+        cycle: usize,
+    }
+
+    impl NeuralEngine for Blinker {
+        fn neural_cycle(&mut self, _stim_neuron: Option<&[u16]>) {
+            self.cycle += 1;
+        }
+
+        fn discharge_query(&self, _input_id: &[u16], query_result: &mut [u8]) {
+            query_result.fill((self.cycle % 2) as u8);
+        }
+
+        fn muscle_query(&self, _input_id: &[u16], query_result: &mut [i16]) {
+            query_result.fill(self.cycle as i16);
+        }
+    }
+
+    #[test]
+    fn rates_count_discharges_per_cycle() {
+        let mut blinker = Blinker { cycle: 0 };
+        assert_eq!(firing_rates(&mut blinker, None, 4, &[0, 1]), [0.5, 0.5]);
+        assert_eq!(firing_rates(&mut blinker, None, 0, &[0]), [0.]);
+        let mut force = [0.; 2];
+        blinker.muscle_force_query(&[5, 6], &mut force);
+        assert_eq!(force, [4., 4.]);
+    }
+
+    #[test]
+    fn connectome_fires_under_stimulus() {
+        let circuit = Circuit::parse("threshold 20\nneuron A\nneuron B\nA B 30\n").unwrap();
+        let mut connectome = circuit.connectome().unwrap();
+        let engine: &mut dyn NeuralEngine = &mut connectome;
+        let rates = firing_rates(engine, Some(&[0]), 10, &[0, 1]);
+        assert!(rates[1] > 0.);
+        // A has no inputs of its own
+        assert_eq!(firing_rates(engine, None, 10, &[0]), [0.]);
+    }
+}
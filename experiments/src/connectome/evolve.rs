@@ -0,0 +1,301 @@
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::connectome::rom_image::build_rom;
+use crate::connectome::{Connectome, Edge};
+
+/// Weights a ROM word can hold
+pub const WEIGHT_MIN: i8 = -64;
+pub const WEIGHT_MAX: i8 = 63;
+
+/// Settings of the genetic algorithm in `evolve_weights`.
+#[derive(Clone, Debug)]
+pub struct EvolveParams {
+    pub population: usize,
+    pub generations: usize,
+    /// Chance of each weight being mutated in a child
+    pub mutation_rate: f64,
+    /// Standard deviation of a mutation, in weight units
+    pub mutation_scale: f64,
+    /// Individuals drawn per tournament when picking a parent
+    pub tournament: usize,
+    /// Best individuals copied unchanged into the next generation
+    pub elites: usize,
+    /// Keep excitatory edges excitatory and inhibitory ones inhibitory; a
+    /// weight may still reach zero
+    pub keep_signs: bool,
+    pub seed: u64,
+}
+
+impl Default for EvolveParams {
+    fn default() -> Self {
+        Self {
+            population: 32,
+            generations: 20,
+            mutation_rate: 0.02,
+            mutation_scale: 4.,
+            tournament: 3,
+            elites: 2,
+            keep_signs: true,
+            seed: 0,
+        }
+    }
+}
+
+/// Fitness statistics of one generation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub best: f64,
+    pub mean: f64,
+}
+
+impl fmt::Display for GenerationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "generation {:>4}  best {:+.4}  mean {:+.4}",
+            self.generation, self.best, self.mean
+        )
+    }
+}
+
+/// Outcome of `evolve_weights`
+#[derive(Clone, Debug)]
+pub struct Evolution {
+    /// Fitness of the unchanged wiring
+    pub initial_fitness: f64,
+    pub best_fitness: f64,
+    /// The best wiring found, with the topology of the starting edges
+    pub best_edges: Vec<Edge>,
+    pub history: Vec<GenerationStats>,
+}
+
+impl Evolution {
+    /// Edges whose weight differs from `original`, as `(edge, old weight)`
+    pub fn changed<'a>(&'a self, original: &'a [Edge]) -> impl Iterator<Item = (&'a Edge, i8)> {
+        self.best_edges
+            .iter()
+            .zip(original)
+            .filter(|(new, old)| new.weight != old.weight)
+            .map(|(new, old)| (new, old.weight))
+    }
+}
+
+/// Evolve the weights of `edges` with a genetic algorithm, keeping the wiring
+/// itself fixed: no connection is added or removed, and every weight stays in
+/// the range a ROM word can hold.
+///
+/// Each candidate is packed into a ROM, turned into a connectome by `build`
+/// (which applies threshold, update mode and the like) and scored by
+/// `fitness`, higher being better. Candidates of a generation are evaluated in
+/// parallel; all random choices come from `params.seed`, so a run is
+/// reproducible as long as `build` and `fitness` are deterministic.
+pub fn evolve_weights(
+    neurons_tot: u16,
+    edges: &[Edge],
+    build: &(dyn Fn(Vec<u16>) -> Result<Connectome, String> + Sync),
+    fitness: &(dyn Fn(&mut Connectome) -> Result<f64, String> + Sync),
+    params: &EvolveParams,
+    mut on_generation: impl FnMut(&GenerationStats),
+) -> Result<Evolution, String> {
+    if params.population < 2 {
+        return Err("Population needs at least two individuals".to_string());
+    }
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let original: Vec<i8> = edges.iter().map(|e| e.weight).collect();
+    let evaluate = |genomes: &[Vec<i8>]| -> Result<Vec<f64>, String> {
+        genomes
+            .par_iter()
+            .map(|weights| {
+                let mut connectome = build(build_rom(neurons_tot, &with_weights(edges, weights))?)?;
+                fitness(&mut connectome)
+            })
+            .collect()
+    };
+
+    // The starting wiring competes in the first generation alongside mutants of it
+    let mut population = vec![original.clone()];
+    while population.len() < params.population {
+        let mut child = original.clone();
+        mutate(&mut child, &original, params, &mut rng);
+        population.push(child);
+    }
+    let mut scores = evaluate(&population)?;
+    let initial_fitness = scores[0];
+    let mut history = vec![];
+
+    for generation in 0..=params.generations {
+        let mut ranked: Vec<usize> = (0..population.len()).collect();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        let stats = GenerationStats {
+            generation,
+            best: scores[ranked[0]],
+            mean: scores.iter().sum::<f64>() / scores.len() as f64,
+        };
+        on_generation(&stats);
+        history.push(stats);
+        if generation == params.generations {
+            let best = &population[ranked[0]];
+            return Ok(Evolution {
+                initial_fitness,
+                best_fitness: scores[ranked[0]],
+                best_edges: with_weights(edges, best),
+                history,
+            });
+        }
+
+        let elites = params.elites.min(population.len());
+        let mut next: Vec<Vec<i8>> = ranked[..elites]
+            .iter()
+            .map(|&i| population[i].clone())
+            .collect();
+        let mut next_scores: Vec<f64> = ranked[..elites].iter().map(|&i| scores[i]).collect();
+        let mut children = vec![];
+        while next.len() + children.len() < params.population {
+            let a = tournament(&scores, params.tournament, &mut rng);
+            let b = tournament(&scores, params.tournament, &mut rng);
+            let mut child: Vec<i8> = population[a]
+                .iter()
+                .zip(&population[b])
+                .map(|(&x, &y)| if rng.random_bool(0.5) { x } else { y })
+                .collect();
+            mutate(&mut child, &original, params, &mut rng);
+            children.push(child);
+        }
+        next_scores.extend(evaluate(&children)?);
+        next.extend(children);
+        population = next;
+        scores = next_scores;
+    }
+    unreachable!("the last generation returns")
+}
+
+fn with_weights(edges: &[Edge], weights: &[i8]) -> Vec<Edge> {
+    edges
+        .iter()
+        .zip(weights)
+        .map(|(e, &weight)| Edge { weight, ..*e })
+        .collect()
+}
+
+/// Index of the fittest of `size` randomly drawn individuals
+fn tournament(scores: &[f64], size: usize, rng: &mut StdRng) -> usize {
+    (0..size.max(1))
+        .map(|_| rng.random_range(0..scores.len()))
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+        .expect("at least one draw")
+}
+
+fn mutate(weights: &mut [i8], original: &[i8], params: &EvolveParams, rng: &mut StdRng) {
+    for (w, &o) in weights.iter_mut().zip(original) {
+        if !rng.random_bool(params.mutation_rate.clamp(0., 1.)) {
+            continue;
+        }
+        // Box-Muller normal sample
+        let normal = (-2. * (1. - rng.random::<f64>()).ln()).sqrt()
+            * (2. * std::f64::consts::PI * rng.random::<f64>()).cos();
+        let (low, high) = match (params.keep_signs, o.signum()) {
+            (true, 1) => (0, WEIGHT_MAX),
+            (true, -1) => (WEIGHT_MIN, 0),
+            _ => (WEIGHT_MIN, WEIGHT_MAX),
+        };
+        let shifted = *w as f64 + (normal * params.mutation_scale).round();
+        *w = shifted.clamp(low as f64, high as f64) as i8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    const RING: &str = "neuron A\nneuron B\nneuron C\nmuscle M\n\
+        A B 10\nB C -20\nC A 5\nC M 30\nA M -3\n";
+
+    /// Rewards large excitatory weights, so the optimum is known
+    fn run(params: &EvolveParams) -> (Evolution, Vec<Edge>, usize) {
+        let circuit = Circuit::parse(RING).unwrap();
+        let edges = circuit.connectome().unwrap().edges();
+        let build = |rom: Vec<u16>| Connectome::from_rom(rom, 4);
+        let fitness = |c: &mut Connectome| Ok(c.edges().iter().map(|e| e.weight as f64).sum());
+        let mut generations = 0;
+        let evolution =
+            evolve_weights(3, &edges, &build, &fitness, params, |_| generations += 1).unwrap();
+        (evolution, edges, generations)
+    }
+
+    #[test]
+    fn a_seed_reproduces_the_run() {
+        let params = EvolveParams {
+            population: 8,
+            generations: 5,
+            mutation_rate: 0.5,
+            seed: 11,
+            ..EvolveParams::default()
+        };
+        let (a, _, _) = run(&params);
+        let (b, _, _) = run(&params);
+        assert_eq!(a.history, b.history);
+        assert_eq!(a.best_edges, b.best_edges);
+        let (other, _, _) = run(&EvolveParams { seed: 12, ..params });
+        assert_ne!(a.history, other.history);
+    }
+
+    #[test]
+    fn keeps_the_wiring_and_improves() {
+        let params = EvolveParams {
+            population: 16,
+            generations: 10,
+            mutation_rate: 0.5,
+            ..EvolveParams::default()
+        };
+        let (evolution, edges, generations) = run(&params);
+        assert_eq!(generations, 11);
+        assert_eq!(evolution.initial_fitness, 22.);
+        assert!(evolution.best_fitness > evolution.initial_fitness);
+        // elites carry the best over, so it never gets worse
+        let best: Vec<f64> = evolution.history.iter().map(|g| g.best).collect();
+        assert!(best.windows(2).all(|w| w[1] >= w[0]));
+        for (new, old) in evolution.best_edges.iter().zip(&edges) {
+            assert_eq!((new.pre, new.post), (old.pre, old.post));
+            // signs are kept, a weight may reach zero
+            assert!(new.weight.signum() == old.weight.signum() || new.weight == 0);
+        }
+        assert!(evolution.changed(&edges).count() > 0);
+    }
+
+    #[test]
+    fn free_signs_stay_in_rom_range() {
+        let params = EvolveParams {
+            population: 8,
+            generations: 20,
+            mutation_rate: 1.,
+            mutation_scale: 100.,
+            keep_signs: false,
+            ..EvolveParams::default()
+        };
+        let (evolution, _, _) = run(&params);
+        assert!(evolution.best_edges.iter().all(|e| e.weight >= WEIGHT_MIN));
+        // with every weight free the best reaches the top of the range
+        assert!(evolution.best_fitness > 22.);
+
+        let tiny = EvolveParams {
+            population: 1,
+            ..EvolveParams::default()
+        };
+        let circuit = Circuit::parse(RING).unwrap();
+        let err = evolve_weights(
+            3,
+            &circuit.edges,
+            &|rom| Connectome::from_rom(rom, 4),
+            &|_| Ok(0.),
+            &tiny,
+            |_| {},
+        );
+        assert!(err.is_err());
+    }
+}
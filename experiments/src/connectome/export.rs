@@ -0,0 +1,78 @@
+use std::io::Write;
+
+use crate::connectome::Edge;
+
+/// Write the wiring as GraphML. Cells below `neurons_tot` are typed "neuron",
+/// the rest "muscle"; `name` labels each cell id.
+pub fn write_graphml<W: Write>(
+    mut w: W,
+    cells: u16,
+    neurons_tot: u16,
+    edges: &[Edge],
+    name: impl Fn(u16) -> String,
+) -> Result<(), std::io::Error> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="kind" for="node" attr.name="kind" attr.type="string"/>"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+    )?;
+    writeln!(w, r#"  <graph id="connectome" edgedefault="directed">"#)?;
+    for id in 0..cells {
+        let kind = if id < neurons_tot { "neuron" } else { "muscle" };
+        writeln!(
+            w,
+            r#"    <node id="n{}"><data key="name">{}</data><data key="kind">{}</data></node>"#,
+            id,
+            name(id),
+            kind
+        )?;
+    }
+    for e in edges {
+        writeln!(
+            w,
+            r#"    <edge source="n{}" target="n{}"><data key="weight">{}</data></edge>"#,
+            e.pre, e.post, e.weight
+        )?;
+    }
+    writeln!(w, "  </graph>")?;
+    writeln!(w, "</graphml>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphml_lists_typed_nodes_and_weighted_edges() {
+        let edges = [Edge {
+            pre: 0,
+            post: 2,
+            weight: -7,
+        }];
+        let mut out = Vec::new();
+        write_graphml(&mut out, 3, 2, &edges, |id| format!("c{}", id)).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert_eq!(xml.matches("<node ").count(), 3);
+        assert!(xml.contains(
+            r#"<node id="n1"><data key="name">c1</data><data key="kind">neuron</data></node>"#
+        ));
+        assert!(xml.contains(r#"<data key="name">c2</data><data key="kind">muscle</data>"#));
+        assert!(
+            xml.contains(r#"<edge source="n0" target="n2"><data key="weight">-7</data></edge>"#)
+        );
+        assert!(xml.trim_end().ends_with("</graphml>"));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+use crate::connectome::Edge;
+
+/// Summary statistics of a wiring diagram.
+#[derive(Clone, Debug)]
+pub struct GraphStats {
+    pub neurons: u16,
+    pub muscles: u16,
+    pub edges: usize,
+    pub neuron_to_neuron: usize,
+    pub neuron_to_muscle: usize,
+    pub excitatory: usize,
+    pub inhibitory: usize,
+    pub mean_abs_weight: f64,
+    /// Neurons with neither inputs nor outputs
+    pub isolated: usize,
+    /// (cell id, degree) of the cell with the most outputs
+    pub max_out_degree: (u16, usize),
+    /// (cell id, degree) of the cell with the most inputs
+    pub max_in_degree: (u16, usize),
+}
+
+impl GraphStats {
+    pub fn from_edges(neurons: u16, muscles: u16, edges: &[Edge]) -> Self {
+        let cells = (neurons + muscles) as usize;
+        let mut out_degree = vec![0usize; cells];
+        let mut in_degree = vec![0usize; cells];
+        for e in edges {
+            out_degree[e.pre as usize] += 1;
+            in_degree[e.post as usize] += 1;
+        }
+        let arg_max = |degrees: &[usize]| {
+            degrees
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, d)| *d)
+                .map_or((0, 0), |(id, &d)| (id as u16, d))
+        };
+
+        Self {
+            neurons,
+            muscles,
+            edges: edges.len(),
+            neuron_to_neuron: edges.iter().filter(|e| e.post < neurons).count(),
+            neuron_to_muscle: edges.iter().filter(|e| e.post >= neurons).count(),
+            excitatory: edges.iter().filter(|e| e.weight > 0).count(),
+            inhibitory: edges.iter().filter(|e| e.weight < 0).count(),
+            mean_abs_weight: edges
+                .iter()
+                .map(|e| e.weight.unsigned_abs() as f64)
+                .sum::<f64>()
+                / edges.len().max(1) as f64,
+            isolated: (0..neurons as usize)
+                .filter(|&id| out_degree[id] == 0 && in_degree[id] == 0)
+                .count(),
+            max_out_degree: arg_max(&out_degree),
+            max_in_degree: arg_max(&in_degree),
+        }
+    }
+}
+
+impl fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "neurons            {}", self.neurons)?;
+        writeln!(f, "muscles            {}", self.muscles)?;
+        writeln!(f, "edges              {}", self.edges)?;
+        writeln!(f, "  neuron -> neuron {}", self.neuron_to_neuron)?;
+        writeln!(f, "  neuron -> muscle {}", self.neuron_to_muscle)?;
+        writeln!(f, "  excitatory       {}", self.excitatory)?;
+        writeln!(f, "  inhibitory       {}", self.inhibitory)?;
+        writeln!(f, "mean |weight|      {:.3}", self.mean_abs_weight)?;
+        writeln!(f, "isolated neurons   {}", self.isolated)?;
+        writeln!(
+            f,
+            "max out-degree     {} (cell {})",
+            self.max_out_degree.1, self.max_out_degree.0
+        )?;
+        write!(
+            f,
+            "max in-degree      {} (cell {})",
+            self.max_in_degree.1, self.max_in_degree.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_edges_degrees_and_isolated_neurons() {
+        let edges = [
+            Edge {
+                pre: 0,
+                post: 1,
+                weight: 4,
+            },
+            Edge {
+                pre: 0,
+                post: 3,
+                weight: -2,
+            },
+            Edge {
+                pre: 1,
+                post: 3,
+                weight: 3,
+            },
+        ];
+        let stats = GraphStats::from_edges(3, 1, &edges);
+        assert_eq!(stats.edges, 3);
+        assert_eq!((stats.neuron_to_neuron, stats.neuron_to_muscle), (1, 2));
+        assert_eq!((stats.excitatory, stats.inhibitory), (2, 1));
+        assert_eq!(stats.mean_abs_weight, 3.);
+        assert_eq!(stats.isolated, 1);
+        assert_eq!(stats.max_out_degree, (0, 2));
+        assert_eq!(stats.max_in_degree, (3, 2));
+        assert!(stats.to_string().contains("isolated neurons   1"));
+
+        let empty = GraphStats::from_edges(2, 0, &[]);
+        assert_eq!((empty.mean_abs_weight, empty.isolated), (0., 2));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::connectome::{Connectome, engine::NeuralEngine};
+use crate::lify_stuff::{first_order_lif::FirstOrderLif, first_order_synapse::FirstOrderSynapse};
+
+/// Parameters of the continuous-time connectome.
+#[derive(Clone, Debug)]
+pub struct HybridParams {
+    /// Integration step of the LIF neurons and synapses (seconds)
+    pub t_step: f64,
+    /// Integration steps per neural cycle
+    pub steps_per_cycle: usize,
+    pub tau_rc: f64,
+    pub tau_ref: f64,
+    /// Synaptic time constant of every edge
+    pub tau_s: f64,
+    /// Firing rate of a neuron driven at one unit of input
+    pub max_rate: f64,
+    /// Input at which a neuron starts firing
+    pub intercept: f64,
+    /// Peak input per unit of connectome weight when a presynaptic spike arrives
+    pub weight_scale: f64,
+}
+
+impl Default for HybridParams {
+    fn default() -> Self {
+        Self {
+            t_step: 0.001,
+            steps_per_cycle: 10,
+            tau_rc: 0.02,
+            tau_ref: 0.002,
+            tau_s: 0.005,
+            max_rate: 200.,
+            // Roughly matches the integer engine's overall activity under
+            // chemotaxis stimulus
+            intercept: 0.3,
+            weight_scale: 1. / 20.,
+        }
+    }
+}
+
+struct HybridEdge {
+    pre: u16,
+    post: u16,
+    weight: f64,
+    synapse: FirstOrderSynapse,
+}
+
+/// Connectome simulator with one `FirstOrderLif` per neuron and one
+/// `FirstOrderSynapse` per edge, weighted like the integer engine.
+///
+/// A neural cycle is `steps_per_cycle` integration steps. A neuron counts as
+/// discharged if it spiked at least once during the cycle, and a muscle's value
+/// is the weighted spike count it received, as in the integer engine. Stimulus
+/// pings a neuron the same way: its outgoing synapses receive one spike.
+pub struct HybridConnectome {
+    params: HybridParams,
+    neurons_tot: u16,
+    neurons: Vec<FirstOrderLif>,
+    edges: Vec<HybridEdge>,
+
+    input: Vec<f64>,
+    spikes: Vec<f64>,
+    discharged: Vec<u8>,
+    muscle_current: Vec<i16>,
+    muscle_next: Vec<i16>,
+}
+
+impl HybridConnectome {
+    /// Build the LIF network on the same wiring as `connectome`.
+    pub fn from_connectome(connectome: &Connectome, params: HybridParams) -> Self {
+        let neurons_tot = connectome.neurons_tot();
+        let neurons = (0..neurons_tot)
+            .map(|_| {
+                let mut lif = FirstOrderLif::new(params.max_rate, params.intercept, 1);
+                lif.tau_rc = params.tau_rc;
+                lif.tau_ref = params.tau_ref;
+                let (gain, bias) = lif.gain_bias(params.max_rate, params.intercept);
+                lif.gain = gain;
+                lif.bias = bias;
+                lif
+            })
+            .collect();
+        let edges = connectome
+            .edges()
+            .into_iter()
+            .map(|e| HybridEdge {
+                pre: e.pre,
+                post: e.post,
+                weight: e.weight as f64,
+                synapse: FirstOrderSynapse {
+                    tau_s: params.tau_s,
+                    ..Default::default()
+                },
+            })
+            .collect();
+        let muscles = connectome.muscles_tot() as usize;
+
+        Self {
+            params,
+            neurons_tot,
+            neurons,
+            edges,
+            input: vec![0.; neurons_tot as usize],
+            spikes: vec![0.; neurons_tot as usize],
+            discharged: vec![0; neurons_tot as usize],
+            muscle_current: vec![0; muscles],
+            muscle_next: vec![0; muscles],
+        }
+    }
+
+    pub fn params(&self) -> &HybridParams {
+        &self.params
+    }
+
+    /// Membrane voltage of a neuron
+    pub fn voltage(&self, id: u16) -> f64 {
+        self.neurons[id as usize].v
+    }
+
+    pub fn reset(&mut self) {
+        self.neurons.iter_mut().for_each(|n| n.reset());
+        self.edges.iter_mut().for_each(|e| e.synapse.reset());
+        self.discharged.fill(0);
+        self.muscle_current.fill(0);
+        self.muscle_next.fill(0);
+    }
+
+    /// Advance the network by one integration step. `stim` neurons have a
+    /// spike injected into their outgoing synapses; other ids are ignored.
+    fn step(&mut self, stim_neuron: Option<&[u16]>) {
+        let t_step = self.params.t_step;
+
+        // Spikes emitted this step drive the synapses; stimulus adds to them
+        if let Some(stim) = stim_neuron {
+            for &id in stim {
+                if let Some(spike) = self.spikes.get_mut(id as usize) {
+                    *spike += 1. / t_step;
+                }
+            }
+        }
+
+        self.input.fill(0.);
+        for edge in self.edges.iter_mut() {
+            let spike = self.spikes[edge.pre as usize];
+            let out = edge.synapse.step(spike, t_step, None);
+            if edge.post < self.neurons_tot {
+                self.input[edge.post as usize] += edge.weight * out;
+            } else if spike > 0. {
+                let idx = (edge.post - self.neurons_tot) as usize;
+                self.muscle_next[idx] = self.muscle_next[idx].saturating_add(edge.weight as i16);
+            }
+        }
+
+        // A spike lifts a synapse's output by 1 / tau_s
+        let scale = self.params.weight_scale * self.params.tau_s;
+        for (i, n) in self.neurons.iter_mut().enumerate() {
+            let drive = self.input[i] * scale;
+            let out = n.step(drive * n.gain + n.bias, t_step, None);
+            self.spikes[i] = out;
+            if out > 0. {
+                self.discharged[i] = 1;
+            }
+        }
+    }
+
+    /// Complete one neural cycle (`steps_per_cycle` integration steps)
+    pub fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>) {
+        self.discharged.fill(0);
+        for step in 0..self.params.steps_per_cycle {
+            // Stimulus pings once per cycle, like the integer engine
+            self.step(if step == 0 { stim_neuron } else { None });
+        }
+        self.muscle_current.copy_from_slice(&self.muscle_next);
+        self.muscle_next.fill(0);
+    }
+
+    /// 1 for each neuron that discharged in the last cycle; 0 for muscles and
+    /// unknown ids
+    pub fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = self.discharged.get(id as usize).copied().unwrap_or(0);
+        }
+    }
+
+    /// Muscle values from the last cycle, queried by cell id; 0 for neurons
+    /// and unknown ids
+    pub fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = id
+                .checked_sub(self.neurons_tot)
+                .and_then(|m| self.muscle_current.get(m as usize))
+                .copied()
+                .unwrap_or(0);
+        }
+    }
+}
+
+impl NeuralEngine for HybridConnectome {
+    fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>) {
+        HybridConnectome::neural_cycle(self, stim_neuron);
+    }
+
+    fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]) {
+        HybridConnectome::discharge_query(self, input_id, query_result);
+    }
+
+    fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        HybridConnectome::muscle_query(self, input_id, query_result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::neuron_ids::CELLS;
+    use crate::emulations::c_elegans::preset;
+
+    #[test]
+    fn queries_and_stimulus_tolerate_ids_of_the_other_kind() {
+        let mut hybrid =
+            HybridConnectome::from_connectome(&preset::connectome(), HybridParams::default());
+        let muscle = CELLS - 1;
+        let beyond = CELLS + 10;
+        hybrid.neural_cycle(Some(&[0, muscle, beyond]));
+
+        let mut forces = [7; 3];
+        hybrid.muscle_query(&[0, 1, beyond], &mut forces);
+        assert_eq!(forces, [0, 0, 0]);
+        let mut fired = [7; 2];
+        hybrid.discharge_query(&[muscle, beyond], &mut fired);
+        assert_eq!(fired, [0, 0]);
+    }
+
+    #[test]
+    fn stimulus_pings_outgoing_synapses_like_the_integer_engine() {
+        let connectome = preset::connectome();
+        let edge = connectome
+            .edges()
+            .into_iter()
+            .find(|e| e.post >= connectome.neurons_tot())
+            .unwrap();
+        let mut hybrid = HybridConnectome::from_connectome(&connectome, HybridParams::default());
+        hybrid.neural_cycle(Some(&[edge.pre]));
+        let mut value = [0];
+        hybrid.muscle_query(&[edge.post], &mut value);
+        let mut expected = [0];
+        let mut integer = preset::connectome();
+        integer.neural_cycle(Some(&[edge.pre]));
+        integer.muscle_query(&[edge.post], &mut expected);
+        assert_ne!(value[0], 0);
+        assert_eq!(value, expected);
+
+        hybrid.reset();
+        hybrid.neural_cycle(None);
+        hybrid.muscle_query(&[edge.post], &mut value);
+        assert_eq!(value, [0]);
+    }
+
+    #[test]
+    fn chemotaxis_stimulus_makes_neurons_discharge() {
+        let stimulus = resolve_targets(&["chemotaxis"]).unwrap();
+        let neurons: Vec<u16> = (0..299).collect();
+        let mut hybrid =
+            HybridConnectome::from_connectome(&preset::connectome(), HybridParams::default());
+        let mut fired = vec![0; neurons.len()];
+        let mut active = 0;
+        for _ in 0..100 {
+            hybrid.neural_cycle(Some(&stimulus));
+            hybrid.discharge_query(&neurons, &mut fired);
+            active += fired.iter().filter(|&&d| d == 1).count();
+        }
+        assert!(active > 0, "nothing discharged under chemotaxis");
+    }
+}
@@ -0,0 +1,893 @@
+pub mod cells;
+pub mod community;
+pub mod decay;
+pub mod edge_list;
+pub mod engine;
+pub mod evolve;
+pub mod export;
+pub mod graph_stats;
+pub mod hybrid;
+pub mod mode_comparison;
+pub mod muscle_model;
+pub mod neuroml;
+pub mod neuromodulation;
+pub mod null_model;
+pub mod observer;
+pub mod propagation;
+pub mod reservoir;
+pub mod rom_image;
+pub mod spectral;
+pub mod update_mode;
+
+use std::any::Any;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::connectome::cells::CellRegistry;
+use crate::connectome::decay::DecayPolicy;
+use crate::connectome::muscle_model::MuscleModel;
+use crate::connectome::neuromodulation::Neuromodulation;
+use crate::connectome::observer::{CycleEvent, CycleObserver, ObserverId};
+use crate::connectome::update_mode::{DelayLine, UpdateMode};
+
+/// Struct for representing a neuron connection
+#[derive(Copy, Clone)]
+struct NeuronConnection {
+    id: u16,
+    weight: i8,
+}
+
+/// A weighted connection between two cells, as decoded from ROM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub pre: u16,
+    pub post: u16,
+    pub weight: i8,
+}
+
+/// Parse ROM word exactly like C
+fn parse_rom_word(rom_word: u16) -> NeuronConnection {
+    let [low, high] = rom_word.to_le_bytes();
+
+    // uint16_t id = rom_byte[1] + ((rom_byte[0] & 0x80) << 1);
+    let id = high as u16 + (((low & 0x80) as u16) << 1);
+
+    // uint8_t weight_bits = rom_byte[0] & 0x7F;
+    let mut weight_bits = low & 0x7F;
+
+    // weight_bits = weight_bits + ((weight_bits & 0x40) << 1);
+    weight_bits = weight_bits.wrapping_add((weight_bits & 0x40) << 1);
+
+    let weight = weight_bits as i8;
+
+    NeuronConnection { id, weight }
+}
+
+/// Inverse of `parse_rom_word`; `id` must fit in 9 bits and `weight` in 7.
+pub fn encode_rom_word(id: u16, weight: i8) -> Result<u16, String> {
+    if id > 0x1FF {
+        return Err(format!("Cell id {} does not fit in a ROM word", id));
+    }
+    if !(-64..=63).contains(&weight) {
+        return Err(format!(
+            "Weight {} is outside the ROM range -64..=63",
+            weight
+        ));
+    }
+    let low = (weight as u8 & 0x7F) | ((id >> 1) as u8 & 0x80);
+    let high = (id & 0xFF) as u8;
+    Ok(u16::from_le_bytes([low, high]))
+}
+
+/// Connectome struct (layout mirrors C)
+pub struct Connectome {
+    neurons_tot: u16,
+    rom: Vec<u16>,
+    cells: CellRegistry,
+    threshold: i8,
+
+    neuron_current: Vec<i8>,
+    neuron_next: Vec<i8>,
+
+    muscle_current: Vec<i16>,
+    muscle_next: Vec<i16>,
+
+    // meta: [discharged_bit | idle_ticks(7 bits)]
+    meta: Vec<u8>,
+
+    update_mode: UpdateMode,
+    order_rng: StdRng,
+    delay_line: DelayLine,
+    decay: DecayPolicy,
+    ablated: Vec<bool>,
+    modulation: Option<Neuromodulation>,
+    muscle_model: Option<MuscleModel>,
+
+    // observers are slots so that handed-out ids stay valid after unsubscribe
+    observers: Vec<Option<Box<dyn CycleObserver>>>,
+    events: Vec<CycleEvent>,
+    cycle: u64,
+    muscle_level: i16,
+}
+
+/// Saved engine state, see `Connectome::snapshot`
+#[derive(Clone)]
+pub struct ConnectomeSnapshot {
+    threshold: i8,
+    neuron_current: Vec<i8>,
+    neuron_next: Vec<i8>,
+    muscle_current: Vec<i16>,
+    muscle_next: Vec<i16>,
+    meta: Vec<u8>,
+    update_mode: UpdateMode,
+    order_rng: StdRng,
+    delay_line: DelayLine,
+    decay: DecayPolicy,
+    ablated: Vec<bool>,
+    modulation: Option<Neuromodulation>,
+    muscle_model: Option<MuscleModel>,
+    cycle: u64,
+}
+
+impl ConnectomeSnapshot {
+    /// Cycle count at the time of the snapshot
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+}
+
+impl Connectome {
+    /// Initialize a connectome from a ROM image covering `cells` cells
+    /// (neurons followed by muscles). The cells are named by id until a
+    /// registry is attached with `with_cells`.
+    pub fn from_rom(rom: Vec<u16>, cells: u16) -> Result<Self, String> {
+        let neurons_tot = *rom.first().ok_or("ROM is empty")?;
+        if neurons_tot > cells {
+            return Err(format!(
+                "ROM declares {} neurons but only {} cells",
+                neurons_tot, cells
+            ));
+        }
+        let table_end = neurons_tot as usize + 2;
+        if rom.len() < table_end {
+            return Err("ROM is shorter than its address table".to_string());
+        }
+        for id in 0..neurons_tot as usize {
+            let (start, end) = (rom[id + 1] as usize, rom[id + 2] as usize);
+            if start < table_end || start > end || end > rom.len() {
+                return Err(format!("ROM address table is invalid at neuron {}", id));
+            }
+            for &word in &rom[start..end] {
+                let target = parse_rom_word(word).id;
+                if target >= cells {
+                    return Err(format!(
+                        "ROM connects neuron {} to cell {}, beyond the {} cells",
+                        id, target, cells
+                    ));
+                }
+            }
+        }
+        let muscles_tot = cells - neurons_tot;
+
+        let neurons_usize = neurons_tot as usize;
+        let muscles_usize = muscles_tot as usize;
+
+        Ok(Self {
+            neurons_tot,
+            rom,
+            cells: CellRegistry::anonymous(neurons_tot, cells),
+            threshold: 40,
+
+            neuron_current: vec![0; neurons_usize],
+            neuron_next: vec![0; neurons_usize],
+
+            muscle_current: vec![0; muscles_usize],
+            muscle_next: vec![0; muscles_usize],
+
+            meta: vec![0; neurons_usize],
+
+            update_mode: UpdateMode::Sequential,
+            order_rng: StdRng::seed_from_u64(0),
+            delay_line: DelayLine::new(1, neurons_usize + muscles_usize),
+            decay: DecayPolicy::default(),
+            ablated: vec![false; cells as usize],
+            modulation: None,
+            muscle_model: None,
+
+            observers: vec![],
+            events: vec![],
+            cycle: 0,
+            muscle_level: 0,
+        })
+    }
+
+    /// Name the cells; the registry must list the same neurons and muscles
+    pub fn with_cells(mut self, cells: CellRegistry) -> Result<Self, String> {
+        if cells.neurons_tot() != self.neurons_tot || cells.muscles_tot() != self.muscles_tot() {
+            return Err(format!(
+                "Registry has {} neurons and {} muscles, the ROM {} and {}",
+                cells.neurons_tot(),
+                cells.muscles_tot(),
+                self.neurons_tot,
+                self.muscles_tot()
+            ));
+        }
+        self.cells = cells;
+        Ok(self)
+    }
+
+    /// Names, types and roles of the cells
+    pub fn cells(&self) -> &CellRegistry {
+        &self.cells
+    }
+
+    /// The ROM image this connectome runs on
+    pub fn rom(&self) -> &[u16] {
+        &self.rom
+    }
+
+    /// Neurons discharge when their state is strictly above the threshold
+    pub fn set_threshold(&mut self, threshold: i8) {
+        self.threshold = threshold;
+    }
+
+    pub fn threshold(&self) -> i8 {
+        self.threshold
+    }
+
+    /// Lesion a cell: it never discharges, and nothing is sent to or from it
+    pub fn ablate(&mut self, id: u16) {
+        self.ablated[id as usize] = true;
+        self.set_next_state(id, 0);
+        if id < self.neurons_tot {
+            self.neuron_current[id as usize] = 0;
+            self.meta[id as usize] = 0;
+        } else {
+            self.muscle_current[(id - self.neurons_tot) as usize] = 0;
+        }
+    }
+
+    pub fn is_ablated(&self, id: u16) -> bool {
+        self.ablated[id as usize]
+    }
+
+    /// Ids of every ablated cell
+    pub fn ablated(&self) -> Vec<u16> {
+        (0..self.ablated.len() as u16)
+            .filter(|&id| self.ablated[id as usize])
+            .collect()
+    }
+
+    /// Select how discharges are applied each cycle. Resets the shuffle RNG
+    /// and drops any contributions still in flight on delayed edges.
+    pub fn set_update_mode(&mut self, mode: UpdateMode) {
+        let cells = self.neuron_next.len() + self.muscle_next.len();
+        self.order_rng = match mode {
+            UpdateMode::RandomAsync { seed } => StdRng::seed_from_u64(seed),
+            _ => StdRng::seed_from_u64(0),
+        };
+        self.delay_line = match &mode {
+            UpdateMode::EventDriven(delays) => DelayLine::new(delays.max_delay(), cells),
+            _ => DelayLine::new(1, cells),
+        };
+        self.update_mode = mode;
+    }
+
+    pub fn update_mode(&self) -> &UpdateMode {
+        &self.update_mode
+    }
+
+    /// Select how neuron states relax between discharges.
+    pub fn set_decay_policy(&mut self, decay: DecayPolicy) -> Result<(), String> {
+        if let DecayPolicy::Leak(leak) = &decay
+            && leak.len() != self.neuron_current.len()
+        {
+            return Err(format!(
+                "Leak model covers {} neurons but the connectome has {}",
+                leak.len(),
+                self.neuron_current.len()
+            ));
+        }
+        self.decay = decay;
+        Ok(())
+    }
+
+    pub fn decay_policy(&self) -> &DecayPolicy {
+        &self.decay
+    }
+
+    /// Attach an observer that receives the events of every following cycle
+    pub fn subscribe(&mut self, observer: Box<dyn CycleObserver>) -> ObserverId {
+        self.observers.push(Some(observer));
+        ObserverId(self.observers.len() - 1)
+    }
+
+    /// Detach an observer and hand it back
+    pub fn unsubscribe(&mut self, id: ObserverId) -> Option<Box<dyn CycleObserver>> {
+        self.observers.get_mut(id.0)?.take()
+    }
+
+    /// Borrow a subscribed observer as its concrete type
+    pub fn observer<T: CycleObserver>(&self, id: ObserverId) -> Option<&T> {
+        let observer: &dyn Any = self.observers.get(id.0)?.as_deref()?;
+        observer.downcast_ref()
+    }
+
+    pub fn observer_mut<T: CycleObserver>(&mut self, id: ObserverId) -> Option<&mut T> {
+        let observer: &mut dyn Any = self.observers.get_mut(id.0)?.as_deref_mut()?;
+        observer.downcast_mut()
+    }
+
+    /// Muscles emit a crossing event when their value passes this level
+    pub fn set_muscle_level(&mut self, level: i16) {
+        self.muscle_level = level;
+    }
+
+    pub fn muscle_level(&self) -> i16 {
+        self.muscle_level
+    }
+
+    /// Neural cycles completed so far; observers see the index of the cycle
+    /// being reported, starting from 0
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    fn observed(&self) -> bool {
+        self.observers.iter().any(Option::is_some)
+    }
+
+    fn emit(&mut self, event: CycleEvent) {
+        if self.observed() {
+            self.events.push(event);
+        }
+    }
+
+    /// Deliver the buffered events of the cycle that just completed
+    fn notify_observers(&mut self) {
+        if !self.observed() {
+            return;
+        }
+        let mut observers = std::mem::take(&mut self.observers);
+        let events = std::mem::take(&mut self.events);
+        for observer in observers.iter_mut().flatten() {
+            for event in &events {
+                observer.on_event(self.cycle, event);
+            }
+            observer.on_cycle_end(self.cycle, self);
+        }
+        self.observers = observers;
+        self.events = events;
+        self.events.clear();
+    }
+
+    /// Add a slow modulatory layer that shifts thresholds and input gains
+    pub fn set_neuromodulation(&mut self, modulation: Option<Neuromodulation>) {
+        self.modulation = modulation;
+    }
+
+    pub fn neuromodulation(&self) -> Option<&Neuromodulation> {
+        self.modulation.as_ref()
+    }
+
+    pub fn neuromodulation_mut(&mut self) -> Option<&mut Neuromodulation> {
+        self.modulation.as_mut()
+    }
+
+    /// Filter muscle input through activation dynamics; see `muscle_force_query`
+    pub fn set_muscle_model(&mut self, model: Option<MuscleModel>) -> Result<(), String> {
+        if let Some(model) = &model
+            && model.len() != self.muscle_current.len()
+        {
+            return Err(format!(
+                "Muscle model covers {} muscles but the connectome has {}",
+                model.len(),
+                self.muscle_current.len()
+            ));
+        }
+        self.muscle_model = model;
+        Ok(())
+    }
+
+    pub fn muscle_model(&self) -> Option<&MuscleModel> {
+        self.muscle_model.as_ref()
+    }
+
+    pub fn muscle_model_mut(&mut self) -> Option<&mut MuscleModel> {
+        self.muscle_model.as_mut()
+    }
+
+    /// Save everything but the wiring and the observers
+    pub fn snapshot(&self) -> ConnectomeSnapshot {
+        ConnectomeSnapshot {
+            threshold: self.threshold,
+            neuron_current: self.neuron_current.clone(),
+            neuron_next: self.neuron_next.clone(),
+            muscle_current: self.muscle_current.clone(),
+            muscle_next: self.muscle_next.clone(),
+            meta: self.meta.clone(),
+            update_mode: self.update_mode.clone(),
+            order_rng: self.order_rng.clone(),
+            delay_line: self.delay_line.clone(),
+            decay: self.decay.clone(),
+            ablated: self.ablated.clone(),
+            modulation: self.modulation.clone(),
+            muscle_model: self.muscle_model.clone(),
+            cycle: self.cycle,
+        }
+    }
+
+    /// Return to a snapshot taken from a connectome with the same cells
+    pub fn restore(&mut self, snapshot: &ConnectomeSnapshot) -> Result<(), String> {
+        if snapshot.neuron_current.len() != self.neuron_current.len()
+            || snapshot.muscle_current.len() != self.muscle_current.len()
+        {
+            return Err("Snapshot was taken from a connectome with other cells".to_string());
+        }
+        let snapshot = snapshot.clone();
+        self.threshold = snapshot.threshold;
+        self.neuron_current = snapshot.neuron_current;
+        self.neuron_next = snapshot.neuron_next;
+        self.muscle_current = snapshot.muscle_current;
+        self.muscle_next = snapshot.muscle_next;
+        self.meta = snapshot.meta;
+        self.update_mode = snapshot.update_mode;
+        self.order_rng = snapshot.order_rng;
+        self.delay_line = snapshot.delay_line;
+        self.decay = snapshot.decay;
+        self.ablated = snapshot.ablated;
+        self.modulation = snapshot.modulation;
+        self.muscle_model = snapshot.muscle_model;
+        self.cycle = snapshot.cycle;
+        Ok(())
+    }
+
+    pub fn neurons_tot(&self) -> u16 {
+        self.neurons_tot
+    }
+
+    pub fn muscles_tot(&self) -> u16 {
+        self.muscle_current.len() as u16
+    }
+
+    /// Get current state (ctm_get_current_state)
+    fn get_current_state(&self, id: u16) -> i16 {
+        if id < self.neurons_tot {
+            self.neuron_current[id as usize] as i16
+        } else {
+            self.muscle_current[(id - self.neurons_tot) as usize]
+        }
+    }
+
+    /// Get next state (ctm_get_next_state)
+    fn get_next_state(&self, id: u16) -> i16 {
+        if id < self.neurons_tot {
+            self.neuron_next[id as usize] as i16
+        } else {
+            self.muscle_next[(id - self.neurons_tot) as usize]
+        }
+    }
+
+    /// Set next state with saturation (ctm_set_next_state)
+    fn set_next_state(&mut self, id: u16, val: i16) {
+        if id < self.neurons_tot {
+            let clipped = val.clamp(-128, 127) as i8;
+            if clipped as i16 != val {
+                self.emit(CycleEvent::Saturation {
+                    id,
+                    attempted: val,
+                    clipped,
+                });
+            }
+            self.neuron_next[id as usize] = clipped;
+        } else {
+            self.muscle_next[(id - self.neurons_tot) as usize] = val;
+        }
+    }
+
+    /// Add to next state (ctm_add_to_next_state)
+    fn add_to_next_state(&mut self, id: u16, val: i8) {
+        let curr = self.get_next_state(id);
+        self.set_next_state(id, curr + val as i16);
+    }
+
+    /// Iterate state (ctm_iterate_state)
+    fn iterate_state(&mut self) {
+        if self.observed() {
+            let level = self.muscle_level;
+            for (i, (&before, &after)) in self
+                .muscle_current
+                .iter()
+                .zip(&self.muscle_next)
+                .enumerate()
+            {
+                let rising = before <= level && after > level;
+                if rising || (before > level && after <= level) {
+                    self.events.push(CycleEvent::MuscleCrossing {
+                        id: self.neurons_tot + i as u16,
+                        value: after,
+                        rising,
+                    });
+                }
+            }
+        }
+        self.neuron_current.copy_from_slice(&self.neuron_next);
+        self.muscle_current.copy_from_slice(&self.muscle_next);
+        if let Some(model) = &mut self.muscle_model {
+            model.update(&self.muscle_current);
+        }
+        self.muscle_next.fill(0);
+    }
+
+    /// Meta flag discharge (ctm_meta_flag_discharge)
+    fn meta_flag_discharge(&mut self, id: u16, val: u8) {
+        let idx = id as usize;
+        if val == 0 {
+            self.meta[idx] &= 0x7F;
+        } else {
+            self.meta[idx] = 0x80;
+            self.emit(CycleEvent::Discharge { id });
+        }
+    }
+
+    /// Handle idle neurons (ctm_meta_handle_idle_neurons)
+    fn meta_handle_idle_neurons(&mut self, max_idle: u8) {
+        let max_idle = max_idle.min(126);
+        for i in 0..self.neurons_tot {
+            let idx = i as usize;
+
+            let low = self.meta[idx] & 0x7F;
+            let high = self.meta[idx] & 0x80;
+
+            let mut idle_ticks = low;
+
+            if self.get_next_state(i) == self.get_current_state(i) {
+                self.meta[idx] = self.meta[idx].wrapping_add(1);
+                idle_ticks = idle_ticks.wrapping_add(1);
+            } else {
+                self.meta[idx] = high;
+            }
+
+            if idle_ticks > max_idle {
+                if self.get_next_state(i) != 0 {
+                    self.emit(CycleEvent::IdleReset { id: i });
+                }
+                self.set_next_state(i, 0);
+                self.meta[idx] = high;
+            }
+        }
+    }
+
+    /// Every connection in the wiring, in ROM order, skipping ablated cells
+    pub fn edges(&self) -> Vec<Edge> {
+        (0..self.neurons_tot)
+            .flat_map(|pre| {
+                self.rom_range(pre).filter_map(move |k| {
+                    let conn = parse_rom_word(self.rom[k]);
+                    let live = !self.ablated[pre as usize] && !self.ablated[conn.id as usize];
+                    live.then_some(Edge {
+                        pre,
+                        post: conn.id,
+                        weight: conn.weight,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// A standalone connectome holding only `cells` and the connections
+    /// among them, renumbered into a compact ROM: kept neurons first, then
+    /// kept muscles, each in id order. The cells keep their names and types
+    /// in the new registry. The threshold is carried over, other settings
+    /// start at their defaults.
+    pub fn subcircuit(&self, cells: &[u16]) -> Result<Connectome, String> {
+        let keep = self.kept_cells(cells)?;
+        let kept: Vec<u16> = (0..keep.len() as u16)
+            .filter(|&id| keep[id as usize])
+            .collect();
+        let mut renumber = vec![None; keep.len()];
+        for (new, &old) in kept.iter().enumerate() {
+            renumber[old as usize] = Some(new as u16);
+        }
+        let edges: Vec<Edge> = self
+            .edges()
+            .into_iter()
+            .filter_map(|e| {
+                Some(Edge {
+                    pre: renumber[e.pre as usize]?,
+                    post: renumber[e.post as usize]?,
+                    weight: e.weight,
+                })
+            })
+            .collect();
+        // The registry always covers every cell of the layout
+        let registry = CellRegistry::new(
+            kept.iter()
+                .filter_map(|&id| self.cells.get(id).cloned())
+                .collect(),
+        )?;
+        let neurons_tot = registry.neurons_tot();
+        let mut sub =
+            Connectome::from_rom(rom_image::build_rom(neurons_tot, &edges)?, registry.len())?
+                .with_cells(registry)?;
+        sub.set_threshold(self.threshold);
+        Ok(sub)
+    }
+
+    /// Like `subcircuit`, but keeping the full cell layout: ids keep their
+    /// meaning, so names, groups and protocols apply unchanged, and every
+    /// other cell is ablated.
+    pub fn subcircuit_keeping_ids(&self, cells: &[u16]) -> Result<Connectome, String> {
+        let keep = self.kept_cells(cells)?;
+        let total = keep.len() as u16;
+        let edges: Vec<Edge> = self
+            .edges()
+            .into_iter()
+            .filter(|e| keep[e.pre as usize] && keep[e.post as usize])
+            .collect();
+        let mut sub = Connectome::from_rom(rom_image::build_rom(self.neurons_tot, &edges)?, total)?
+            .with_cells(self.cells.clone())?;
+        sub.set_threshold(self.threshold);
+        for id in (0..total).filter(|&id| !keep[id as usize]) {
+            sub.ablate(id);
+        }
+        Ok(sub)
+    }
+
+    /// Flags of the cells to keep, checking every id is in range
+    fn kept_cells(&self, cells: &[u16]) -> Result<Vec<bool>, String> {
+        let mut keep = vec![false; (self.neurons_tot + self.muscles_tot()) as usize];
+        for &id in cells {
+            *keep
+                .get_mut(id as usize)
+                .ok_or_else(|| format!("Cell {} is out of range", id))? = true;
+        }
+        Ok(keep)
+    }
+
+    /// ROM addresses of a neuron's outgoing connections
+    fn rom_range(&self, id: u16) -> std::ops::Range<usize> {
+        let address = self.rom[id as usize + 1] as usize;
+        let end = self.rom[id as usize + 2] as usize;
+        address..end
+    }
+
+    /// Propagate connections (ctm_ping_neuron)
+    fn ping_neuron(&mut self, id: u16) {
+        if self.ablated[id as usize] {
+            return;
+        }
+        for k in self.rom_range(id) {
+            let conn = parse_rom_word(self.rom[k]);
+            if !self.ablated[conn.id as usize] {
+                self.add_to_next_state(conn.id, self.modulated_weight(conn.id, conn.weight));
+            }
+        }
+    }
+
+    fn above_threshold(&self, id: u16) -> bool {
+        let offset = self
+            .modulation
+            .as_ref()
+            .map_or(0, |m| m.threshold_offset(id));
+        !self.ablated[id as usize] && self.get_current_state(id) > self.threshold as i16 + offset
+    }
+
+    /// A connection's weight scaled by the receiving neuron's modulated gain
+    fn modulated_weight(&self, post: u16, weight: i8) -> i8 {
+        match &self.modulation {
+            Some(m) => (weight as f32 * m.gain(post).max(0.))
+                .round()
+                .clamp(-128., 127.) as i8,
+            None => weight,
+        }
+    }
+
+    /// Discharge neuron (ctm_discharge_neuron)
+    fn discharge_neuron(&mut self, id: u16) {
+        self.ping_neuron(id);
+        self.set_next_state(id, 0);
+    }
+
+    /// Complete one neural cycle (ctm_neural_cycle)
+    pub fn neural_cycle(&mut self, stim_neuron: Option<&[u16]>) {
+        // Leak acts on the carried-over state, before this cycle's input arrives
+        if let DecayPolicy::Leak(leak) = &mut self.decay {
+            leak.apply(&mut self.neuron_next);
+        }
+
+        // Only neurons can be pinged; muscles have no outgoing connections
+        if let Some(stim) = stim_neuron {
+            for &id in stim {
+                if id < self.neurons_tot {
+                    self.ping_neuron(id);
+                }
+            }
+        }
+
+        match self.update_mode {
+            UpdateMode::Sequential => {
+                for i in 0..self.neurons_tot {
+                    self.fire_if_above(i);
+                }
+            }
+            UpdateMode::RandomAsync { .. } => {
+                let mut order: Vec<u16> = (0..self.neurons_tot).collect();
+                order.shuffle(&mut self.order_rng);
+                for i in order {
+                    self.fire_if_above(i);
+                }
+            }
+            UpdateMode::Synchronous | UpdateMode::EventDriven(_) => {
+                self.synchronous_discharge();
+            }
+        }
+
+        if let Some(modulation) = &mut self.modulation {
+            let meta = &self.meta;
+            modulation.update(|id| meta[id as usize] & 0x80 != 0);
+        }
+
+        if let DecayPolicy::IdleReset { max_idle } = self.decay {
+            self.meta_handle_idle_neurons(max_idle);
+        }
+        self.iterate_state();
+        self.notify_observers();
+        self.cycle += 1;
+    }
+
+    /// Discharge a single neuron in place if it is above threshold
+    fn fire_if_above(&mut self, id: u16) {
+        if self.above_threshold(id) {
+            self.discharge_neuron(id);
+            self.meta_flag_discharge(id, 1);
+        } else {
+            self.meta_flag_discharge(id, 0);
+        }
+    }
+
+    /// Decide every discharge from the current state, then apply the summed
+    /// contributions once so the result does not depend on neuron order.
+    fn synchronous_discharge(&mut self) {
+        let cells = self.neuron_next.len() + self.muscle_next.len();
+        let mut acc = vec![0i32; cells];
+        // Taken out for the duration of the cycle so edges can be looked up
+        // while the state is mutated.
+        let mode = std::mem::take(&mut self.update_mode);
+        let delays = match &mode {
+            UpdateMode::EventDriven(delays) => Some(delays),
+            _ => None,
+        };
+        if delays.is_some() {
+            self.delay_line.drain_due(&mut acc);
+        }
+
+        for i in 0..self.neurons_tot {
+            let discharged = self.above_threshold(i);
+            self.meta_flag_discharge(i, discharged as u8);
+            if !discharged {
+                continue;
+            }
+            self.set_next_state(i, 0);
+            for k in self.rom_range(i) {
+                let conn = parse_rom_word(self.rom[k]);
+                if self.ablated[conn.id as usize] {
+                    continue;
+                }
+                let weight = self.modulated_weight(conn.id, conn.weight);
+                match delays.map(|d| d.delay(i, conn.id)) {
+                    Some(delay) if delay > 1 => {
+                        self.delay_line.schedule(delay, conn.id, weight);
+                    }
+                    _ => acc[conn.id as usize] += weight as i32,
+                }
+            }
+        }
+
+        if delays.is_some() {
+            self.delay_line.advance();
+        }
+        self.update_mode = mode;
+
+        for (id, &val) in acc.iter().enumerate() {
+            if val != 0 {
+                let id = id as u16;
+                let curr = self.get_next_state(id) as i32;
+                let sum = (curr + val).clamp(i16::MIN as i32, i16::MAX as i32);
+                self.set_next_state(id, sum as i16);
+            }
+        }
+    }
+
+    /// 1 for each neuron that discharged in the last cycle, 0 otherwise
+    /// (and for muscles)
+    pub fn discharge_query(&self, input_id: &[u16], query_result: &mut [u8]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = self.meta.get(id as usize).map_or(0, |m| m >> 7);
+        }
+    }
+
+    /// State of any cell, neuron or muscle, from the last cycle
+    pub fn state_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        for (id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = self.get_current_state(*id);
+        }
+    }
+
+    /// Muscle values from the last cycle, queried by cell id (`NeuronId` for muscles)
+    pub fn muscle_query(&self, input_id: &[u16], query_result: &mut [i16]) {
+        self.state_query(input_id, query_result);
+    }
+
+    /// Muscle force from the muscle model, or the raw muscle value without one
+    pub fn muscle_force_query(&self, input_id: &[u16], query_result: &mut [f32]) {
+        for (&id, result) in input_id.iter().zip(query_result.iter_mut()) {
+            *result = match &self.muscle_model {
+                Some(model) if id >= self.neurons_tot => {
+                    model.force((id - self.neurons_tot) as usize)
+                }
+                _ => self.get_current_state(id) as f32,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    const CHAIN: &str = "threshold 20\n\
+        neuron A sensory\nneuron B inter\nneuron C motor\nmuscle M body\n\
+        A B 30\nB C 30\nC A 5\nC M 40\nA M 10\n";
+
+    #[test]
+    fn subcircuit_renumbers_and_keeps_names() {
+        let full = Circuit::parse(CHAIN).unwrap().connectome().unwrap();
+        // B, C and M, given out of order
+        let sub = full.subcircuit(&[3, 2, 1]).unwrap();
+        assert_eq!((sub.neurons_tot(), sub.muscles_tot()), (2, 1));
+        assert_eq!(sub.threshold(), 20);
+        let names: Vec<&str> = sub.cells().iter().map(|(_, c)| c.name.as_str()).collect();
+        assert_eq!(names, ["B", "C", "M"]);
+        assert_eq!(sub.cells().kind(2), Some("body"));
+        let edges: Vec<(u16, u16, i8)> = sub
+            .edges()
+            .iter()
+            .map(|e| (e.pre, e.post, e.weight))
+            .collect();
+        assert_eq!(edges, [(0, 1, 30), (1, 2, 40)]);
+        assert!(full.subcircuit(&[4]).is_err());
+    }
+
+    #[test]
+    fn subcircuit_keeping_ids_ablates_the_rest() {
+        let full = Circuit::parse(CHAIN).unwrap().connectome().unwrap();
+        let sub = full.subcircuit_keeping_ids(&[1, 2, 3]).unwrap();
+        assert_eq!((sub.neurons_tot(), sub.muscles_tot()), (3, 1));
+        assert_eq!(sub.cells().name(1), "B");
+        assert_eq!(sub.ablated(), [0]);
+        let edges: Vec<(u16, u16)> = sub.edges().iter().map(|e| (e.pre, e.post)).collect();
+        assert_eq!(edges, [(1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn from_rom_checks_connection_targets() {
+        // one neuron with a single connection of weight 10 to cell 5
+        let rom = vec![1, 3, 4, (5 << 8) | 10];
+        let err = Connectome::from_rom(rom.clone(), 2).err().unwrap();
+        assert!(err.contains("cell 5"));
+        assert!(Connectome::from_rom(rom, 6).is_ok());
+    }
+
+    #[test]
+    fn muscles_are_neither_pinged_nor_discharged() {
+        let mut connectome = Circuit::parse(CHAIN).unwrap().connectome().unwrap();
+        connectome.neural_cycle(Some(&[3, 4, 300]));
+        let mut flags = [1; 3];
+        connectome.discharge_query(&[3, 4, 300], &mut flags);
+        assert_eq!(flags, [0, 0, 0]);
+    }
+}
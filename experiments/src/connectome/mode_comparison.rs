@@ -0,0 +1,149 @@
+use std::fmt;
+
+use crate::connectome::{Connectome, update_mode::UpdateMode};
+
+/// How far one update mode drifts from the reference mode over a run.
+#[derive(Clone, Debug)]
+pub struct ModeDivergence {
+    pub mode: UpdateMode,
+    /// Fraction of (cycle, neuron) pairs whose discharge flag differs from the reference.
+    pub discharge_mismatch: f64,
+    /// Mean absolute difference in per-neuron firing rate (discharges per cycle).
+    pub firing_rate_delta: f64,
+    /// Mean absolute difference of muscle values per cycle and muscle.
+    pub muscle_delta: f64,
+    /// First cycle where any discharge differs, if any.
+    pub first_divergent_cycle: Option<usize>,
+}
+
+impl fmt::Display for ModeDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first = self
+            .first_divergent_cycle
+            .map_or("never".to_string(), |c| c.to_string());
+        write!(
+            f,
+            "{:<28} mismatch {:>7.4}  rate delta {:>7.4}  muscle delta {:>8.3}  first divergence {}",
+            self.mode.to_string(),
+            self.discharge_mismatch,
+            self.firing_rate_delta,
+            self.muscle_delta,
+            first
+        )
+    }
+}
+
+/// Run a fresh connectome from `build` per mode in lockstep under the same
+/// stimulus and report how much each one diverges from `modes[0]`. Each mode
+/// replaces the update mode `build` sets.
+pub fn compare_update_modes(
+    build: &dyn Fn() -> Result<Connectome, String>,
+    modes: &[UpdateMode],
+    stim_neuron: Option<&[u16]>,
+    cycles: usize,
+) -> Result<Vec<ModeDivergence>, String> {
+    let mut connectomes = vec![];
+    for mode in modes {
+        let mut c = build()?;
+        c.set_update_mode(mode.clone());
+        connectomes.push(c);
+    }
+    let Some(reference) = connectomes.first() else {
+        return Ok(vec![]);
+    };
+
+    let neurons: Vec<u16> = (0..reference.neurons_tot()).collect();
+    let muscles: Vec<u16> =
+        (reference.neurons_tot()..reference.neurons_tot() + reference.muscles_tot()).collect();
+
+    let mut discharges = vec![vec![0u8; neurons.len()]; modes.len()];
+    let mut muscle_values = vec![vec![0i16; muscles.len()]; modes.len()];
+    let mut fire_counts = vec![vec![0usize; neurons.len()]; modes.len()];
+    let mut mismatches = vec![0usize; modes.len()];
+    let mut muscle_error = vec![0f64; modes.len()];
+    let mut first_divergent_cycle = vec![None; modes.len()];
+
+    for cycle in 0..cycles {
+        for (m, c) in connectomes.iter_mut().enumerate() {
+            c.neural_cycle(stim_neuron);
+            c.discharge_query(&neurons, &mut discharges[m]);
+            c.muscle_query(&muscles, &mut muscle_values[m]);
+            for (count, &d) in fire_counts[m].iter_mut().zip(discharges[m].iter()) {
+                *count += d as usize;
+            }
+        }
+
+        for m in 1..modes.len() {
+            let diff = discharges[m]
+                .iter()
+                .zip(discharges[0].iter())
+                .filter(|(a, b)| a != b)
+                .count();
+            if diff > 0 && first_divergent_cycle[m].is_none() {
+                first_divergent_cycle[m] = Some(cycle);
+            }
+            mismatches[m] += diff;
+            muscle_error[m] += muscle_values[m]
+                .iter()
+                .zip(muscle_values[0].iter())
+                .map(|(&a, &b)| (a as f64 - b as f64).abs())
+                .sum::<f64>();
+        }
+    }
+
+    let neuron_cycles = (cycles * neurons.len()).max(1) as f64;
+    let muscle_cycles = (cycles * muscles.len()).max(1) as f64;
+    Ok(modes
+        .iter()
+        .enumerate()
+        .map(|(m, mode)| ModeDivergence {
+            mode: mode.clone(),
+            discharge_mismatch: mismatches[m] as f64 / neuron_cycles,
+            firing_rate_delta: fire_counts[m]
+                .iter()
+                .zip(fire_counts[0].iter())
+                .map(|(&a, &b)| (a as f64 - b as f64).abs())
+                .sum::<f64>()
+                / neuron_cycles,
+            muscle_delta: muscle_error[m] / muscle_cycles,
+            first_divergent_cycle: first_divergent_cycle[m],
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::preset;
+
+    #[test]
+    fn reference_never_diverges_and_identical_modes_agree() {
+        let stimulus = resolve_targets(&["chemotaxis"]).unwrap();
+        let modes = [
+            UpdateMode::Sequential,
+            UpdateMode::Sequential,
+            UpdateMode::Synchronous,
+        ];
+        let divergence =
+            compare_update_modes(&|| Ok(preset::connectome()), &modes, Some(&stimulus), 300)
+                .unwrap();
+        for same in &divergence[..2] {
+            assert_eq!(same.discharge_mismatch, 0.);
+            assert_eq!(same.first_divergent_cycle, None);
+        }
+        assert!(divergence[2].discharge_mismatch > 0.);
+    }
+
+    #[test]
+    fn build_errors_are_returned() {
+        let err = compare_update_modes(
+            &|| Err("no wiring".to_string()),
+            &[UpdateMode::Sequential],
+            None,
+            10,
+        )
+        .unwrap_err();
+        assert_eq!(err, "no wiring");
+    }
+}
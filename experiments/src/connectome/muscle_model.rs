@@ -0,0 +1,175 @@
+/// Parameters of the muscle model.
+#[derive(Clone, Debug)]
+pub struct MuscleParams {
+    /// Cycles for activation to rise towards a stronger drive
+    pub tau_activation: f32,
+    /// Cycles for activation to relax towards a weaker drive
+    pub tau_relaxation: f32,
+    /// Muscle input at which the driven activation reaches one half
+    pub half_activation: f32,
+    /// Fraction of the drive removed at full activation of the antagonist
+    pub antagonism: f32,
+    /// Force at full activation
+    pub max_force: f32,
+}
+
+impl Default for MuscleParams {
+    fn default() -> Self {
+        Self {
+            tau_activation: 3.,
+            tau_relaxation: 10.,
+            half_activation: 5.,
+            antagonism: 0.5,
+            max_force: 1.,
+        }
+    }
+}
+
+/// First-order muscle activation driven by the connectome's muscle input.
+///
+/// Each cycle the input saturates to a drive in `0..1` as
+/// `x / (x + half_activation)`, is reduced by the antagonist's activation
+/// (dorsal against ventral in the same segment), and the activation follows
+/// it with separate rise and relaxation time constants. Force is
+/// proportional to activation.
+#[derive(Clone, Debug)]
+pub struct MuscleModel {
+    params: MuscleParams,
+    antagonist: Vec<Option<usize>>,
+    activation: Vec<f32>,
+}
+
+impl MuscleModel {
+    /// A model for `muscles` muscles, indexed from the first muscle cell
+    pub fn new(muscles: usize, params: MuscleParams) -> Self {
+        Self {
+            params,
+            antagonist: vec![None; muscles],
+            activation: vec![0.; muscles],
+        }
+    }
+
+    /// Make each pair of muscle indices inhibit each other
+    pub fn with_antagonists(mut self, pairs: &[(usize, usize)]) -> Self {
+        for &(a, b) in pairs {
+            self.antagonist[a] = Some(b);
+            self.antagonist[b] = Some(a);
+        }
+        self
+    }
+
+    pub fn params(&self) -> &MuscleParams {
+        &self.params
+    }
+
+    pub fn len(&self) -> usize {
+        self.activation.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.activation.is_empty()
+    }
+
+    pub fn antagonist(&self, muscle: usize) -> Option<usize> {
+        self.antagonist[muscle]
+    }
+
+    /// Activation of a muscle index, in `0..1`
+    pub fn activation(&self, muscle: usize) -> f32 {
+        self.activation[muscle]
+    }
+
+    pub fn force(&self, muscle: usize) -> f32 {
+        self.params.max_force * self.activation[muscle]
+    }
+
+    pub fn reset(&mut self) {
+        self.activation.fill(0.);
+    }
+
+    /// Advance one cycle on the summed input each muscle received
+    pub fn update(&mut self, input: &[i16]) {
+        let p = &self.params;
+        let previous = self.activation.clone();
+        for (i, (a, &x)) in self.activation.iter_mut().zip(input).enumerate() {
+            let x = x.max(0) as f32;
+            let mut drive = x / (x + p.half_activation);
+            if let Some(j) = self.antagonist[i] {
+                drive *= (1. - p.antagonism * previous[j]).max(0.);
+            }
+            let tau = if drive > *a {
+                p.tau_activation
+            } else {
+                p.tau_relaxation
+            };
+            *a += (drive - *a) * (1. - (-1. / tau).exp());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    #[test]
+    fn activation_rises_fast_and_relaxes_slowly() {
+        let mut model = MuscleModel::new(1, MuscleParams::default());
+        model.update(&[5]);
+        // input at half activation drives 0.5, approached with tau 3
+        let rise = 0.5 * (1. - (-1f32 / 3.).exp());
+        assert!((model.activation(0) - rise).abs() < 1e-6);
+        for _ in 0..50 {
+            model.update(&[5]);
+        }
+        assert!((model.activation(0) - 0.5).abs() < 1e-3);
+        model.update(&[-20]);
+        let relaxed = 0.5 * (-1f32 / 10.).exp();
+        assert!((model.activation(0) - relaxed).abs() < 1e-3);
+        assert!((model.force(0) - model.activation(0)).abs() < 1e-6);
+        model.reset();
+        assert_eq!(model.activation(0), 0.);
+    }
+
+    #[test]
+    fn antagonists_weaken_each_other() {
+        let mut free = MuscleModel::new(2, MuscleParams::default());
+        let mut paired = MuscleModel::new(2, MuscleParams::default()).with_antagonists(&[(0, 1)]);
+        assert_eq!(paired.antagonist(1), Some(0));
+        assert_eq!(free.antagonist(1), None);
+        for _ in 0..30 {
+            free.update(&[20, 20]);
+            paired.update(&[20, 20]);
+        }
+        assert!(paired.activation(0) < free.activation(0) - 0.1);
+        assert!((paired.activation(0) - paired.activation(1)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn connectome_reports_force_through_the_model() {
+        let mut connectome = Circuit::parse("neuron A\nmuscle M\nA M 40\n")
+            .unwrap()
+            .connectome()
+            .unwrap();
+        assert!(
+            connectome
+                .set_muscle_model(Some(MuscleModel::new(2, MuscleParams::default())))
+                .is_err()
+        );
+        let params = MuscleParams {
+            max_force: 2.,
+            ..MuscleParams::default()
+        };
+        connectome
+            .set_muscle_model(Some(MuscleModel::new(1, params)))
+            .unwrap();
+        let mut force = [0.];
+        connectome.muscle_force_query(&[1], &mut force);
+        assert_eq!(force[0], 0.);
+        for _ in 0..5 {
+            connectome.neural_cycle(Some(&[0]));
+        }
+        connectome.muscle_force_query(&[1], &mut force);
+        assert!(force[0] > 0. && force[0] <= 2.);
+    }
+}
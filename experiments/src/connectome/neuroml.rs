@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use crate::connectome::Edge;
+use crate::connectome::hybrid::HybridParams;
+use crate::lify_stuff::first_order_lif::FirstOrderLif;
+
+const NEURON: &str = "neuron";
+const MUSCLE: &str = "muscle";
+const SYNAPSE: &str = "synapse";
+const BIAS: &str = "bias";
+const STIMULUS: &str = "stimulus";
+
+/// A connectome as a NeuroML 2 network: one single-cell population per cell,
+/// one projection per edge and one input list per stimulated cell.
+///
+/// Neurons are `iafRefCell`s with the `FirstOrderLif` parameters of the
+/// hybrid engine (membrane time constant `C / leakConductance`, threshold
+/// `v_th`, refractory period `tau_ref`); their gain and bias follow from the
+/// `max_rate` and `intercept` properties, and the bias is also given as a
+/// constant current so other simulators see the same tuning. Muscles are
+/// passive `iafCell`s. Every edge uses one `expOneSynapse` with the
+/// `FirstOrderSynapse` time constant, its ROM weight as connection weight.
+/// The integer engine's threshold and the remaining `HybridParams` are
+/// stored as properties of the network.
+#[derive(Clone, Debug)]
+pub struct NeuroMlNetwork {
+    pub id: String,
+    pub neurons_tot: u16,
+    pub cells: u16,
+    pub edges: Vec<Edge>,
+    /// Threshold of the integer engine, if the document gives one
+    pub threshold: Option<i8>,
+    pub params: HybridParams,
+    /// Cells driven by the stimulus current
+    pub inputs: Vec<u16>,
+}
+
+impl NeuroMlNetwork {
+    /// Write the network as a NeuroML 2 document, naming each cell's
+    /// population with `name`.
+    pub fn write<W: Write>(&self, mut w: W, name: impl Fn(u16) -> String) -> Result<(), String> {
+        self.write_xml(&mut w, &name).map_err(|err| err.to_string())
+    }
+
+    fn write_xml(&self, w: &mut dyn Write, name: &dyn Fn(u16) -> String) -> std::io::Result<()> {
+        let p = &self.params;
+        let mut lif = FirstOrderLif::new(p.max_rate, p.intercept, 1);
+        lif.tau_rc = p.tau_rc;
+        lif.tau_ref = p.tau_ref;
+        let (_, bias) = lif.gain_bias(p.max_rate, p.intercept);
+
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<neuroml xmlns="http://www.neuroml.org/schema/neuroml2" id="{}">"#,
+            self.id
+        )?;
+        // With leakConductance 1 nS, currents in nA map to volts of input
+        writeln!(
+            w,
+            r#"  <iafRefCell id="{}" C="{}nF" leakConductance="1nS" leakReversal="0V" thresh="{}V" reset="0V" refract="{}s">"#,
+            NEURON, p.tau_rc, lif.v_th, p.tau_ref
+        )?;
+        writeln!(
+            w,
+            r#"    <property tag="max_rate" value="{}"/>"#,
+            p.max_rate
+        )?;
+        writeln!(
+            w,
+            r#"    <property tag="intercept" value="{}"/>"#,
+            p.intercept
+        )?;
+        writeln!(w, "  </iafRefCell>")?;
+        writeln!(
+            w,
+            r#"  <iafCell id="{}" C="1nF" leakConductance="1nS" leakReversal="0V" thresh="1e9V" reset="0V"/>"#,
+            MUSCLE
+        )?;
+        writeln!(
+            w,
+            r#"  <expOneSynapse id="{}" gbase="1nS" erev="0V" tauDecay="{}s"/>"#,
+            SYNAPSE, p.tau_s
+        )?;
+        writeln!(
+            w,
+            r#"  <pulseGenerator id="{}" delay="0s" duration="1e9s" amplitude="{}nA"/>"#,
+            BIAS, bias
+        )?;
+        // Well above threshold, so a stimulated neuron fires every cycle
+        writeln!(
+            w,
+            r#"  <pulseGenerator id="{}" delay="0s" duration="1e9s" amplitude="{}nA"/>"#,
+            STIMULUS,
+            10. * lif.v_th
+        )?;
+        writeln!(w, r#"  <network id="{}_network">"#, self.id)?;
+        let mut properties = vec![
+            ("t_step", p.t_step.to_string()),
+            ("steps_per_cycle", p.steps_per_cycle.to_string()),
+            ("weight_scale", p.weight_scale.to_string()),
+        ];
+        if let Some(threshold) = self.threshold {
+            properties.push(("threshold", threshold.to_string()));
+        }
+        for (tag, value) in properties {
+            writeln!(w, r#"    <property tag="{}" value="{}"/>"#, tag, value)?;
+        }
+        for id in 0..self.cells {
+            let component = if id < self.neurons_tot {
+                NEURON
+            } else {
+                MUSCLE
+            };
+            writeln!(
+                w,
+                r#"    <population id="{}" component="{}" size="1"/>"#,
+                name(id),
+                component
+            )?;
+        }
+        for e in &self.edges {
+            let (pre, post) = (name(e.pre), name(e.post));
+            let post_component = if e.post < self.neurons_tot {
+                NEURON
+            } else {
+                MUSCLE
+            };
+            writeln!(
+                w,
+                r#"    <projection id="NC_{pre}_{post}" presynapticPopulation="{pre}" postsynapticPopulation="{post}" synapse="{SYNAPSE}">"#,
+            )?;
+            writeln!(
+                w,
+                r#"      <connectionWD id="0" preCellId="../{pre}/0/{NEURON}" postCellId="../{post}/0/{post_component}" weight="{}" delay="0s"/>"#,
+                e.weight
+            )?;
+            writeln!(w, "    </projection>")?;
+        }
+        for id in 0..self.neurons_tot {
+            writeln!(
+                w,
+                r#"    <inputList id="{BIAS}_{0}" component="{BIAS}" population="{0}"><input id="0" target="../{0}/0/{NEURON}" destination="synapses"/></inputList>"#,
+                name(id)
+            )?;
+        }
+        // In id order, as `read` returns them
+        let inputs: BTreeSet<u16> = self.inputs.iter().copied().collect();
+        for id in inputs {
+            writeln!(
+                w,
+                r#"    <inputList id="{STIMULUS}_{0}" component="{STIMULUS}" population="{0}"><input id="0" target="../{0}/0/{NEURON}" destination="synapses"/></inputList>"#,
+                name(id)
+            )?;
+        }
+        writeln!(w, "  </network>")?;
+        writeln!(w, "</neuroml>")
+    }
+
+    /// Read a NeuroML 2 document built from the component types `write`
+    /// uses. Populations are mapped to cell ids with `id_of`; the cells from
+    /// the first muscle population on are muscles. Parameters the document
+    /// leaves out keep their defaults. Connection weights are rounded and
+    /// clamped to the range of a ROM word, and edges keep the document order,
+    /// so an exported ROM reads back into the identical image. Input lists of
+    /// any component but the bias current mark stimulated cells.
+    pub fn read(text: &str, id_of: impl Fn(&str) -> Option<u16>) -> Result<Self, String> {
+        let mut reader = Reader::from_str(text);
+        reader.config_mut().trim_text(true);
+
+        let mut id = String::new();
+        let mut params = HybridParams::default();
+        let mut threshold = None;
+        let mut cell_kinds: BTreeMap<String, bool> = BTreeMap::new();
+        let mut populations: BTreeMap<String, (u16, bool)> = BTreeMap::new();
+        // Summed weight per connected pair, in document order
+        let mut weights: Vec<((u16, u16), f64)> = vec![];
+        let mut pair_index: BTreeMap<(u16, u16), usize> = BTreeMap::new();
+        let mut inputs = vec![];
+        let mut capacitance = None;
+        let mut conductance = None;
+        // Enclosing elements, innermost last, with their attributes
+        let mut stack: Vec<(String, BTreeMap<String, String>)> = vec![];
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|err| format!("NeuroML at byte {}: {}", reader.error_position(), err))?;
+            let (element, empty) = match &event {
+                Event::Start(e) => (e, false),
+                Event::Empty(e) => (e, true),
+                Event::End(_) => {
+                    stack.pop();
+                    continue;
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+            let (tag, attrs) = attributes(element)?;
+            let attr = |key: &str| {
+                attrs
+                    .get(key)
+                    .map(String::as_str)
+                    .ok_or_else(|| format!("<{}> has no {} attribute", tag, key))
+            };
+            let parent = stack.last().map(|(t, a)| (t.as_str(), a));
+            match tag.as_str() {
+                "neuroml" => id = attrs.get("id").cloned().unwrap_or_default(),
+                "iafRefCell" | "iafCell" => {
+                    let neuron = tag == "iafRefCell";
+                    cell_kinds.insert(attr("id")?.to_string(), neuron);
+                    if neuron {
+                        capacitance = Some(quantity(attr("C")?, "F")?);
+                        conductance = Some(quantity(attr("leakConductance")?, "S")?);
+                        params.tau_ref = quantity(attr("refract")?, "s")?;
+                    }
+                }
+                "expOneSynapse" => params.tau_s = quantity(attr("tauDecay")?, "s")?,
+                "property" => {
+                    let value = attr("value")?;
+                    let number = || {
+                        value
+                            .parse::<f64>()
+                            .map_err(|err| format!("property {}: {}", value, err))
+                    };
+                    match (parent.map(|p| p.0), attr("tag")?) {
+                        (Some("iafRefCell"), "max_rate") => params.max_rate = number()?,
+                        (Some("iafRefCell"), "intercept") => params.intercept = number()?,
+                        (Some("network"), "t_step") => params.t_step = number()?,
+                        (Some("network"), "steps_per_cycle") => {
+                            params.steps_per_cycle = number()? as usize
+                        }
+                        (Some("network"), "weight_scale") => params.weight_scale = number()?,
+                        (Some("network"), "threshold") => {
+                            threshold = Some(number()?.round().clamp(-128., 127.) as i8)
+                        }
+                        _ => {}
+                    }
+                }
+                "population" => {
+                    let name = attr("id")?;
+                    let cell = id_of(name).ok_or_else(|| format!("Unknown cell {}", name))?;
+                    let component = attr("component")?;
+                    let neuron = *cell_kinds.get(component).ok_or_else(|| {
+                        format!("Population {} uses unknown cell {}", name, component)
+                    })?;
+                    populations.insert(name.to_string(), (cell, neuron));
+                }
+                "connection" | "connectionWD" => {
+                    let Some(("projection", projection)) = parent else {
+                        return Err(format!("<{}> outside a projection", tag));
+                    };
+                    let end = |key: &str| {
+                        let name = projection
+                            .get(key)
+                            .ok_or_else(|| format!("projection has no {}", key))?;
+                        populations
+                            .get(name)
+                            .map(|&(cell, _)| cell)
+                            .ok_or_else(|| format!("Unknown population {}", name))
+                    };
+                    let pair = (
+                        end("presynapticPopulation")?,
+                        end("postsynapticPopulation")?,
+                    );
+                    let weight = match attrs.get("weight") {
+                        Some(w) => w
+                            .parse::<f64>()
+                            .map_err(|err| format!("connection weight {}: {}", w, err))?,
+                        None => 1.,
+                    };
+                    let k = *pair_index.entry(pair).or_insert_with(|| {
+                        weights.push((pair, 0.));
+                        weights.len() - 1
+                    });
+                    weights[k].1 += weight;
+                }
+                "inputList" if attr("component")? != BIAS => {
+                    let name = attr("population")?;
+                    let &(cell, _) = populations
+                        .get(name)
+                        .ok_or_else(|| format!("Unknown population {}", name))?;
+                    inputs.push(cell);
+                }
+                _ => {}
+            }
+            if !empty {
+                stack.push((tag, attrs));
+            }
+        }
+
+        if let (Some(c), Some(g)) = (capacitance, conductance) {
+            params.tau_rc = c / g;
+        }
+        let cells = populations
+            .values()
+            .map(|&(cell, _)| cell + 1)
+            .max()
+            .unwrap_or(0);
+        let neurons_tot = populations
+            .values()
+            .filter(|&&(_, neuron)| !neuron)
+            .map(|&(cell, _)| cell)
+            .min()
+            .unwrap_or(cells);
+        if let Some((name, _)) = populations
+            .iter()
+            .find(|(_, (cell, neuron))| *neuron && *cell >= neurons_tot)
+        {
+            return Err(format!("Neuron {} comes after the first muscle", name));
+        }
+        let edges = weights
+            .into_iter()
+            .map(|((pre, post), weight)| Edge {
+                pre,
+                post,
+                weight: weight.round().clamp(-64., 63.) as i8,
+            })
+            .filter(|e| e.weight != 0)
+            .collect();
+        inputs.sort_unstable();
+        inputs.dedup();
+        Ok(Self {
+            id,
+            neurons_tot,
+            cells,
+            edges,
+            threshold,
+            params,
+            inputs,
+        })
+    }
+}
+
+/// Tag name and attributes of an element
+fn attributes(element: &BytesStart) -> Result<(String, BTreeMap<String, String>), String> {
+    let tag = element.local_name().as_ref().to_string();
+    let mut attrs = BTreeMap::new();
+    for attr in element.attributes() {
+        let attr = attr.map_err(|err| format!("<{}>: {}", tag, err))?;
+        let key = attr.key.local_name().as_ref().to_string();
+        let value = attr
+            .normalized_value(XmlVersion::Implicit1_0)
+            .map_err(|err| format!("<{}> {}: {}", tag, key, err))?;
+        attrs.insert(key, value.into_owned());
+    }
+    Ok((tag, attrs))
+}
+
+/// Parse a NeuroML quantity such as `20ms` or `1.5 nF` into SI units of
+/// `unit` (one of V, s, F, S, A)
+fn quantity(text: &str, unit: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E')
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(split);
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|err| format!("quantity {}: {}", text, err))?;
+    let prefix = suffix
+        .strip_suffix(unit)
+        .ok_or_else(|| format!("quantity {} is not in {}", text, unit))?;
+    let scale = match prefix {
+        "" => 1.,
+        "m" => 1e-3,
+        "u" => 1e-6,
+        "n" => 1e-9,
+        "p" => 1e-12,
+        _ => return Err(format!("quantity {} has an unknown prefix", text)),
+    };
+    Ok(number * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::rom_image::build_rom;
+    use crate::emulations::c_elegans::preset;
+
+    fn export(connectome: &crate::connectome::Connectome, inputs: Vec<u16>) -> String {
+        let network = NeuroMlNetwork {
+            id: "c_elegans".to_string(),
+            neurons_tot: connectome.neurons_tot(),
+            cells: connectome.neurons_tot() + connectome.muscles_tot(),
+            edges: connectome.edges(),
+            threshold: Some(connectome.threshold()),
+            params: HybridParams {
+                tau_rc: 0.05,
+                weight_scale: 0.25,
+                ..HybridParams::default()
+            },
+            inputs,
+        };
+        let mut text = vec![];
+        network
+            .write(&mut text, |id| connectome.cells().name(id))
+            .unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn exported_rom_reads_back_identically() {
+        let mut connectome = preset::connectome();
+        connectome.set_threshold(25);
+        let cells = preset::cell_registry();
+        let inputs = vec![cells.id("ASEL").unwrap(), cells.id("ASER").unwrap()];
+        let text = export(&connectome, inputs.clone());
+
+        let read = NeuroMlNetwork::read(&text, |name| cells.id(name)).unwrap();
+        assert_eq!(read.id, "c_elegans");
+        assert_eq!(read.neurons_tot, connectome.neurons_tot());
+        assert_eq!(read.cells, cells.len());
+        assert_eq!(read.threshold, Some(25));
+        assert_eq!(read.inputs, inputs);
+        assert!((read.params.tau_rc - 0.05).abs() < 1e-9);
+        assert!((read.params.weight_scale - 0.25).abs() < 1e-9);
+        assert_eq!(
+            build_rom(read.neurons_tot, &read.edges).unwrap(),
+            connectome.rom()
+        );
+    }
+
+    #[test]
+    fn read_rejects_unknown_cells() {
+        let connectome = preset::connectome();
+        let text = export(&connectome, vec![]).replace("\"AVAL\"", "\"NOTACELL\"");
+        let err = NeuroMlNetwork::read(&text, |name| preset::cell_registry().id(name));
+        assert!(err.unwrap_err().contains("NOTACELL"));
+        assert!(NeuroMlNetwork::read("<neuroml><network", |_| None).is_err());
+    }
+
+    #[test]
+    fn rewriting_a_read_document_is_identical() {
+        let connectome = preset::connectome();
+        let cells = preset::cell_registry();
+        // Inputs in any order, repeated, are written once in id order
+        let inputs = ["ASER", "ASEL", "ASER"].map(|name| cells.id(name).unwrap());
+        let text = export(&connectome, inputs.to_vec());
+        let read = NeuroMlNetwork::read(&text, |name| cells.id(name)).unwrap();
+        let mut again = vec![];
+        read.write(&mut again, |id| cells.name(id)).unwrap();
+        assert_eq!(String::from_utf8(again).unwrap(), text);
+    }
+}
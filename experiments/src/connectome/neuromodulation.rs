@@ -0,0 +1,259 @@
+/// One diffusing modulator (e.g. serotonin): discharges of its source
+/// neurons release it, it decays with time constant `tau` (in cycles), and
+/// its concentration shifts the threshold and input gain of target neurons.
+///
+/// The effect saturates as `c / (1 + c)`, so `threshold_shift` and
+/// `gain_shift` are the shifts approached at high concentration, scaled by
+/// each target's sensitivity. `tonic` is a baseline level on top of the
+/// released one, for states such as being on food.
+#[derive(Clone, Debug)]
+pub struct ModulatorChannel {
+    pub name: String,
+    pub release: f32,
+    pub tau: f32,
+    pub tonic: f32,
+    pub threshold_shift: f32,
+    pub gain_shift: f32,
+    sources: Vec<u16>,
+    targets: Vec<(u16, f32)>,
+    released: f32,
+}
+
+impl ModulatorChannel {
+    /// A channel with no sources or targets and no effect
+    pub fn new(name: &str, tau: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            release: 0.001,
+            tau,
+            tonic: 0.,
+            threshold_shift: 0.,
+            gain_shift: 0.,
+            sources: vec![],
+            targets: vec![],
+            released: 0.,
+        }
+    }
+
+    pub fn with_sources(mut self, sources: Vec<u16>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Regional release: only these neurons, each with a sensitivity in 0..=1
+    pub fn with_targets(mut self, targets: Vec<(u16, f32)>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Global release: every one of `neurons` is fully sensitive
+    pub fn global(mut self, neurons: u16) -> Self {
+        self.targets = (0..neurons).map(|id| (id, 1.)).collect();
+        self
+    }
+
+    pub fn with_effect(mut self, threshold_shift: f32, gain_shift: f32) -> Self {
+        self.threshold_shift = threshold_shift;
+        self.gain_shift = gain_shift;
+        self
+    }
+
+    pub fn sources(&self) -> &[u16] {
+        &self.sources
+    }
+
+    pub fn targets(&self) -> &[(u16, f32)] {
+        &self.targets
+    }
+
+    /// Tonic plus released concentration
+    pub fn concentration(&self) -> f32 {
+        self.tonic + self.released
+    }
+
+    /// Saturating effect of the current concentration, in 0..1
+    fn occupancy(&self) -> f32 {
+        let c = self.concentration().max(0.);
+        c / (1. + c)
+    }
+}
+
+/// Slow volume transmission on top of the wired synapses. Each cycle the
+/// channels are updated from that cycle's discharges, and the resulting
+/// threshold offsets and gains apply from the next cycle on.
+#[derive(Clone, Debug)]
+pub struct Neuromodulation {
+    channels: Vec<ModulatorChannel>,
+    threshold_offset: Vec<i16>,
+    gain: Vec<f32>,
+}
+
+impl Neuromodulation {
+    pub fn new(neurons: u16) -> Self {
+        Self {
+            channels: vec![],
+            threshold_offset: vec![0; neurons as usize],
+            gain: vec![1.; neurons as usize],
+        }
+    }
+
+    pub fn with_channel(mut self, channel: ModulatorChannel) -> Self {
+        self.channels.push(channel);
+        self.apply_effects();
+        self
+    }
+
+    pub fn channels(&self) -> &[ModulatorChannel] {
+        &self.channels
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&ModulatorChannel> {
+        self.channels.iter().find(|c| c.name == name)
+    }
+
+    /// Changes (e.g. to `tonic`) take effect from the next cycle, or call
+    /// `refresh` to apply them at once
+    pub fn channel_mut(&mut self, name: &str) -> Option<&mut ModulatorChannel> {
+        self.channels.iter_mut().find(|c| c.name == name)
+    }
+
+    /// Keep only the channels for which `keep` is true
+    pub fn retain(&mut self, keep: impl Fn(&ModulatorChannel) -> bool) {
+        self.channels.retain(keep);
+        self.apply_effects();
+    }
+
+    /// Amount added to a neuron's discharge threshold
+    pub fn threshold_offset(&self, id: u16) -> i16 {
+        self.threshold_offset.get(id as usize).copied().unwrap_or(0)
+    }
+
+    /// Factor applied to weights arriving at a neuron; 1 for muscles
+    pub fn gain(&self, id: u16) -> f32 {
+        self.gain.get(id as usize).copied().unwrap_or(1.)
+    }
+
+    /// Drop all released modulator, keeping tonic levels
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(|c| c.released = 0.);
+        self.apply_effects();
+    }
+
+    /// Decay, then release for every source that `discharged` this cycle.
+    pub(crate) fn update(&mut self, discharged: impl Fn(u16) -> bool) {
+        for channel in &mut self.channels {
+            let releases = channel.sources.iter().filter(|&&id| discharged(id)).count();
+            channel.released =
+                channel.released * (-1. / channel.tau).exp() + channel.release * releases as f32;
+        }
+        self.apply_effects();
+    }
+
+    pub fn refresh(&mut self) {
+        self.apply_effects();
+    }
+
+    fn apply_effects(&mut self) {
+        let mut threshold = vec![0f32; self.threshold_offset.len()];
+        self.gain.fill(1.);
+        for channel in &self.channels {
+            let occupancy = channel.occupancy();
+            for &(id, sensitivity) in &channel.targets {
+                let Some(t) = threshold.get_mut(id as usize) else {
+                    continue;
+                };
+                *t += channel.threshold_shift * occupancy * sensitivity;
+                self.gain[id as usize] *= 1. + channel.gain_shift * occupancy * sensitivity;
+            }
+        }
+        for (offset, t) in self.threshold_offset.iter_mut().zip(threshold) {
+            *offset = t.round() as i16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+    use crate::emulations::c_elegans::csv_tables::builtin_neuromodulation;
+
+    #[test]
+    fn tonic_level_shifts_targets_only() {
+        let mut channel = ModulatorChannel::new("serotonin", 100.)
+            .with_targets(vec![(1, 0.5)])
+            .with_effect(20., 0.5);
+        channel.tonic = 1.;
+        let modulation = Neuromodulation::new(3).with_channel(channel);
+        // occupancy 1 / (1 + 1) = 0.5, times sensitivity 0.5
+        assert_eq!(modulation.threshold_offset(1), 5);
+        assert!((modulation.gain(1) - 1.125).abs() < 1e-6);
+        assert_eq!(modulation.threshold_offset(0), 0);
+        assert_eq!(modulation.gain(0), 1.);
+        // muscles and other ids out of range are never modulated
+        assert_eq!(modulation.gain(7), 1.);
+    }
+
+    #[test]
+    fn release_decays_and_resets() {
+        let channel = ModulatorChannel::new("dopamine", 10.)
+            .with_sources(vec![0, 1])
+            .global(2)
+            .with_effect(-10., 0.);
+        let mut modulation = Neuromodulation::new(2).with_channel(channel);
+        modulation.channel_mut("dopamine").unwrap().release = 0.5;
+        modulation.update(|id| id == 0);
+        modulation.update(|_| true);
+        let expected = 0.5 * (-0.1f32).exp() + 1.;
+        let level = modulation.channel("dopamine").unwrap().concentration();
+        assert!((level - expected).abs() < 1e-6);
+        assert!(modulation.threshold_offset(1) < 0);
+
+        modulation.reset();
+        assert_eq!(modulation.channel("dopamine").unwrap().concentration(), 0.);
+        assert_eq!(modulation.threshold_offset(1), 0);
+        modulation.retain(|c| c.name != "dopamine");
+        assert!(modulation.channels().is_empty());
+    }
+
+    #[test]
+    fn raised_threshold_silences_a_neuron() {
+        let circuit = Circuit::parse("threshold 20\nneuron A\nneuron B\nA B 30\n").unwrap();
+        let fires = |modulation: Option<Neuromodulation>| {
+            let mut connectome = circuit.connectome().unwrap();
+            connectome.set_neuromodulation(modulation);
+            let mut discharged = [0];
+            let mut count = 0;
+            for cycle in 0..4 {
+                connectome.neural_cycle((cycle == 0).then_some(&[0][..]));
+                connectome.discharge_query(&[1], &mut discharged);
+                count += discharged[0] as usize;
+            }
+            count
+        };
+        let mut channel = ModulatorChannel::new("octopamine", 100.)
+            .with_targets(vec![(1, 1.)])
+            .with_effect(40., 0.);
+        channel.tonic = 100.;
+        assert_eq!(fires(None), 1);
+        assert_eq!(
+            fires(Some(Neuromodulation::new(2).with_channel(channel))),
+            0
+        );
+    }
+
+    #[test]
+    fn builtin_channels_have_sources() {
+        let modulation = builtin_neuromodulation();
+        let names: Vec<&str> = modulation
+            .channels()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["serotonin", "dopamine", "octopamine", "fmrfamide"]);
+        for channel in modulation.channels() {
+            assert!(!channel.sources().is_empty(), "{}", channel.name);
+            assert!(channel.targets().iter().all(|&(_, s)| s > 0. && s <= 1.));
+        }
+    }
+}
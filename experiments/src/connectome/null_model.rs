@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::connectome::Edge;
+
+/// A random wiring to compare the real one against.
+///
+/// Both models keep the number of cells and the multiset of weights, so any
+/// difference in behaviour comes from who connects to whom. Neither creates
+/// self-connections or duplicate edges, and muscles only ever receive edges.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NullModel {
+    /// Repeated double-edge swaps (`a->b, c->d` becomes `a->d, c->b`) that
+    /// keep every cell's in- and out-degree; each edge keeps its weight and
+    /// presynaptic cell
+    Rewired { swaps_per_edge: f64 },
+    /// Edges placed uniformly at random, with as many neuron-to-neuron and
+    /// neuron-to-muscle edges as the original, and the original weights
+    /// shuffled over them
+    ErdosRenyi,
+}
+
+impl NullModel {
+    /// Randomise `edges` of a wiring with `neurons_tot` neurons followed by
+    /// muscles, up to `cells` cells in total. The same seed gives the same wiring.
+    pub fn generate(
+        &self,
+        neurons_tot: u16,
+        cells: u16,
+        edges: &[Edge],
+        seed: u64,
+    ) -> Result<Vec<Edge>, String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        match *self {
+            NullModel::Rewired { swaps_per_edge } => Ok(rewire(edges, swaps_per_edge, &mut rng)),
+            NullModel::ErdosRenyi => erdos_renyi(neurons_tot, cells, edges, &mut rng),
+        }
+    }
+}
+
+impl fmt::Display for NullModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NullModel::Rewired { swaps_per_edge } => write!(f, "rewire:{}", swaps_per_edge),
+            NullModel::ErdosRenyi => write!(f, "erdos_renyi"),
+        }
+    }
+}
+
+impl FromStr for NullModel {
+    type Err = String;
+
+    /// `rewire`, `rewire:<swaps per edge>` or `erdos_renyi` (also `er`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        match (name, arg) {
+            ("rewire", "") => Ok(NullModel::Rewired {
+                swaps_per_edge: 10.,
+            }),
+            ("rewire", swaps) => Ok(NullModel::Rewired {
+                swaps_per_edge: swaps.parse().map_err(|err| format!("{}: {}", s, err))?,
+            }),
+            ("erdos_renyi" | "er", "") => Ok(NullModel::ErdosRenyi),
+            _ => Err(format!("Unknown null model {}", s)),
+        }
+    }
+}
+
+fn rewire(edges: &[Edge], swaps_per_edge: f64, rng: &mut StdRng) -> Vec<Edge> {
+    let mut edges = edges.to_vec();
+    if edges.len() < 2 {
+        return edges;
+    }
+    let mut present: HashSet<(u16, u16)> = edges.iter().map(|e| (e.pre, e.post)).collect();
+    let swaps = (edges.len() as f64 * swaps_per_edge).round() as usize;
+    for _ in 0..swaps {
+        let (i, j) = (
+            rng.random_range(0..edges.len()),
+            rng.random_range(0..edges.len()),
+        );
+        let (a, b) = (edges[i], edges[j]);
+        // Skip swaps that would create a self-connection or an existing edge
+        if a.pre == b.post
+            || b.pre == a.post
+            || present.contains(&(a.pre, b.post))
+            || present.contains(&(b.pre, a.post))
+        {
+            continue;
+        }
+        present.remove(&(a.pre, a.post));
+        present.remove(&(b.pre, b.post));
+        present.insert((a.pre, b.post));
+        present.insert((b.pre, a.post));
+        edges[i].post = b.post;
+        edges[j].post = a.post;
+    }
+    edges
+}
+
+fn erdos_renyi(
+    neurons_tot: u16,
+    cells: u16,
+    edges: &[Edge],
+    rng: &mut StdRng,
+) -> Result<Vec<Edge>, String> {
+    let to_muscles = edges.iter().filter(|e| e.post >= neurons_tot).count();
+    let to_neurons = edges.len() - to_muscles;
+    let neurons = neurons_tot as usize;
+    let muscles = cells.saturating_sub(neurons_tot) as usize;
+    if to_neurons > neurons * neurons.saturating_sub(1) || to_muscles > neurons * muscles {
+        return Err("More edges than possible cell pairs".to_string());
+    }
+
+    let mut weights: Vec<i8> = edges.iter().map(|e| e.weight).collect();
+    weights.shuffle(rng);
+    let mut present = HashSet::new();
+    let mut random = Vec::with_capacity(edges.len());
+    for (count, targets) in [
+        (to_neurons, 0..neurons_tot),
+        (to_muscles, neurons_tot..cells),
+    ] {
+        let start = random.len();
+        while random.len() - start < count {
+            let pre = rng.random_range(0..neurons_tot);
+            let post = rng.random_range(targets.clone());
+            if pre != post && present.insert((pre, post)) {
+                random.push(Edge {
+                    pre,
+                    post,
+                    weight: weights[random.len()],
+                });
+            }
+        }
+    }
+    // ROM order: grouped by presynaptic neuron
+    random.sort_by_key(|e| (e.pre, e.post));
+    Ok(random)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEURONS: u16 = 12;
+    const CELLS: u16 = 16;
+
+    /// A chain through the neurons, skip links and a few muscle edges
+    fn wiring() -> Vec<Edge> {
+        let mut edges = Vec::new();
+        for pre in 0..NEURONS {
+            for post in [(pre + 1) % NEURONS, (pre + 3) % NEURONS] {
+                let weight = pre as i8 - 6;
+                edges.push(Edge { pre, post, weight });
+            }
+            if pre % 3 == 0 {
+                let post = NEURONS + pre / 3;
+                edges.push(Edge {
+                    pre,
+                    post,
+                    weight: 20,
+                });
+            }
+        }
+        edges
+    }
+
+    fn degrees(edges: &[Edge]) -> (Vec<usize>, Vec<usize>) {
+        let mut out = vec![0; CELLS as usize];
+        let mut inn = vec![0; CELLS as usize];
+        for e in edges {
+            out[e.pre as usize] += 1;
+            inn[e.post as usize] += 1;
+        }
+        (out, inn)
+    }
+
+    fn sorted_weights(edges: &[Edge]) -> Vec<i8> {
+        let mut weights: Vec<i8> = edges.iter().map(|e| e.weight).collect();
+        weights.sort();
+        weights
+    }
+
+    fn simple(edges: &[Edge]) -> bool {
+        let pairs: HashSet<(u16, u16)> = edges.iter().map(|e| (e.pre, e.post)).collect();
+        pairs.len() == edges.len() && edges.iter().all(|e| e.pre != e.post && e.pre < NEURONS)
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        let model: NullModel = "rewire:2.5".parse().unwrap();
+        assert_eq!(
+            model,
+            NullModel::Rewired {
+                swaps_per_edge: 2.5
+            }
+        );
+        assert_eq!(model.to_string(), "rewire:2.5");
+        assert_eq!("er".parse::<NullModel>().unwrap(), NullModel::ErdosRenyi);
+        assert_eq!(
+            "rewire".parse::<NullModel>().unwrap(),
+            NullModel::Rewired {
+                swaps_per_edge: 10.
+            }
+        );
+        assert!("rewire:x".parse::<NullModel>().is_err());
+        assert!("lattice".parse::<NullModel>().is_err());
+    }
+
+    #[test]
+    fn rewiring_keeps_degrees_and_weights() {
+        let edges = wiring();
+        let model = NullModel::Rewired {
+            swaps_per_edge: 10.,
+        };
+        let random = model.generate(NEURONS, CELLS, &edges, 3).unwrap();
+        assert_ne!(random, edges);
+        assert_eq!(degrees(&random), degrees(&edges));
+        assert!(simple(&random));
+        // each edge keeps its presynaptic cell and weight
+        for (new, old) in random.iter().zip(&edges) {
+            assert_eq!((new.pre, new.weight), (old.pre, old.weight));
+        }
+    }
+
+    #[test]
+    fn erdos_renyi_keeps_edge_counts_and_weights() {
+        let edges = wiring();
+        let random = NullModel::ErdosRenyi
+            .generate(NEURONS, CELLS, &edges, 3)
+            .unwrap();
+        assert_eq!(random.len(), edges.len());
+        let to_muscles = |edges: &[Edge]| edges.iter().filter(|e| e.post >= NEURONS).count();
+        assert_eq!(to_muscles(&random), to_muscles(&edges));
+        assert_eq!(sorted_weights(&random), sorted_weights(&edges));
+        assert!(simple(&random));
+        assert!(random.iter().all(|e| e.post < CELLS));
+
+        let too_dense = NullModel::ErdosRenyi.generate(2, 2, &edges, 3);
+        assert!(too_dense.is_err());
+    }
+
+    #[test]
+    fn a_seed_reproduces_the_wiring() {
+        let edges = wiring();
+        for model in [
+            NullModel::Rewired { swaps_per_edge: 5. },
+            NullModel::ErdosRenyi,
+        ] {
+            let a = model.generate(NEURONS, CELLS, &edges, 7).unwrap();
+            let b = model.generate(NEURONS, CELLS, &edges, 7).unwrap();
+            let c = model.generate(NEURONS, CELLS, &edges, 8).unwrap();
+            assert_eq!(a, b);
+            assert_ne!(a, c);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use crate::connectome::Connectome;
+
+/// Something that happened inside one neural cycle.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CycleEvent {
+    Discharge {
+        id: u16,
+    },
+    /// A muscle's value crossed the connectome's muscle level
+    MuscleCrossing {
+        id: u16,
+        value: i16,
+        rising: bool,
+    },
+    /// A neuron's state was zeroed after too many idle cycles
+    IdleReset {
+        id: u16,
+    },
+    /// A neuron's next state was clipped to the i8 range
+    Saturation {
+        id: u16,
+        attempted: i16,
+        clipped: i8,
+    },
+}
+
+/// Receives the events of every neural cycle once the cycle is complete.
+///
+/// Observers are `Send + Sync` so a connectome with observers attached can
+/// still move between threads and be shared, e.g. by the Python bindings.
+pub trait CycleObserver: Any + Send + Sync {
+    fn on_event(&mut self, cycle: u64, event: &CycleEvent);
+
+    /// Called after all events of the cycle, with the updated connectome.
+    fn on_cycle_end(&mut self, _cycle: u64, _connectome: &Connectome) {}
+}
+
+/// Handle returned by `Connectome::subscribe`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ObserverId(pub(crate) usize);
+
+/// Keeps every event, tagged with its cycle.
+#[derive(Default, Debug)]
+pub struct EventLog {
+    pub events: Vec<(u64, CycleEvent)>,
+}
+
+impl CycleObserver for EventLog {
+    fn on_event(&mut self, cycle: u64, event: &CycleEvent) {
+        self.events.push((cycle, event.clone()));
+    }
+}
+
+/// Sparse raster: the ids that discharged in each cycle.
+#[derive(Default, Debug)]
+pub struct DischargeRecorder {
+    pub cycles: Vec<(u64, Vec<u16>)>,
+}
+
+impl CycleObserver for DischargeRecorder {
+    fn on_event(&mut self, cycle: u64, event: &CycleEvent) {
+        if let CycleEvent::Discharge { id } = event {
+            match self.cycles.last_mut() {
+                Some((c, ids)) if *c == cycle => ids.push(*id),
+                _ => self.cycles.push((cycle, vec![*id])),
+            }
+        }
+    }
+}
+
+/// Counts saturation clips per neuron, to see where the i8 clamp bites.
+#[derive(Default, Debug)]
+pub struct SaturationCounter {
+    pub per_neuron: BTreeMap<u16, usize>,
+}
+
+impl SaturationCounter {
+    pub fn total(&self) -> usize {
+        self.per_neuron.values().sum()
+    }
+
+    /// Neurons ordered by how often they were clipped, most first
+    pub fn ranked(&self) -> Vec<(u16, usize)> {
+        let mut ranked: Vec<(u16, usize)> =
+            self.per_neuron.iter().map(|(&id, &n)| (id, n)).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+impl CycleObserver for SaturationCounter {
+    fn on_event(&mut self, _cycle: u64, event: &CycleEvent) {
+        if let CycleEvent::Saturation { id, .. } = event {
+            *self.per_neuron.entry(*id).or_insert(0) += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::preset;
+
+    #[test]
+    fn connectome_stays_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Connectome>();
+    }
+
+    #[test]
+    fn recorders_see_the_discharges_the_queries_report() {
+        let stimulus = resolve_targets(&["chemotaxis"]).unwrap();
+        let mut connectome = preset::connectome();
+        let log = connectome.subscribe(Box::new(EventLog::default()));
+        let raster = connectome.subscribe(Box::new(DischargeRecorder::default()));
+        let neurons: Vec<u16> = (0..connectome.neurons_tot()).collect();
+        let mut fired = vec![0; neurons.len()];
+        let mut expected = vec![];
+        for cycle in 0..100 {
+            connectome.neural_cycle(Some(&stimulus));
+            connectome.discharge_query(&neurons, &mut fired);
+            let ids: Vec<u16> = neurons
+                .iter()
+                .copied()
+                .filter(|&id| fired[id as usize] == 1)
+                .collect();
+            if !ids.is_empty() {
+                expected.push((cycle, ids));
+            }
+        }
+        assert!(!expected.is_empty());
+        let recorded = &connectome
+            .observer::<DischargeRecorder>(raster)
+            .unwrap()
+            .cycles;
+        assert_eq!(recorded, &expected);
+        let discharges = connectome
+            .observer::<EventLog>(log)
+            .unwrap()
+            .events
+            .iter()
+            .filter(|(_, e)| matches!(e, CycleEvent::Discharge { .. }))
+            .count();
+        assert_eq!(
+            discharges,
+            expected.iter().map(|(_, ids)| ids.len()).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn unsubscribed_observers_stop_receiving_events() {
+        let mut connectome = preset::connectome();
+        let id = connectome.subscribe(Box::new(EventLog::default()));
+        assert!(connectome.observer::<DischargeRecorder>(id).is_none());
+        let observer = connectome.unsubscribe(id).unwrap();
+        assert!(connectome.observer::<EventLog>(id).is_none());
+        assert!(connectome.unsubscribe(id).is_none());
+        let stimulus = resolve_targets(&["chemotaxis"]).unwrap();
+        for _ in 0..50 {
+            connectome.neural_cycle(Some(&stimulus));
+        }
+        let log: &dyn Any = observer.as_ref();
+        assert!(log.downcast_ref::<EventLog>().unwrap().events.is_empty());
+        // With every slot emptied the engine stops buffering events
+        assert!(!connectome.observed());
+        assert!(connectome.events.is_empty());
+    }
+}
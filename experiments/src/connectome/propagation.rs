@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use crate::connectome::{Connectome, Edge};
+
+/// How soon activity from a set of sources reaches every cell, with the
+/// predecessor of each reached cell on its fastest route.
+#[derive(Clone, Debug)]
+pub struct Reach {
+    pub sources: Vec<u16>,
+    /// Hops or cycles per cell, `None` if never reached; 0 for the sources
+    pub latency: Vec<Option<u32>>,
+    /// Cell the activity came from, `None` for sources and unreached cells
+    pub parent: Vec<Option<u16>>,
+}
+
+impl Reach {
+    /// Cells leading to `target` along the fastest route, `target` last. The
+    /// route starts at a source, or for dynamic reach possibly at a cell
+    /// that became active without any earlier excitatory input.
+    pub fn path_to(&self, target: u16) -> Option<Vec<u16>> {
+        self.latency.get(target as usize).copied().flatten()?;
+        let mut path = vec![target];
+        while let Some(parent) = self.parent[*path.last().expect("not empty") as usize] {
+            path.push(parent);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Largest finite latency among `targets`
+    pub fn max_latency(&self, targets: &[u16]) -> Option<u32> {
+        targets
+            .iter()
+            .filter_map(|&id| self.latency.get(id as usize).copied().flatten())
+            .max()
+    }
+}
+
+/// Fewest excitatory synapses from `sources` to every cell, by breadth-first
+/// search. Only edges with positive weight carry activity forward, since an
+/// inhibitory input cannot push its target over threshold.
+pub fn structural_reach(cells: u16, edges: &[Edge], sources: &[u16]) -> Reach {
+    let mut outgoing: Vec<Vec<u16>> = vec![vec![]; cells as usize];
+    for e in edges.iter().filter(|e| e.weight > 0) {
+        outgoing[e.pre as usize].push(e.post);
+    }
+    let mut latency = vec![None; cells as usize];
+    let mut parent = vec![None; cells as usize];
+    let mut queue = VecDeque::new();
+    for &s in sources {
+        latency[s as usize] = Some(0);
+        queue.push_back(s);
+    }
+    while let Some(cell) = queue.pop_front() {
+        let hops = latency[cell as usize].expect("queued cells are reached") + 1;
+        for &post in &outgoing[cell as usize] {
+            if latency[post as usize].is_none() {
+                latency[post as usize] = Some(hops);
+                parent[post as usize] = Some(cell);
+                queue.push_back(post);
+            }
+        }
+    }
+    Reach {
+        sources: sources.to_vec(),
+        latency,
+        parent,
+    }
+}
+
+/// Stimulate `sources` every cycle from the connectome's current state for up
+/// to `max_cycles` cycles and note the cycle (counted from 1) in which each
+/// neuron first discharges and each muscle first receives positive input.
+///
+/// The parent of a reached cell is its excitatory presynaptic cell that
+/// became active most recently before it (in the same cycle for muscles,
+/// which take input from the discharges of that cycle), the likeliest
+/// immediate cause. Sources count as active from cycle 0.
+pub fn dynamic_reach(connectome: &mut Connectome, sources: &[u16], max_cycles: usize) -> Reach {
+    let neurons_tot = connectome.neurons_tot();
+    let cells = neurons_tot + connectome.muscles_tot();
+    let neurons: Vec<u16> = (0..neurons_tot).collect();
+    let muscles: Vec<u16> = (neurons_tot..cells).collect();
+    let mut latency = vec![None; cells as usize];
+    for &s in sources {
+        latency[s as usize] = Some(0);
+    }
+    let mut flags = vec![0u8; neurons.len()];
+    let mut values = vec![0i16; muscles.len()];
+    for cycle in 1..=max_cycles as u32 {
+        connectome.neural_cycle(Some(sources));
+        connectome.discharge_query(&neurons, &mut flags);
+        connectome.muscle_query(&muscles, &mut values);
+        let fired = neurons.iter().zip(&flags).filter(|&(_, &f)| f == 1);
+        let driven = muscles.iter().zip(&values).filter(|&(_, &v)| v > 0);
+        for (&id, _) in fired {
+            latency[id as usize].get_or_insert(cycle);
+        }
+        for (&id, _) in driven {
+            latency[id as usize].get_or_insert(cycle);
+        }
+        if latency.iter().all(Option::is_some) {
+            break;
+        }
+    }
+
+    let mut parent = vec![None; cells as usize];
+    for e in connectome.edges().iter().filter(|e| e.weight > 0) {
+        let (Some(pre), Some(post)) = (latency[e.pre as usize], latency[e.post as usize]) else {
+            continue;
+        };
+        if post == 0 {
+            continue;
+        }
+        let causal = if e.post >= neurons_tot {
+            pre <= post
+        } else {
+            pre < post
+        };
+        let better = parent[e.post as usize]
+            .is_none_or(|p: u16| latency[p as usize].is_some_and(|lp| pre > lp));
+        if causal && better {
+            parent[e.post as usize] = Some(e.pre);
+        }
+    }
+    Reach {
+        sources: sources.to_vec(),
+        latency,
+        parent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    /// A -> B -> C -> M with a shortcut A -> C and an inhibited D
+    const CHAIN: &str = "threshold 20\nneuron A\nneuron B\nneuron C\nneuron D\nmuscle M\n\
+        A B 30\nB C 30\nC M 30\nA D -30\nD A 30\n";
+
+    fn chain() -> Connectome {
+        Circuit::parse(CHAIN).unwrap().connectome().unwrap()
+    }
+
+    #[test]
+    fn structural_reach_follows_excitatory_edges() {
+        let mut edges = chain().edges();
+        let reach = structural_reach(5, &edges, &[0]);
+        assert_eq!(reach.latency, [Some(0), Some(1), Some(2), None, Some(3)]);
+        assert_eq!(reach.path_to(4), Some(vec![0, 1, 2, 4]));
+        assert_eq!(reach.path_to(0), Some(vec![0]));
+        assert_eq!(reach.path_to(3), None);
+        assert_eq!(reach.max_latency(&[1, 3, 4]), Some(3));
+        assert_eq!(reach.max_latency(&[3]), None);
+
+        edges.push(Edge {
+            pre: 0,
+            post: 2,
+            weight: 5,
+        });
+        let shortcut = structural_reach(5, &edges, &[0]);
+        assert_eq!(shortcut.path_to(4), Some(vec![0, 2, 4]));
+    }
+
+    #[test]
+    fn dynamic_reach_times_the_discharges() {
+        let mut connectome = chain();
+        let reach = dynamic_reach(&mut connectome, &[0], 10);
+        let latency = |id: u16| reach.latency[id as usize];
+        assert_eq!(latency(0), Some(0));
+        assert!(latency(1) < latency(2));
+        assert!(latency(2) <= latency(4));
+        assert_eq!(latency(3), None);
+        assert_eq!(reach.path_to(4), Some(vec![0, 1, 2, 4]));
+    }
+
+    #[test]
+    fn dynamic_reach_stops_at_max_cycles() {
+        let mut connectome = chain();
+        let reach = dynamic_reach(&mut connectome, &[0], 1);
+        assert!(reach.latency[2].is_none() && reach.latency[4].is_none());
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use nalgebra::DMatrix;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::connectome::Connectome;
+use crate::lify_stuff::utils::ridge_regression;
+
+/// A signal in `0..=1` driven into a set of neurons.
+///
+/// The engine only takes pings, so the value is delivered as a pulse density:
+/// it accumulates every cycle and the neurons are pinged whenever the sum
+/// reaches one, e.g. every other cycle for 0.5.
+#[derive(Clone, Debug)]
+pub struct ReservoirInput {
+    pub neurons: Vec<u16>,
+    pub signal: Vec<f64>,
+}
+
+/// Run the connectome as a fixed reservoir for `cycles` cycles under `inputs`
+/// and collect the state of every neuron after each cycle, scaled by the
+/// threshold. The result has one row per neuron plus a constant bias row and
+/// one column per cycle.
+pub fn collect_states(
+    connectome: &mut Connectome,
+    inputs: &[ReservoirInput],
+    cycles: usize,
+) -> DMatrix<f64> {
+    let neurons: Vec<u16> = (0..connectome.neurons_tot()).collect();
+    let scale = connectome.threshold().max(1) as f64;
+    let mut states = DMatrix::<f64>::zeros(neurons.len() + 1, cycles);
+    let mut state = vec![0i16; neurons.len()];
+    let mut accumulators = vec![0f64; inputs.len()];
+    let mut stimulus = vec![];
+    for t in 0..cycles {
+        stimulus.clear();
+        for (input, acc) in inputs.iter().zip(accumulators.iter_mut()) {
+            *acc += input.signal.get(t).copied().unwrap_or(0.).clamp(0., 1.);
+            if *acc >= 1. {
+                *acc -= 1.;
+                stimulus.extend(&input.neurons);
+            }
+        }
+        connectome.neural_cycle((!stimulus.is_empty()).then_some(stimulus.as_slice()));
+        connectome.state_query(&neurons, &mut state);
+        for (i, &s) in state.iter().enumerate() {
+            states[(i, t)] = s as f64 / scale;
+        }
+        states[(neurons.len(), t)] = 1.;
+    }
+    states
+}
+
+/// Exponentially smooth every state row over time with time constant `tau`
+/// cycles, turning spiking states into rate-like features. The bias row is
+/// constant and stays as it is; `tau` of 1 or less leaves the states as they are.
+pub fn low_pass(states: &DMatrix<f64>, tau: f64) -> DMatrix<f64> {
+    let mut smoothed = states.clone();
+    if tau <= 1. {
+        return smoothed;
+    }
+    for t in 1..states.ncols() {
+        for i in 0..states.nrows() {
+            let previous = smoothed[(i, t - 1)];
+            smoothed[(i, t)] = previous + (states[(i, t)] - previous) / tau;
+        }
+    }
+    smoothed
+}
+
+/// Random levels in `0..=1`, each held for `hold` cycles
+pub fn random_levels(cycles: usize, hold: usize, seed: u64) -> Vec<f64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut level = 0.;
+    (0..cycles)
+        .map(|t| {
+            if t % hold.max(1) == 0 {
+                level = rng.random::<f64>();
+            }
+            level
+        })
+        .collect()
+}
+
+/// Linear readout of reservoir states trained by ridge regression.
+#[derive(Clone, Debug)]
+pub struct RidgeReadout {
+    /// One weight per state row, the bias last
+    pub weights: Vec<f64>,
+    pub ridge: f64,
+    /// Root mean square error over the standard deviation of the target, so 0
+    /// is perfect and 1 is no better than predicting the mean
+    pub train_nrmse: f64,
+    pub test_nrmse: f64,
+    pub train_samples: usize,
+    pub test_samples: usize,
+}
+
+impl RidgeReadout {
+    /// Drop the first `washout` columns, train on the next `train_fraction`
+    /// of the rest and test on the remainder. `target` has one value per
+    /// column of `states`.
+    pub fn train(
+        states: &DMatrix<f64>,
+        target: &[f64],
+        washout: usize,
+        train_fraction: f64,
+        ridge: f64,
+    ) -> Result<Self, String> {
+        if target.len() != states.ncols() {
+            return Err(format!(
+                "{} cycles of states but {} target values",
+                states.ncols(),
+                target.len()
+            ));
+        }
+        let usable = states.ncols().saturating_sub(washout);
+        let split = washout + (usable as f64 * train_fraction.clamp(0., 1.)).round() as usize;
+        if split == washout || split == states.ncols() {
+            return Err("Need samples both to train and to test the readout".to_string());
+        }
+        let train = states.columns(washout, split - washout).into_owned();
+        let weights = ridge_regression(&train, &target[washout..split], ridge)?;
+        let mut readout = Self {
+            weights,
+            ridge,
+            train_nrmse: 0.,
+            test_nrmse: 0.,
+            train_samples: split - washout,
+            test_samples: states.ncols() - split,
+        };
+        let prediction = readout.predict(states);
+        readout.train_nrmse = nrmse(&prediction[washout..split], &target[washout..split]);
+        readout.test_nrmse = nrmse(&prediction[split..], &target[split..]);
+        Ok(readout)
+    }
+
+    /// Readout value for every column of `states`
+    pub fn predict(&self, states: &DMatrix<f64>) -> Vec<f64> {
+        states
+            .column_iter()
+            .map(|column| column.iter().zip(&self.weights).map(|(s, w)| s * w).sum())
+            .collect()
+    }
+}
+
+impl fmt::Display for RidgeReadout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ridge {}  train nrmse {:.4} ({} cycles)  test nrmse {:.4} ({} cycles)",
+            self.ridge, self.train_nrmse, self.train_samples, self.test_nrmse, self.test_samples
+        )
+    }
+}
+
+fn nrmse(prediction: &[f64], target: &[f64]) -> f64 {
+    let n = target.len().max(1) as f64;
+    let mean = target.iter().sum::<f64>() / n;
+    let variance = target.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / n;
+    let mse = prediction
+        .iter()
+        .zip(target)
+        .map(|(p, t)| (p - t).powi(2))
+        .sum::<f64>()
+        / n;
+    if variance > 0. {
+        (mse / variance).sqrt()
+    } else {
+        mse.sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::preset;
+
+    #[test]
+    fn random_levels_are_seeded_and_held() {
+        let levels = random_levels(100, 10, 3);
+        assert_eq!(levels, random_levels(100, 10, 3));
+        assert_ne!(levels, random_levels(100, 10, 4));
+        assert!(
+            levels
+                .chunks(10)
+                .all(|hold| hold.iter().all(|&l| l == hold[0]))
+        );
+        assert!(levels.iter().all(|l| (0. ..=1.).contains(l)));
+    }
+
+    #[test]
+    fn input_pings_at_the_signal_density() {
+        let neurons = resolve_targets(&["chemotaxis"]).unwrap();
+        let mut half = preset::connectome();
+        let mut always = preset::connectome();
+        let input = |level: f64| ReservoirInput {
+            neurons: neurons.clone(),
+            signal: vec![level; 6],
+        };
+        // At 0.5 the first ping comes on the second cycle, so that state
+        // matches a single cycle at 1.0 from rest
+        let sparse = collect_states(&mut half, &[input(0.5)], 6);
+        let dense = collect_states(&mut always, &[input(1.)], 6);
+        assert_eq!(sparse.nrows(), 300);
+        assert!(sparse.row(299).iter().all(|&b| b == 1.));
+        assert_eq!(sparse.column(1), {
+            let mut one = preset::connectome();
+            collect_states(&mut one, &[input(1.)], 1)
+                .column(0)
+                .into_owned()
+        });
+        assert_ne!(sparse, dense);
+    }
+
+    #[test]
+    fn low_pass_smooths_without_touching_constant_rows() {
+        let states = DMatrix::from_row_slice(2, 4, &[0., 1., 1., 1., 1., 1., 1., 1.]);
+        let smoothed = low_pass(&states, 2.);
+        assert_eq!(
+            smoothed.row(0).iter().copied().collect::<Vec<_>>(),
+            [0., 0.5, 0.75, 0.875]
+        );
+        assert!(smoothed.row(1).iter().all(|&b| b == 1.));
+        assert_eq!(low_pass(&states, 1.), states);
+    }
+
+    #[test]
+    fn readout_recovers_a_linear_target() {
+        let signal = random_levels(400, 5, 1);
+        let states = DMatrix::from_fn(3, 400, |i, t| match i {
+            0 => signal[t],
+            1 => (t as f64 * 0.1).sin(),
+            _ => 1.,
+        });
+        let target: Vec<f64> = signal.iter().map(|s| 2. * s - 0.5).collect();
+        let readout = RidgeReadout::train(&states, &target, 20, 0.7, 1e-6).unwrap();
+        assert!(readout.train_nrmse < 1e-3 && readout.test_nrmse < 1e-3);
+        assert!((readout.weights[0] - 2.).abs() < 1e-3);
+        assert!((readout.weights[2] + 0.5).abs() < 1e-3);
+        assert_eq!((readout.train_samples, readout.test_samples), (266, 114));
+
+        assert!(RidgeReadout::train(&states, &target[1..], 20, 0.7, 1.).is_err());
+        assert!(RidgeReadout::train(&states, &target, 20, 1., 1.).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+
+use crate::connectome::{Edge, encode_rom_word};
+
+/// Pack edges into a ROM image: `[neurons_tot, address table.., connections..]`.
+///
+/// Edges are grouped by presynaptic neuron, keeping their relative order, so
+/// the edges of an existing ROM rebuild the identical image.
+pub fn build_rom(neurons_tot: u16, edges: &[Edge]) -> Result<Vec<u16>, String> {
+    let mut by_pre: Vec<Vec<u16>> = vec![vec![]; neurons_tot as usize];
+    for e in edges {
+        let outgoing = by_pre.get_mut(e.pre as usize).ok_or_else(|| {
+            format!(
+                "Edge {} -> {} starts at a muscle or unknown cell",
+                e.pre, e.post
+            )
+        })?;
+        outgoing.push(encode_rom_word(e.post, e.weight)?);
+    }
+
+    let mut rom = vec![neurons_tot];
+    let mut address = neurons_tot as usize + 2;
+    for outgoing in by_pre.iter() {
+        rom.push(address as u16);
+        address += outgoing.len();
+    }
+    rom.push(address as u16);
+    if address > u16::MAX as usize {
+        return Err("Wiring does not fit in a 16 bit ROM".to_string());
+    }
+    for outgoing in by_pre {
+        rom.extend(outgoing);
+    }
+    Ok(rom)
+}
+
+/// Read a ROM image from text. Every `0x` hex token is a word, so both a bare
+/// word list and a `rom.rs`-style array parse; `//` comments are skipped.
+pub fn read_rom_file(path: &Path) -> Result<Vec<u16>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    parse_rom_text(&text)
+}
+
+pub fn parse_rom_text(text: &str) -> Result<Vec<u16>, String> {
+    let rom = text
+        .lines()
+        .flat_map(|line| {
+            line.split("//")
+                .next()
+                .unwrap_or("")
+                .split(|c: char| !c.is_ascii_alphanumeric())
+        })
+        .filter_map(|token| token.strip_prefix("0x"))
+        .map(|hex| u16::from_str_radix(hex, 16).map_err(|err| format!("0x{}: {}", hex, err)))
+        .collect::<Result<Vec<u16>, String>>()?;
+    if rom.is_empty() {
+        return Err("No ROM words found".to_string());
+    }
+    Ok(rom)
+}
+
+/// Write a ROM image in the same layout as `emulations::c_elegans::rom`
+pub fn write_rom_file(path: &Path, rom: &[u16]) -> Result<(), String> {
+    fs::write(path, rom_to_text(rom)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn rom_to_text(rom: &[u16]) -> String {
+    let mut text = format!("pub const ROM: [u16; {}] = [\n", rom.len());
+    for row in rom.chunks(12) {
+        let words: Vec<String> = row.iter().map(|w| format!("0x{:04x}", w)).collect();
+        text.push_str(&format!("    {},\n", words.join(", ")));
+    }
+    text.push_str("];\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::preset;
+    use crate::emulations::c_elegans::rom::ROM;
+
+    #[test]
+    fn rom_text_round_trips() {
+        assert_eq!(parse_rom_text(&rom_to_text(&ROM)).unwrap(), ROM.to_vec());
+
+        let path = std::env::temp_dir().join(format!("rom_round_trip_{}.rs", std::process::id()));
+        write_rom_file(&path, &ROM).unwrap();
+        let read = read_rom_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), ROM.to_vec());
+
+        let commented = "// dataset run_0x1f 1.0\n0x0001, 0x0002 // 0xffff\n";
+        assert_eq!(parse_rom_text(commented).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn edges_rebuild_the_identical_rom() {
+        let connectome = preset::connectome();
+        let rom = build_rom(connectome.neurons_tot(), &connectome.edges()).unwrap();
+        assert_eq!(rom, ROM.to_vec());
+    }
+
+    #[test]
+    fn parse_and_build_reject_bad_input() {
+        assert!(parse_rom_text("no words here").is_err());
+        assert!(parse_rom_text("0x12345").is_err());
+        let from_muscle = [Edge {
+            pre: 5,
+            post: 0,
+            weight: 1,
+        }];
+        assert!(build_rom(3, &from_muscle).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+
+use nalgebra::{Complex, DMatrix, DVector};
+
+use crate::connectome::Edge;
+
+/// Weighted adjacency of the subnetwork `cells`, oriented for `x' = A x`:
+/// entry (i, j) is the weight from `cells[j]` onto `cells[i]`. Edges with an
+/// end outside `cells` are left out.
+pub fn adjacency(cells: &[u16], edges: &[Edge]) -> DMatrix<f64> {
+    let index = |id: u16| cells.iter().position(|&c| c == id);
+    let mut a = DMatrix::zeros(cells.len(), cells.len());
+    for e in edges {
+        if let (Some(pre), Some(post)) = (index(e.pre), index(e.post)) {
+            a[(post, pre)] += e.weight as f64;
+        }
+    }
+    a
+}
+
+/// Columns selecting `subset` out of `cells`, as the input matrix B of
+/// `x' = A x + B u`; transpose it for the output matrix C of `y = C x`.
+/// Members of `subset` outside `cells` are dropped.
+pub fn selection(cells: &[u16], subset: &[u16]) -> DMatrix<f64> {
+    let columns: Vec<usize> = subset
+        .iter()
+        .filter_map(|&id| cells.iter().position(|&c| c == id))
+        .collect();
+    let mut b = DMatrix::zeros(cells.len(), columns.len());
+    for (k, &row) in columns.iter().enumerate() {
+        b[(row, k)] = 1.;
+    }
+    b
+}
+
+/// Eigenvalues of a square matrix, largest modulus first
+pub fn eigenvalues(a: &DMatrix<f64>) -> Vec<Complex<f64>> {
+    // nalgebra's Schur decomposition does not handle an empty matrix
+    if a.is_empty() {
+        return vec![];
+    }
+    let mut values: Vec<Complex<f64>> = a.complex_eigenvalues().iter().copied().collect();
+    values.sort_by(|x, y| y.norm().total_cmp(&x.norm()));
+    values
+}
+
+/// Largest eigenvalue modulus, 0 for an empty matrix
+pub fn spectral_radius(a: &DMatrix<f64>) -> f64 {
+    eigenvalues(a).first().map_or(0., |v| v.norm())
+}
+
+/// Unit eigenvector of `a` for the (approximate) eigenvalue `value`, by
+/// inverse iteration with a slightly perturbed shift.
+pub fn eigenvector(a: &DMatrix<f64>, value: Complex<f64>) -> Result<DVector<Complex<f64>>, String> {
+    let n = a.nrows();
+    let shift = value + Complex::new(1e-9 * value.norm().max(1.), 0.);
+    let shifted = a.map(|x| Complex::new(x, 0.)) - DMatrix::from_diagonal_element(n, n, shift);
+    let lu = shifted.lu();
+    let mut v = DVector::from_element(n, Complex::new(1. / (n as f64).sqrt(), 0.));
+    for _ in 0..20 {
+        let next = lu
+            .solve(&v)
+            .ok_or_else(|| format!("eigenvector for {} did not converge", value))?;
+        let norm = next.norm();
+        if !norm.is_finite() || norm == 0. {
+            return Err(format!("eigenvector for {} did not converge", value));
+        }
+        v = next / Complex::new(norm, 0.);
+    }
+    // Rotate so the largest component is real and positive
+    let (k, _) = v
+        .iter()
+        .enumerate()
+        .max_by(|x, y| x.1.norm().total_cmp(&y.1.norm()))
+        .expect("not empty");
+    let phase = v[k] / Complex::new(v[k].norm(), 0.);
+    Ok(v / phase)
+}
+
+/// Graph Laplacian `D - W` of undirected links (gap junctions) among
+/// `cells`, with W symmetrised from `links` in either direction. Where both
+/// directions are listed the larger magnitude is kept.
+pub fn laplacian(cells: &[u16], links: &[Edge]) -> DMatrix<f64> {
+    let index = |id: u16| cells.iter().position(|&c| c == id);
+    let n = cells.len();
+    let mut w: DMatrix<f64> = DMatrix::zeros(n, n);
+    for e in links {
+        if let (Some(i), Some(j)) = (index(e.pre), index(e.post))
+            && i != j
+        {
+            let weight = (e.weight as f64).abs().max(w[(i, j)]);
+            w[(i, j)] = weight;
+            w[(j, i)] = weight;
+        }
+    }
+    let mut l = -w.clone();
+    for i in 0..n {
+        l[(i, i)] = w.row(i).sum();
+    }
+    l
+}
+
+/// Eigenvalues of a Laplacian in ascending order with their eigenvectors as
+/// columns. The number of (near) zero eigenvalues counts the connected
+/// components; the second smallest is the algebraic connectivity.
+pub fn laplacian_spectrum(l: &DMatrix<f64>) -> (Vec<f64>, DMatrix<f64>) {
+    let eigen = l.clone().symmetric_eigen();
+    let mut order: Vec<usize> = (0..eigen.eigenvalues.len()).collect();
+    order.sort_by(|&x, &y| eigen.eigenvalues[x].total_cmp(&eigen.eigenvalues[y]));
+    let values = order.iter().map(|&k| eigen.eigenvalues[k]).collect();
+    let vectors = DMatrix::from_columns(
+        &order
+            .iter()
+            .map(|&k| eigen.eigenvectors.column(k).into_owned())
+            .collect::<Vec<_>>(),
+    );
+    (values, vectors)
+}
+
+/// Finite-horizon controllability Gramian of `x' = A x + B u`:
+/// the sum over `k < horizon` of `A^k B B^T (A^T)^k`.
+pub fn controllability_gramian(a: &DMatrix<f64>, b: &DMatrix<f64>, horizon: usize) -> DMatrix<f64> {
+    let mut gramian = DMatrix::zeros(a.nrows(), a.nrows());
+    let mut m = b.clone();
+    for _ in 0..horizon {
+        gramian += &m * m.transpose();
+        m = a * m;
+    }
+    gramian
+}
+
+/// Finite-horizon observability Gramian of `x' = A x, y = C x`: the
+/// controllability Gramian of the dual system `(A^T, C^T)`.
+pub fn observability_gramian(a: &DMatrix<f64>, c: &DMatrix<f64>, horizon: usize) -> DMatrix<f64> {
+    controllability_gramian(&a.transpose(), &c.transpose(), horizon)
+}
+
+/// Scalar summary of a Gramian
+#[derive(Clone, Debug)]
+pub struct GramianSummary {
+    pub trace: f64,
+    pub min_eigenvalue: f64,
+    pub max_eigenvalue: f64,
+    /// Eigenvalues above `max_eigenvalue * 1e-10`: the dimension of the
+    /// reachable (or observable) subspace
+    pub rank: usize,
+    /// Diagonal entries: how strongly each state is reached (or seen)
+    pub diagonal: Vec<f64>,
+}
+
+impl GramianSummary {
+    pub fn new(gramian: &DMatrix<f64>) -> Self {
+        let values = gramian.clone().symmetric_eigen().eigenvalues;
+        let max = values.iter().copied().fold(0., f64::max);
+        Self {
+            trace: gramian.trace(),
+            min_eigenvalue: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max_eigenvalue: max,
+            rank: values.iter().filter(|&&v| v > max * 1e-10).count(),
+            diagonal: gramian.diagonal().iter().copied().collect(),
+        }
+    }
+}
+
+impl fmt::Display for GramianSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  trace            {:.4e}", self.trace)?;
+        writeln!(
+            f,
+            "  rank             {} of {}",
+            self.rank,
+            self.diagonal.len()
+        )?;
+        writeln!(f, "  max eigenvalue   {:.4e}", self.max_eigenvalue)?;
+        write!(f, "  min eigenvalue   {:.4e}", self.min_eigenvalue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(pre: u16, post: u16, weight: i8) -> Edge {
+        Edge { pre, post, weight }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn adjacency_is_oriented_post_by_pre() {
+        let edges = [edge(5, 7, 3), edge(7, 5, -2), edge(7, 9, 4)];
+        let a = adjacency(&[5, 7], &edges);
+        assert_eq!(a, DMatrix::from_row_slice(2, 2, &[0., -2., 3., 0.]));
+
+        let b = selection(&[5, 7, 9], &[9, 5, 1]);
+        assert_eq!(b, DMatrix::from_row_slice(3, 2, &[0., 1., 0., 0., 1., 0.]));
+    }
+
+    #[test]
+    fn eigenvalues_come_largest_first() {
+        let a = DMatrix::from_row_slice(3, 3, &[2., 0., 0., 0., -5., 0., 0., 0., 1.]);
+        let values: Vec<f64> = eigenvalues(&a).iter().map(|v| v.re).collect();
+        assert_eq!(values, [-5., 2., 1.]);
+        assert!(close(spectral_radius(&a), 5.));
+        assert_eq!(spectral_radius(&DMatrix::zeros(0, 0)), 0.);
+
+        // a 3-cycle has the cube roots of unity as eigenvalues
+        let cycle = adjacency(&[0, 1, 2], &[edge(0, 1, 1), edge(1, 2, 1), edge(2, 0, 1)]);
+        assert!(close(spectral_radius(&cycle), 1.));
+        let v = eigenvector(&cycle, Complex::new(1., 0.)).unwrap();
+        for x in v.iter() {
+            assert!(close(x.re, 1. / 3f64.sqrt()) && close(x.im, 0.));
+        }
+    }
+
+    #[test]
+    fn laplacian_counts_components() {
+        // two pairs, one link listed in both directions
+        let links = [edge(0, 1, 2), edge(1, 0, -3), edge(2, 3, 1), edge(3, 3, 9)];
+        let l = laplacian(&[0, 1, 2, 3], &links);
+        assert_eq!(l[(0, 0)], 3.);
+        assert_eq!(l[(0, 1)], -3.);
+        assert_eq!(l[(3, 3)], 1.);
+        assert!(l.row_sum().iter().all(|&s| close(s, 0.)));
+
+        let (values, vectors) = laplacian_spectrum(&l);
+        assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(values.iter().filter(|&&v| v.abs() < 1e-9).count(), 2);
+        assert!(close(values[3], 6.));
+        assert!(close(
+            (&l * vectors.column(3) - vectors.column(3) * 6.).norm(),
+            0.
+        ));
+    }
+
+    #[test]
+    fn gramians_reach_along_the_chain() {
+        // 0 -> 1 -> 2, driven at 0 and read at 2
+        let cells = [0, 1, 2];
+        let a = adjacency(&cells, &[edge(0, 1, 1), edge(1, 2, 1)]);
+        let b = selection(&cells, &[0]);
+        let short = GramianSummary::new(&controllability_gramian(&a, &b, 2));
+        assert_eq!(short.diagonal, [1., 1., 0.]);
+        assert_eq!(short.rank, 2);
+        let full = GramianSummary::new(&controllability_gramian(&a, &b, 3));
+        assert_eq!(full.rank, 3);
+        assert!(close(full.trace, 3.) && close(full.min_eigenvalue, 1.));
+
+        let c = selection(&cells, &[2]).transpose();
+        let seen = GramianSummary::new(&observability_gramian(&a, &c, 3));
+        assert_eq!(seen.diagonal, [1., 1., 1.]);
+        assert!(seen.to_string().contains("rank             3 of 3"));
+    }
+}
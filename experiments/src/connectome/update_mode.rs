@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the discharges of one neural cycle are applied to the next state.
+#[derive(Clone, Debug, Default)]
+pub enum UpdateMode {
+    /// Neurons fire in `NeuronId` order and each discharge resets the neuron's
+    /// next state immediately, exactly like the C firmware.
+    #[default]
+    Sequential,
+    /// Every discharge is decided from the current state, discharged neurons are
+    /// reset, then all contributions are summed and applied at once.
+    Synchronous,
+    /// Same per-neuron semantics as `Sequential`, but the firing order is
+    /// shuffled every cycle.
+    RandomAsync { seed: u64 },
+    /// Synchronous discharges whose contributions arrive after a per-edge delay
+    /// (in cycles). A delay of 1 behaves like `Synchronous`.
+    EventDriven(EdgeDelays),
+}
+
+impl fmt::Display for UpdateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateMode::Sequential => write!(f, "sequential"),
+            UpdateMode::Synchronous => write!(f, "synchronous"),
+            UpdateMode::RandomAsync { seed } => write!(f, "random-async(seed={})", seed),
+            UpdateMode::EventDriven(delays) => {
+                write!(f, "event-driven(max_delay={})", delays.max_delay())
+            }
+        }
+    }
+}
+
+/// Parses `sequential`, `synchronous`, `random[:<seed>]` (seed 0 by
+/// default) and `event[:<delay>]` (delay 1 by default).
+impl FromStr for UpdateMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        let (name, arg) = mode.split_once(':').unwrap_or((mode, ""));
+        let number = |default: u64| -> Result<u64, String> {
+            if arg.is_empty() {
+                Ok(default)
+            } else {
+                arg.parse::<u64>()
+                    .map_err(|err| format!("{}: {}", mode, err))
+            }
+        };
+        match name {
+            "sequential" if arg.is_empty() => Ok(UpdateMode::Sequential),
+            "synchronous" if arg.is_empty() => Ok(UpdateMode::Synchronous),
+            "random" => Ok(UpdateMode::RandomAsync { seed: number(0)? }),
+            "event" => Ok(UpdateMode::EventDriven(EdgeDelays::uniform(
+                number(1)?.min(u8::MAX as u64) as u8,
+            ))),
+            _ => Err(format!("Unknown update mode {}", mode)),
+        }
+    }
+}
+
+/// Per-edge transmission delays for `UpdateMode::EventDriven`.
+#[derive(Clone, Debug)]
+pub struct EdgeDelays {
+    default: u8,
+    overrides: HashMap<(u16, u16), u8>,
+}
+
+impl EdgeDelays {
+    /// All edges share the same delay (clamped to at least one cycle).
+    pub fn uniform(delay: u8) -> Self {
+        Self {
+            default: delay.max(1),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Override the delay of the edge `pre -> post`.
+    pub fn with_delay(mut self, pre: u16, post: u16, delay: u8) -> Self {
+        self.overrides.insert((pre, post), delay.max(1));
+        self
+    }
+
+    pub fn delay(&self, pre: u16, post: u16) -> u8 {
+        *self.overrides.get(&(pre, post)).unwrap_or(&self.default)
+    }
+
+    pub fn max_delay(&self) -> u8 {
+        self.overrides.values().copied().fold(self.default, u8::max)
+    }
+}
+
+/// Ring buffer of contributions that are still travelling along delayed edges.
+#[derive(Clone)]
+pub(crate) struct DelayLine {
+    slots: Vec<Vec<i32>>,
+    cursor: usize,
+}
+
+impl DelayLine {
+    pub(crate) fn new(max_delay: u8, cells: usize) -> Self {
+        Self {
+            slots: (0..max_delay.max(1)).map(|_| vec![0; cells]).collect(),
+            cursor: 0,
+        }
+    }
+
+    /// Add everything due this cycle into `acc` and clear the slot.
+    pub(crate) fn drain_due(&mut self, acc: &mut [i32]) {
+        for (a, pending) in acc.iter_mut().zip(self.slots[self.cursor].iter_mut()) {
+            *a += *pending;
+            *pending = 0;
+        }
+    }
+
+    /// Queue `weight` for cell `id`, arriving `delay` cycles from now (`delay >= 2`).
+    pub(crate) fn schedule(&mut self, delay: u8, id: u16, weight: i8) {
+        let slot = (self.cursor + delay as usize - 1) % self.slots.len();
+        self.slots[slot][id as usize] += weight as i32;
+    }
+
+    pub(crate) fn advance(&mut self) {
+        self.cursor = (self.cursor + 1) % self.slots.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::Connectome;
+    use crate::connectome::edge_list::Circuit;
+
+    /// A and B excite each other above threshold
+    fn pair(mode: UpdateMode) -> Connectome {
+        let mut connectome = Circuit::parse("threshold 20\nneuron A\nneuron B\nA B 30\nB A 30\n")
+            .unwrap()
+            .connectome()
+            .unwrap();
+        connectome.set_update_mode(mode);
+        connectome
+    }
+
+    /// Per-cycle discharges of A and B after stimulating `stimulus` once
+    fn discharges(connectome: &mut Connectome, stimulus: &[u16], cycles: usize) -> Vec<[u8; 2]> {
+        let mut rows = vec![];
+        for cycle in 0..cycles {
+            connectome.neural_cycle((cycle == 0).then_some(stimulus));
+            let mut row = [0; 2];
+            connectome.discharge_query(&[0, 1], &mut row);
+            rows.push(row);
+        }
+        rows
+    }
+
+    #[test]
+    fn parses_modes_with_and_without_arguments() {
+        let parse = |mode: &str| mode.parse::<UpdateMode>();
+        assert!(matches!(parse("sequential"), Ok(UpdateMode::Sequential)));
+        assert!(matches!(parse("synchronous"), Ok(UpdateMode::Synchronous)));
+        assert!(matches!(
+            parse("random"),
+            Ok(UpdateMode::RandomAsync { seed: 0 })
+        ));
+        assert!(matches!(
+            parse("random:7"),
+            Ok(UpdateMode::RandomAsync { seed: 7 })
+        ));
+        assert!(matches!(parse("event"), Ok(UpdateMode::EventDriven(d)) if d.max_delay() == 1));
+        assert!(matches!(parse("event:3"), Ok(UpdateMode::EventDriven(d)) if d.max_delay() == 3));
+        for bad in ["sideways", "random:x", "event:-1", "sequential:2"] {
+            assert!(parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn delay_line_releases_contributions_after_their_delay() {
+        let mut line = DelayLine::new(3, 2);
+        let mut acc = vec![0; 2];
+        line.drain_due(&mut acc);
+        line.schedule(2, 0, 5);
+        line.schedule(3, 1, -7);
+        line.advance();
+        assert_eq!(acc, [0, 0]);
+
+        line.drain_due(&mut acc);
+        assert_eq!(acc, [5, 0]);
+        line.advance();
+        line.drain_due(&mut acc);
+        assert_eq!(acc, [5, -7]);
+        // Drained slots are cleared, so nothing comes round again
+        for _ in 0..3 {
+            line.advance();
+            line.drain_due(&mut acc);
+        }
+        assert_eq!(acc, [5, -7]);
+    }
+
+    #[test]
+    fn edge_delays_clamp_to_one_cycle_and_override_per_edge() {
+        let delays = EdgeDelays::uniform(0)
+            .with_delay(3, 4, 5)
+            .with_delay(1, 2, 0);
+        assert_eq!(delays.delay(0, 1), 1);
+        assert_eq!(delays.delay(3, 4), 5);
+        assert_eq!(delays.delay(1, 2), 1);
+        assert_eq!(delays.max_delay(), 5);
+    }
+
+    #[test]
+    fn sequential_reset_swallows_input_from_earlier_neurons() {
+        // Both fire in the second cycle. In id order, A's input to B is wiped
+        // when B fires and resets, so only A keeps going.
+        let sequential = discharges(&mut pair(UpdateMode::Sequential), &[0, 1], 4);
+        assert_eq!(sequential, [[0, 0], [1, 1], [1, 0], [0, 1]]);
+        // Resetting before applying the inputs keeps both going.
+        let synchronous = discharges(&mut pair(UpdateMode::Synchronous), &[0, 1], 4);
+        assert_eq!(synchronous, [[0, 0], [1, 1], [1, 1], [1, 1]]);
+    }
+
+    #[test]
+    fn event_driven_with_unit_delay_matches_synchronous() {
+        let synchronous = discharges(&mut pair(UpdateMode::Synchronous), &[0], 20);
+        let event = discharges(
+            &mut pair(UpdateMode::EventDriven(EdgeDelays::uniform(1))),
+            &[0],
+            20,
+        );
+        assert_eq!(event, synchronous);
+    }
+
+    #[test]
+    fn event_driven_delay_postpones_the_target() {
+        let synchronous = discharges(&mut pair(UpdateMode::Synchronous), &[1], 6);
+        let delayed = discharges(
+            &mut pair(UpdateMode::EventDriven(
+                EdgeDelays::uniform(1).with_delay(0, 1, 3),
+            )),
+            &[1],
+            6,
+        );
+        let first_b = |rows: &[[u8; 2]]| rows.iter().position(|row| row[1] == 1);
+        assert_eq!(first_b(&synchronous), Some(2));
+        assert_eq!(first_b(&delayed), Some(4));
+    }
+}
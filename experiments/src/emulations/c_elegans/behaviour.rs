@@ -0,0 +1,132 @@
+use std::fmt;
+
+/// Locomotion direction inferred from A (backward) and B (forward) motor neurons.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Locomotion {
+    Forward,
+    Backward,
+    Stationary,
+}
+
+impl fmt::Display for Locomotion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Locomotion::Forward => write!(f, "forward"),
+            Locomotion::Backward => write!(f, "backward"),
+            Locomotion::Stationary => write!(f, "stationary"),
+        }
+    }
+}
+
+/// Classify from A and B discharge counts. The two classes are normalised by
+/// their sizes, and the direction needs a relative lead of more than `margin`.
+pub fn classify(
+    a_discharges: usize,
+    a_size: usize,
+    b_discharges: usize,
+    b_size: usize,
+    margin: f64,
+) -> Locomotion {
+    let a = a_discharges as f64 / a_size.max(1) as f64;
+    let b = b_discharges as f64 / b_size.max(1) as f64;
+    if a == 0. && b == 0. {
+        Locomotion::Stationary
+    } else if b > a * (1. + margin) {
+        Locomotion::Forward
+    } else if a > b * (1. + margin) {
+        Locomotion::Backward
+    } else {
+        Locomotion::Stationary
+    }
+}
+
+/// Label of each `window`-cycle block of an A/B discharge trace plus the
+/// fraction of windows per label.
+#[derive(Clone, Debug)]
+pub struct BehaviourSummary {
+    pub windows: Vec<Locomotion>,
+    pub forward: f64,
+    pub backward: f64,
+    pub stationary: f64,
+}
+
+impl BehaviourSummary {
+    /// `a_trace` and `b_trace` hold one row of discharge flags per cycle.
+    pub fn from_traces(
+        a_trace: &[Vec<u8>],
+        b_trace: &[Vec<u8>],
+        window: usize,
+        margin: f64,
+    ) -> Self {
+        let window = window.max(1);
+        let count = |rows: &[Vec<u8>]| rows.iter().flatten().map(|&d| d as usize).sum::<usize>();
+        let windows: Vec<Locomotion> = a_trace
+            .chunks(window)
+            .zip(b_trace.chunks(window))
+            .map(|(a, b)| {
+                let a_size = a.first().map_or(0, |r| r.len()) * a.len();
+                let b_size = b.first().map_or(0, |r| r.len()) * b.len();
+                classify(count(a), a_size, count(b), b_size, margin)
+            })
+            .collect();
+        let fraction = |label: Locomotion| {
+            windows.iter().filter(|&&w| w == label).count() as f64 / windows.len().max(1) as f64
+        };
+        Self {
+            forward: fraction(Locomotion::Forward),
+            backward: fraction(Locomotion::Backward),
+            stationary: fraction(Locomotion::Stationary),
+            windows,
+        }
+    }
+}
+
+impl fmt::Display for BehaviourSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "windows     {}", self.windows.len())?;
+        writeln!(f, "forward     {:.3}", self.forward)?;
+        writeln!(f, "backward    {:.3}", self.backward)?;
+        write!(f, "stationary  {:.3}", self.stationary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_needs_a_relative_lead() {
+        assert_eq!(classify(0, 10, 0, 10, 0.2), Locomotion::Stationary);
+        assert_eq!(classify(2, 10, 5, 10, 0.2), Locomotion::Forward);
+        assert_eq!(classify(5, 10, 2, 10, 0.2), Locomotion::Backward);
+        assert_eq!(classify(10, 10, 11, 10, 0.2), Locomotion::Stationary);
+        // normalised by class size
+        assert_eq!(classify(4, 4, 4, 8, 0.2), Locomotion::Backward);
+    }
+
+    #[test]
+    fn summary_labels_each_window() {
+        let rows = |flag: u8, n: usize| vec![vec![flag; 2]; n];
+        let a = [rows(1, 4), rows(0, 4), rows(0, 2)].concat();
+        let b = [rows(0, 4), rows(1, 4), rows(0, 2)].concat();
+        let summary = BehaviourSummary::from_traces(&a, &b, 4, 0.2);
+        assert_eq!(
+            summary.windows,
+            [
+                Locomotion::Backward,
+                Locomotion::Forward,
+                Locomotion::Stationary
+            ]
+        );
+        let third = 1. / 3.;
+        assert_eq!(
+            (summary.forward, summary.backward, summary.stationary),
+            (third, third, third)
+        );
+        assert!(
+            BehaviourSummary::from_traces(&[], &[], 0, 0.2)
+                .windows
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::connectome::neuromodulation::{ModulatorChannel, Neuromodulation};
+use crate::connectome::{Connectome, Edge, rom_image::build_rom};
+use crate::emulations::c_elegans::dataset::{Dataset, DatasetSpec};
+use crate::emulations::c_elegans::neuron_groups::neuropeptide_expressing;
+use crate::emulations::c_elegans::neuron_ids::{CELLS, NeuronId, cell_name};
+use crate::emulations::c_elegans::preset;
+
+pub(crate) const CONNECTOME_CSV: &str = include_str!("CElegansNeuronTables/Connectome.csv");
+pub(crate) const MUSCLE_CSV: &str = include_str!("CElegansNeuronTables/NeuronsToMuscle.csv");
+
+/// Modulators released along `Connectome.csv` edges, with the transmitter
+/// names used there. Time constants and effects are rough assumptions:
+/// serotonin and dopamine slow the animal (higher thresholds), octopamine
+/// arouses it, and FMRFamide-like peptides raise input gain.
+const MODULATORS: [(&str, &str, f32, f32, f32); 4] = [
+    ("serotonin", "Serotonin", 300., 20., 0.),
+    ("dopamine", "Dopamine", 200., 10., 0.),
+    ("octopamine", "Octapamine", 300., -15., 0.),
+    ("fmrfamide", "FMRFamide", 500., 0., 0.5),
+];
+
+/// Neurons in the C. elegans ROM; cells from here on are muscles
+pub const NEURONS_TOT: u16 = NeuronId::MANAL as u16;
+
+/// Weight of one CSV row: the synapse count, negative for GABA, clamped to
+/// the range a ROM word can hold.
+pub(crate) fn row_weight(count: &str, neurotransmitter: &str) -> Result<i8, String> {
+    let count: i32 = count
+        .trim()
+        .parse()
+        .map_err(|err| format!("connection count {}: {}", count, err))?;
+    let signed = if neurotransmitter.trim() == "GABA" {
+        -count
+    } else {
+        count
+    };
+    Ok(signed.clamp(-64, 63) as i8)
+}
+
+/// `Connectome.csv` (Origin, Target, Type, Number of Connections,
+/// Neurotransmitter) and optionally `NeuronsToMuscle.csv` (Neuron, Muscle,
+/// Number of Connections, Neurotransmitter), read with the bundled dataset's
+/// table layouts. Every cell name has to map onto `NeuronId`.
+fn load_tables(connectome_csv: &Path, muscle_csv: Option<&Path>) -> Result<Dataset, String> {
+    let mut spec = DatasetSpec::bundled();
+    spec.name = connectome_csv.display().to_string();
+    spec.table[0].path = connectome_csv.to_path_buf();
+    match muscle_csv {
+        Some(path) => spec.table[1].path = path.to_path_buf(),
+        None => spec.table.truncate(1),
+    }
+    let dataset = spec.load(Path::new(""))?;
+    if !dataset.report.unmapped.is_empty() {
+        let names: Vec<&str> = dataset.report.unmapped.keys().map(|n| n.as_str()).collect();
+        return Err(format!("{}: unknown cells {}", spec.name, names.join(" ")));
+    }
+    Ok(dataset)
+}
+
+/// Edges from `Connectome.csv` and optionally `NeuronsToMuscle.csv`. Where
+/// several rows join the same pair (e.g. a chemical synapse and a gap
+/// junction), the strongest one wins, as in the built-in ROM.
+pub fn load_csv_edges(
+    connectome_csv: &Path,
+    muscle_csv: Option<&Path>,
+) -> Result<Vec<Edge>, String> {
+    load_tables(connectome_csv, muscle_csv).map(|dataset| dataset.edges)
+}
+
+/// Write edges in the `Connectome.csv` layout, muscle targets included, so
+/// `load_csv_edges` reads them back unchanged. Negative weights are written
+/// as GABA with their magnitude as the connection count.
+pub fn write_csv_edges<W: Write>(writer: W, edges: &[Edge]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let header = [
+        "Origin",
+        "Target",
+        "Type",
+        "Number of Connections",
+        "Neurotransmitter",
+    ];
+    writer.write_record(header).map_err(|err| err.to_string())?;
+    for e in edges {
+        let transmitter = if e.weight < 0 { "GABA" } else { "Generic" };
+        writer
+            .write_record([
+                cell_name(e.pre),
+                cell_name(e.post),
+                "Send".to_string(),
+                e.weight.unsigned_abs().to_string(),
+                transmitter.to_string(),
+            ])
+            .map_err(|err| err.to_string())?;
+    }
+    writer.flush().map_err(|err| err.to_string())
+}
+
+/// Build a connectome from the CSV tables instead of the built-in ROM
+pub fn connectome_from_csv(
+    connectome_csv: &Path,
+    muscle_csv: Option<&Path>,
+) -> Result<Connectome, String> {
+    let edges = load_csv_edges(connectome_csv, muscle_csv)?;
+    preset::with_names(Connectome::from_rom(
+        build_rom(NEURONS_TOT, &edges)?,
+        CELLS,
+    )?)
+}
+
+/// Gap junctions (rows of type `GapJunction`) in `Connectome.csv`, one edge
+/// per row with the connection count as weight. Gap junctions are
+/// undirected; the table usually lists both directions.
+pub fn gap_junctions_from_csv(connectome_csv: &Path) -> Result<Vec<Edge>, String> {
+    load_tables(connectome_csv, None).map(|dataset| dataset.gap_junctions)
+}
+
+/// `gap_junctions_from_csv` on the bundled `Connectome.csv`
+pub fn builtin_gap_junctions() -> Vec<Edge> {
+    DatasetSpec::bundled()
+        .load(Path::new(""))
+        .expect("bundled tables are valid")
+        .gap_junctions
+}
+
+/// Volume transmission from the transmitter column of `Connectome.csv`.
+///
+/// Rows naming a modulator (alone or mixed, e.g. `Serotonin_Acetylcholine`)
+/// make their origin a source and their target a regional target, with a
+/// sensitivity proportional to the connection count it receives. Neurons that
+/// `Sensory.csv` lists as expressing FMRFamide-related peptides are added as
+/// FMRFamide sources. The wired edges themselves are left as they are.
+pub fn neuromodulation_from_csv(connectome_csv: &Path) -> Result<Neuromodulation, String> {
+    let file = std::fs::File::open(connectome_csv)
+        .map_err(|err| format!("{}: {}", connectome_csv.display(), err))?;
+    read_modulators(file).map_err(|err| format!("{}: {}", connectome_csv.display(), err))
+}
+
+/// `neuromodulation_from_csv` on the bundled `Connectome.csv`
+pub fn builtin_neuromodulation() -> Neuromodulation {
+    read_modulators(CONNECTOME_CSV.as_bytes()).expect("bundled Connectome.csv is valid")
+}
+
+fn read_modulators(csv: impl Read) -> Result<Neuromodulation, String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(csv);
+    let mut sources: Vec<Vec<u16>> = vec![vec![]; MODULATORS.len()];
+    let mut targets: Vec<BTreeMap<u16, f32>> = vec![BTreeMap::new(); MODULATORS.len()];
+    for (n, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| err.to_string())?;
+        let field = |i: usize| record.get(i).unwrap_or("").trim();
+        let at = |err: String| format!("row {}: {}", n + 2, err);
+        let transmitter = field(4);
+        for (k, (_, column, ..)) in MODULATORS.iter().enumerate() {
+            if !transmitter.split('_').any(|t| t == *column) {
+                continue;
+            }
+            let pre = field(0).parse::<NeuronId>().map_err(at)? as u16;
+            let post = field(1).parse::<NeuronId>().map_err(at)? as u16;
+            let count: f32 = field(3)
+                .parse()
+                .map_err(|err| at(format!("connection count {}: {}", field(3), err)))?;
+            sources[k].push(pre);
+            if post < NEURONS_TOT {
+                *targets[k].entry(post).or_insert(0.) += count;
+            }
+        }
+    }
+    sources[3].extend(neuropeptide_expressing("FMRFamide"));
+
+    let mut modulation = Neuromodulation::new(NEURONS_TOT);
+    for (k, &(name, _, tau, threshold_shift, gain_shift)) in MODULATORS.iter().enumerate() {
+        sources[k].sort_unstable();
+        sources[k].dedup();
+        let max = targets[k].values().copied().fold(0., f32::max);
+        let targets = targets[k]
+            .iter()
+            .map(|(&id, &count)| (id, count / max))
+            .collect();
+        modulation = modulation.with_channel(
+            ModulatorChannel::new(name, tau)
+                .with_sources(std::mem::take(&mut sources[k]))
+                .with_targets(targets)
+                .with_effect(threshold_shift, gain_shift),
+        );
+    }
+    Ok(modulation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(name: &str) -> u16 {
+        name.parse::<NeuronId>().unwrap() as u16
+    }
+
+    fn temp_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("neuro_rust_{}_{}.csv", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn row_weights_are_signed_and_clamped() {
+        assert_eq!(row_weight("3", "Acetylcholine"), Ok(3));
+        assert_eq!(row_weight(" 5 ", "GABA"), Ok(-5));
+        assert_eq!(row_weight("200", "Generic"), Ok(63));
+        assert_eq!(row_weight("200", "GABA"), Ok(-64));
+        assert!(row_weight("many", "GABA").is_err());
+    }
+
+    #[test]
+    fn written_edges_read_back_unchanged() {
+        let edges = vec![
+            Edge {
+                pre: id("ADAL"),
+                post: id("AVAL"),
+                weight: 4,
+            },
+            Edge {
+                pre: id("AVAL"),
+                post: id("VA1"),
+                weight: -9,
+            },
+            Edge {
+                pre: id("VA1"),
+                post: id("MVL07"),
+                weight: 12,
+            },
+        ];
+        let mut csv = Vec::new();
+        write_csv_edges(&mut csv, &edges).unwrap();
+        let path = temp_file("csv_round_trip", &csv);
+        let read = load_csv_edges(&path, None);
+        let connectome = connectome_from_csv(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), edges);
+        assert_eq!(connectome.unwrap().cells().name(id("VA1")), "VA1");
+    }
+
+    #[test]
+    fn unknown_cells_are_an_error() {
+        let csv = "Origin,Target,Type,Number of Connections,Neurotransmitter\n\
+            ADAL,NOTACELL,Send,2,Generic\n";
+        let path = temp_file("csv_unknown", csv.as_bytes());
+        let err = load_csv_edges(&path, None).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.contains("unknown cells NOTACELL"));
+    }
+
+    #[test]
+    fn modulators_come_from_the_transmitter_column() {
+        let csv = "Origin,Target,Type,Number of Connections,Neurotransmitter\n\
+            ADFL,AIYL,Send,2,Serotonin_Acetylcholine\n\
+            ADFL,AIYR,Send,4,Serotonin\n\
+            ADAL,AVAL,Send,3,Acetylcholine\n";
+        let modulation = read_modulators(csv.as_bytes()).unwrap();
+        let serotonin = modulation.channel("serotonin").unwrap();
+        assert_eq!(serotonin.sources(), [id("ADFL")]);
+        assert_eq!(serotonin.targets(), [(id("AIYL"), 0.5), (id("AIYR"), 1.)]);
+        assert!(modulation.channel("dopamine").unwrap().sources().is_empty());
+        // peptide sources come from Sensory.csv
+        let fmrfamide = modulation.channel("fmrfamide").unwrap();
+        assert_eq!(fmrfamide.sources().len(), {
+            let mut ids = neuropeptide_expressing("FMRFamide");
+            ids.sort_unstable();
+            ids.dedup();
+            ids.len()
+        });
+
+        let builtin = builtin_neuromodulation();
+        assert_eq!(builtin.channels().len(), MODULATORS.len());
+        assert!(!builtin.channel("serotonin").unwrap().sources().is_empty());
+        assert!(!builtin_gap_junctions().is_empty());
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Bar, BarChart, Block, Paragraph};
+
+use crate::connectome::Connectome;
+use crate::emulations::c_elegans::behaviour::{Locomotion, classify};
+use crate::emulations::c_elegans::neuron_groups::{
+    CHEMOTAXIS, MOTOR_NEURON_A, MOTOR_NEURON_B, NOSE_TOUCH, body_wall_quadrant,
+};
+use crate::emulations::c_elegans::neuron_ids::cell_name;
+use crate::emulations::c_elegans::protocol::{Protocol, ProtocolStep};
+
+/// Cycles of raster kept for scrolling
+const HISTORY: usize = 400;
+
+/// Settings of the live dashboard.
+#[derive(Clone, Debug)]
+pub struct DashboardParams {
+    /// Cycles over which the locomotion label is taken
+    pub window: usize,
+    /// Relative A/B lead needed for a direction, as in `behaviour::classify`
+    pub margin: f64,
+    /// Cycles an injected stimulus lasts
+    pub inject_cycles: usize,
+    /// Time between frames
+    pub tick: Duration,
+}
+
+impl Default for DashboardParams {
+    fn default() -> Self {
+        Self {
+            window: 50,
+            margin: 0.2,
+            inject_cycles: 100,
+            tick: Duration::from_millis(50),
+        }
+    }
+}
+
+/// A stimulus added by key press on top of the protocol's
+#[derive(Clone, Debug)]
+struct Injection {
+    name: &'static str,
+    ids: Vec<u16>,
+    remaining: usize,
+}
+
+/// A protocol run that can be paused, stepped and stimulated by hand, and
+/// drawn as a terminal dashboard.
+///
+/// After the protocol ends the connectome keeps running without a stimulus,
+/// so injections still have an effect.
+pub struct Dashboard {
+    pub connectome: Connectome,
+    pub params: DashboardParams,
+    protocol: Protocol,
+    step: usize,
+    remaining: usize,
+    paused: bool,
+    cycles_per_tick: usize,
+    injection: Option<Injection>,
+    /// A then B motor discharge flags of recent cycles, newest last
+    raster: VecDeque<Vec<u8>>,
+}
+
+impl Dashboard {
+    pub fn new(connectome: Connectome, protocol: Protocol, params: DashboardParams) -> Self {
+        let mut dashboard = Self {
+            connectome,
+            params,
+            protocol,
+            step: 0,
+            remaining: 0,
+            paused: false,
+            cycles_per_tick: 1,
+            injection: None,
+            raster: VecDeque::with_capacity(HISTORY),
+        };
+        dashboard.enter_run_step();
+        dashboard
+    }
+
+    /// Apply settings and lesions up to the next run step
+    fn enter_run_step(&mut self) {
+        while let Some(step) = self.protocol.steps.get(self.step) {
+            match step {
+                ProtocolStep::Threshold(threshold) => self.connectome.set_threshold(*threshold),
+                ProtocolStep::Ablate(ids) => ids.iter().for_each(|&id| self.connectome.ablate(id)),
+                ProtocolStep::Run { cycles, .. } if *cycles > 0 => {
+                    self.remaining = *cycles;
+                    return;
+                }
+                ProtocolStep::Run { .. } => {}
+            }
+            self.step += 1;
+        }
+    }
+
+    fn protocol_stimulus(&self) -> &[u16] {
+        match self.protocol.steps.get(self.step) {
+            Some(ProtocolStep::Run { stimulus, .. }) => stimulus,
+            _ => &[],
+        }
+    }
+
+    /// Whether every protocol step has run
+    pub fn finished(&self) -> bool {
+        self.step >= self.protocol.steps.len()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Run one neural cycle with the protocol's and any injected stimulus
+    pub fn advance(&mut self) {
+        let mut stimulus = self.protocol_stimulus().to_vec();
+        if let Some(injection) = &mut self.injection {
+            stimulus.extend(&injection.ids);
+            injection.remaining -= 1;
+            if injection.remaining == 0 {
+                self.injection = None;
+            }
+        }
+        self.connectome
+            .neural_cycle((!stimulus.is_empty()).then_some(stimulus.as_slice()));
+
+        let motor = [MOTOR_NEURON_A.as_slice(), MOTOR_NEURON_B.as_slice()].concat();
+        let mut flags = vec![0u8; motor.len()];
+        self.connectome.discharge_query(&motor, &mut flags);
+        if self.raster.len() == HISTORY {
+            self.raster.pop_front();
+        }
+        self.raster.push_back(flags);
+
+        if !self.finished() {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.step += 1;
+                self.enter_run_step();
+            }
+        }
+    }
+
+    /// React to a key; returns false when the dashboard should close.
+    ///
+    /// Space pauses, `s` or right arrow steps one cycle, `c` and `t` inject
+    /// chemotaxis and nose touch, `x` clears the injection, `+` and `-`
+    /// change the cycles per frame and `q` or Esc quits.
+    pub fn handle_key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('s') | KeyCode::Right => {
+                self.paused = true;
+                self.advance();
+            }
+            KeyCode::Char('c') => self.inject("chemotaxis", CHEMOTAXIS.to_vec()),
+            KeyCode::Char('t') => self.inject("nose_touch", NOSE_TOUCH.to_vec()),
+            KeyCode::Char('x') => self.injection = None,
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.cycles_per_tick = (self.cycles_per_tick * 2).min(256)
+            }
+            KeyCode::Char('-') => self.cycles_per_tick = (self.cycles_per_tick / 2).max(1),
+            _ => {}
+        }
+        true
+    }
+
+    fn inject(&mut self, name: &'static str, ids: Vec<u16>) {
+        self.injection = Some(Injection {
+            name,
+            ids,
+            remaining: self.params.inject_cycles.max(1),
+        });
+    }
+
+    /// Locomotion over the last `window` cycles
+    pub fn locomotion(&self) -> Locomotion {
+        let recent = self.raster.iter().rev().take(self.params.window.max(1));
+        let (mut a, mut b, mut cycles) = (0, 0, 0);
+        for row in recent {
+            let (row_a, row_b) = row.split_at(MOTOR_NEURON_A.len());
+            a += row_a.iter().map(|&d| d as usize).sum::<usize>();
+            b += row_b.iter().map(|&d| d as usize).sum::<usize>();
+            cycles += 1;
+        }
+        classify(
+            a,
+            MOTOR_NEURON_A.len() * cycles,
+            b,
+            MOTOR_NEURON_B.len() * cycles,
+            self.params.margin,
+        )
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let [header, raster, muscles, help] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Min(8),
+            Constraint::Length(16),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        frame.render_widget(self.header(), header);
+        self.render_raster(frame, raster);
+        self.render_muscles(frame, muscles);
+        frame.render_widget(
+            Paragraph::new(
+                "space pause  s/→ step  c chemotaxis  t nose touch  x clear  +/- speed  q quit",
+            )
+            .dark_gray(),
+            help,
+        );
+    }
+
+    fn header(&self) -> Paragraph<'_> {
+        let state = if self.paused { "paused" } else { "running" };
+        let step = if self.finished() {
+            "protocol finished, free running".to_string()
+        } else {
+            format!(
+                "step {}/{}, {} cycles left",
+                self.step + 1,
+                self.protocol.steps.len(),
+                self.remaining
+            )
+        };
+        let mut stimulus = describe(self.protocol_stimulus());
+        if let Some(injection) = &self.injection {
+            stimulus = format!(
+                "{} + {} ({} cycles)",
+                stimulus, injection.name, injection.remaining
+            );
+        }
+        let locomotion = self.locomotion();
+        let colour = match locomotion {
+            Locomotion::Forward => Color::Green,
+            Locomotion::Backward => Color::Red,
+            Locomotion::Stationary => Color::Yellow,
+        };
+        Paragraph::new(vec![
+            Line::from(vec![
+                Span::raw(format!("cycle {}  ", self.connectome.cycle())),
+                Span::raw(format!("{}  ", state)).bold(),
+                Span::raw(format!("{} cycles/frame  {}", self.cycles_per_tick, step)),
+            ]),
+            Line::from(format!("stimulus {}", stimulus)),
+            Line::from(vec![
+                Span::raw("locomotion "),
+                Span::styled(locomotion.to_string(), Style::new().fg(colour).bold()),
+            ]),
+        ])
+        .block(Block::bordered().title(" C. elegans connectome "))
+    }
+
+    /// A (red) and B (green) motor neurons as rows, recent cycles as columns;
+    /// each text row shows two neurons with half blocks
+    fn render_raster(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" A/B motor neurons ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        const LABEL: usize = 12;
+        let columns = (inner.width as usize).saturating_sub(LABEL);
+        let cycles: Vec<&Vec<u8>> = self
+            .raster
+            .iter()
+            .skip(self.raster.len().saturating_sub(columns))
+            .collect();
+        let ids = [MOTOR_NEURON_A.as_slice(), MOTOR_NEURON_B.as_slice()].concat();
+        let colour = |i: usize| {
+            if i < MOTOR_NEURON_A.len() {
+                Color::Red
+            } else {
+                Color::Green
+            }
+        };
+        let lines: Vec<Line> = (0..ids.len())
+            .step_by(2)
+            .map(|top| {
+                let bottom = top + 1;
+                let label = match ids.get(bottom) {
+                    Some(&b) => format!("{:>5} {:<5} ", cell_name(ids[top]), cell_name(b)),
+                    None => format!("{:>5}       ", cell_name(ids[top])),
+                };
+                let mut spans = vec![Span::raw(label).dark_gray()];
+                spans.extend(cycles.iter().map(|row| {
+                    let upper = row[top] == 1;
+                    let lower = row.get(bottom) == Some(&1);
+                    let (symbol, fg, bg) = match (upper, lower) {
+                        (true, true) => ("▀", colour(top), colour(bottom)),
+                        (true, false) => ("▀", colour(top), Color::Reset),
+                        (false, true) => ("▄", colour(bottom), Color::Reset),
+                        (false, false) => (" ", Color::Reset, Color::Reset),
+                    };
+                    Span::styled(symbol, Style::new().fg(fg).bg(bg))
+                }));
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+
+    /// Summed positive input of left and right body-wall muscles per segment
+    fn render_muscles(&self, frame: &mut Frame, area: Rect) {
+        let [dorsal, ventral] =
+            Layout::vertical([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).areas(area);
+        for (title, quadrants, area, colour) in [
+            (" dorsal muscles ", ["MDL", "MDR"], dorsal, Color::Cyan),
+            (" ventral muscles ", ["MVL", "MVR"], ventral, Color::Magenta),
+        ] {
+            let [left, right] = quadrants.map(body_wall_quadrant);
+            let mut values = vec![0i16; left.len().max(right.len())];
+            let mut segment = vec![0u64; values.len()];
+            for ids in [&left, &right] {
+                self.connectome.muscle_query(ids, &mut values[..ids.len()]);
+                for (s, &v) in segment.iter_mut().zip(&values[..ids.len()]) {
+                    *s += v.max(0) as u64;
+                }
+            }
+            let bars: Vec<Bar> = segment
+                .iter()
+                .enumerate()
+                .map(|(k, &v)| Bar::new(v).label(format!("{}", k + 1)))
+                .collect();
+            frame.render_widget(
+                BarChart::new(bars)
+                    .block(Block::bordered().title(title))
+                    .bar_width(2)
+                    .bar_gap(1)
+                    .bar_style(Style::new().fg(colour)),
+                area,
+            );
+        }
+    }
+}
+
+/// Short description of a stimulus: its cell names, truncated
+fn describe(ids: &[u16]) -> String {
+    if ids.is_empty() {
+        return "none".to_string();
+    }
+    let names: Vec<String> = ids.iter().take(6).map(|&id| cell_name(id)).collect();
+    match ids.len() {
+        n if n > 6 => format!("{} +{}", names.join(" "), n - 6),
+        _ => names.join(" "),
+    }
+}
+
+/// Run `dashboard` in the terminal until the user quits. Uses only the
+/// terminal itself, so it works over SSH.
+pub fn run_dashboard(mut dashboard: Dashboard) -> Result<(), String> {
+    let mut terminal = ratatui::init();
+    let result = (|| -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| dashboard.render(frame))?;
+            if event::poll(dashboard.params.tick)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && !dashboard.handle_key(key.code)
+            {
+                return Ok(());
+            }
+            if !dashboard.paused {
+                for _ in 0..dashboard.cycles_per_tick {
+                    dashboard.advance();
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    result.map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::preset;
+
+    fn dashboard(protocol: &str) -> Dashboard {
+        let protocol = Protocol::parse(protocol).unwrap();
+        let params = DashboardParams {
+            inject_cycles: 3,
+            ..DashboardParams::default()
+        };
+        Dashboard::new(preset::connectome(), protocol, params)
+    }
+
+    #[test]
+    fn steps_through_the_protocol() {
+        let mut dashboard = dashboard("burn 0\nstimulate 3 nose_touch\nrest 2\n");
+        assert_eq!(dashboard.step, 1);
+        assert_eq!(dashboard.protocol_stimulus(), NOSE_TOUCH);
+        for _ in 0..3 {
+            dashboard.advance();
+        }
+        assert!(dashboard.protocol_stimulus().is_empty());
+        assert!(!dashboard.finished());
+        dashboard.advance();
+        dashboard.advance();
+        assert!(dashboard.finished());
+        // keeps running past the end
+        dashboard.advance();
+        assert_eq!(dashboard.raster.len(), 6);
+
+        assert!(self::dashboard("threshold 10\nburn 0\n").finished());
+    }
+
+    #[test]
+    fn keys_pause_step_inject_and_quit() {
+        let mut dashboard = dashboard("rest 100\n");
+        assert!(dashboard.handle_key(KeyCode::Char(' ')));
+        assert!(dashboard.paused());
+        dashboard.handle_key(KeyCode::Char(' '));
+        dashboard.handle_key(KeyCode::Right);
+        assert!(dashboard.paused());
+        assert_eq!(dashboard.raster.len(), 1);
+
+        dashboard.handle_key(KeyCode::Char('c'));
+        for _ in 0..2 {
+            dashboard.advance();
+        }
+        assert_eq!(dashboard.injection.as_ref().unwrap().remaining, 1);
+        dashboard.advance();
+        assert!(dashboard.injection.is_none());
+        dashboard.handle_key(KeyCode::Char('t'));
+        dashboard.handle_key(KeyCode::Char('x'));
+        assert!(dashboard.injection.is_none());
+
+        for _ in 0..10 {
+            dashboard.handle_key(KeyCode::Char('+'));
+        }
+        assert_eq!(dashboard.cycles_per_tick, 256);
+        for _ in 0..10 {
+            dashboard.handle_key(KeyCode::Char('-'));
+        }
+        assert_eq!(dashboard.cycles_per_tick, 1);
+        assert!(!dashboard.handle_key(KeyCode::Char('q')));
+        assert!(!dashboard.handle_key(KeyCode::Esc));
+    }
+
+    #[test]
+    fn locomotion_reads_the_recent_raster() {
+        let mut dashboard = dashboard("rest 1\n");
+        assert_eq!(dashboard.locomotion(), Locomotion::Stationary);
+        let (a, b) = (MOTOR_NEURON_A.len(), MOTOR_NEURON_B.len());
+        let row = |a_on, b_on| [vec![a_on; a], vec![b_on; b]].concat();
+        dashboard.raster.extend((0..100).map(|_| row(1, 0)));
+        assert_eq!(dashboard.locomotion(), Locomotion::Backward);
+        // only the last `window` cycles count
+        dashboard.raster.extend((0..50).map(|_| row(0, 1)));
+        assert_eq!(dashboard.locomotion(), Locomotion::Forward);
+    }
+
+    #[test]
+    fn describes_stimuli_by_name() {
+        assert_eq!(describe(&[]), "none");
+        let ids = preset::cell_registry().resolve(&["AVAL", "AVAR"]).unwrap();
+        assert_eq!(describe(&ids), "AVAL AVAR");
+        let many: Vec<u16> = (0..8).collect();
+        assert!(describe(&many).ends_with(" +2"));
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::connectome::{Connectome, Edge, rom_image::build_rom};
+use crate::emulations::c_elegans::csv_tables::{
+    CONNECTOME_CSV, MUSCLE_CSV, NEURONS_TOT, row_weight,
+};
+use crate::emulations::c_elegans::neuron_ids::{CELLS, NeuronId, cell_name};
+use crate::emulations::c_elegans::preset;
+
+/// Name of the dataset bundled as `CElegansNeuronTables`
+pub const BUNDLED: &str = "bundled";
+
+/// Column layout of one connectivity table, columns named by their header.
+///
+/// ```toml
+/// [[table]]
+/// path = "white1986_adult.tsv"
+/// delimiter = "\t"
+/// pre = "Neuron 1"
+/// post = "Neuron 2"
+/// count = "Nbr"
+/// transmitter = ""        # no transmitter column: every edge excitatory
+/// kind = "Type"
+/// exclude_kinds = ["EJ"]  # e.g. leave out gap junctions
+/// gap_junction_kinds = ["EJ"]
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableSchema {
+    pub path: PathBuf,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: String,
+    #[serde(default = "default_pre")]
+    pub pre: String,
+    #[serde(default = "default_post")]
+    pub post: String,
+    #[serde(default = "default_count")]
+    pub count: String,
+    /// Column whose value `GABA` makes an edge inhibitory; empty for none
+    #[serde(default = "default_transmitter")]
+    pub transmitter: String,
+    /// Column holding the connection type, needed for `exclude_kinds`
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub exclude_kinds: Vec<String>,
+    /// Connection types that are gap junctions; such rows are wired like
+    /// any other and also listed in `Dataset::gap_junctions`
+    #[serde(default = "default_gap_junction_kinds")]
+    pub gap_junction_kinds: Vec<String>,
+}
+
+fn default_delimiter() -> String {
+    ",".to_string()
+}
+
+fn default_pre() -> String {
+    "Origin".to_string()
+}
+
+fn default_post() -> String {
+    "Target".to_string()
+}
+
+fn default_count() -> String {
+    "Number of Connections".to_string()
+}
+
+fn default_transmitter() -> String {
+    "Neurotransmitter".to_string()
+}
+
+fn default_gap_junction_kinds() -> Vec<String> {
+    vec!["GapJunction".to_string()]
+}
+
+/// A connectome reconstruction described as data, loaded from TOML.
+///
+/// ```toml
+/// name = "white1986_adult"
+/// version = "N2U"
+/// description = "Adult hermaphrodite, White et al. 1986"
+///
+/// [aliases]               # table name -> NeuronId name
+/// "AVAL(L)" = "AVAL"
+///
+/// [[table]]
+/// path = "white1986_adult.csv"
+/// ```
+///
+/// Table paths are resolved against the descriptor's directory. Where
+/// several rows join the same pair, the strongest one wins, as in the
+/// built-in ROM.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetSpec {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    pub table: Vec<TableSchema>,
+}
+
+/// How well a dataset's cell names matched `NeuronId`
+#[derive(Clone, Debug, Default)]
+pub struct MappingReport {
+    pub rows: usize,
+    /// Rows skipped by `exclude_kinds`
+    pub excluded: usize,
+    /// Rows naming a cell that could not be mapped, which are skipped
+    pub unmapped_rows: usize,
+    /// Names that could not be mapped, with the rows they appear in
+    pub unmapped: BTreeMap<String, usize>,
+    /// Names mapped other than verbatim, with the cell they became
+    pub renamed: BTreeMap<String, String>,
+    /// Cells the dataset never mentions
+    pub missing: Vec<u16>,
+}
+
+impl fmt::Display for MappingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: Vec<String>| match items.len() {
+            0 => "0".to_string(),
+            n => format!("{} {}", n, items.join(" ")),
+        };
+        writeln!(f, "rows               {}", self.rows)?;
+        writeln!(f, "  excluded         {}", self.excluded)?;
+        writeln!(f, "  unmapped         {}", self.unmapped_rows)?;
+        let renamed = self
+            .renamed
+            .iter()
+            .map(|(from, to)| format!("{}={}", from, to))
+            .collect();
+        writeln!(f, "renamed cells      {}", list(renamed))?;
+        let unmapped = self
+            .unmapped
+            .iter()
+            .map(|(name, rows)| format!("{}({})", name, rows))
+            .collect();
+        writeln!(f, "unmapped names     {}", list(unmapped))?;
+        let missing = self.missing.iter().map(|&id| cell_name(id)).collect();
+        write!(f, "missing cells      {}", list(missing))
+    }
+}
+
+/// A dataset's wiring with its provenance
+#[derive(Clone, Debug)]
+pub struct Dataset {
+    pub name: String,
+    pub version: String,
+    pub edges: Vec<Edge>,
+    /// One edge per gap-junction row, the connection count as weight; the
+    /// tables usually list both directions
+    pub gap_junctions: Vec<Edge>,
+    pub report: MappingReport,
+}
+
+impl Dataset {
+    /// A dataset without tables, naming a wiring loaded some other way
+    /// (the built-in ROM, a ROM image, a CSV file)
+    pub fn stand_in(name: impl Into<String>, version: &str) -> Self {
+        Self {
+            name: name.into(),
+            version: version.to_string(),
+            edges: vec![],
+            gap_junctions: vec![],
+            report: MappingReport::default(),
+        }
+    }
+
+    pub fn connectome(&self) -> Result<Connectome, String> {
+        preset::with_names(Connectome::from_rom(
+            build_rom(NEURONS_TOT, &self.edges)?,
+            CELLS,
+        )?)
+    }
+}
+
+/// Map a cell name as spelled in some reconstruction onto `NeuronId`:
+/// through `aliases` first, then verbatim, uppercased without spaces, with
+/// the trailing number unpadded (VA01) or padded (MDL1), and with body-wall
+/// muscles written as `dBWML1` / `vBWMR24`.
+pub fn map_name(name: &str, aliases: &BTreeMap<String, String>) -> Option<NeuronId> {
+    let name = name.trim();
+    if let Some(alias) = aliases.get(name) {
+        return NeuronId::from_name(alias);
+    }
+    if let Some(id) = NeuronId::from_name(name) {
+        return Some(id);
+    }
+    let compact: String = name.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = compact.to_uppercase();
+    let digits = upper.len() - upper.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (stem, number) = upper.split_at(upper.len() - digits);
+    let mut candidates = vec![upper.clone()];
+    if let Ok(n) = number.parse::<u32>() {
+        candidates.push(format!("{}{}", stem, n));
+        candidates.push(format!("{}{:02}", stem, n));
+        if let Some(side) = stem
+            .strip_prefix("DBWM")
+            .map(|side| ("MD", side))
+            .or_else(|| stem.strip_prefix("VBWM").map(|side| ("MV", side)))
+        {
+            candidates.push(format!("{}{}{:02}", side.0, side.1, n));
+        }
+    }
+    candidates.iter().find_map(|c| NeuronId::from_name(c))
+}
+
+fn read_table(
+    csv: &[u8],
+    schema: &TableSchema,
+    aliases: &BTreeMap<String, String>,
+    edges: &mut BTreeMap<(u16, u16), i8>,
+    gap_junctions: &mut Vec<Edge>,
+    report: &mut MappingReport,
+    seen: &mut BTreeSet<u16>,
+) -> Result<(), String> {
+    let delimiter = match schema.delimiter.as_str() {
+        "\\t" | "\t" => b'\t',
+        d if d.len() == 1 => d.as_bytes()[0],
+        d => return Err(format!("Delimiter {:?} is not a single character", d)),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(csv);
+    let headers = reader.headers().map_err(|err| err.to_string())?.clone();
+    let column = |name: &str| -> Result<Option<usize>, String> {
+        if name.is_empty() {
+            return Ok(None);
+        }
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .map(Some)
+            .ok_or_else(|| format!("No column {}", name))
+    };
+    let pre = column(&schema.pre)?.ok_or("The pre column needs a name")?;
+    let post = column(&schema.post)?.ok_or("The post column needs a name")?;
+    let count = column(&schema.count)?.ok_or("The count column needs a name")?;
+    let transmitter = column(&schema.transmitter)?;
+    let kind = column(&schema.kind)?;
+    if kind.is_none() && !schema.exclude_kinds.is_empty() {
+        return Err("exclude_kinds needs a kind column".to_string());
+    }
+
+    for (n, record) in reader.records().enumerate() {
+        let record = record.map_err(|err| err.to_string())?;
+        let field = |i: Option<usize>| i.and_then(|i| record.get(i)).unwrap_or("").trim();
+        report.rows += 1;
+        if schema.exclude_kinds.iter().any(|k| k == field(kind)) {
+            report.excluded += 1;
+            continue;
+        }
+        let mut ends = [0u16; 2];
+        let mut mapped = true;
+        for (end, column) in ends.iter_mut().zip([pre, post]) {
+            let name = field(Some(column));
+            match map_name(name, aliases) {
+                Some(id) => {
+                    if id.name() != name {
+                        report.renamed.insert(name.to_string(), id.name());
+                    }
+                    *end = id as u16;
+                }
+                None => {
+                    *report.unmapped.entry(name.to_string()).or_insert(0) += 1;
+                    mapped = false;
+                }
+            }
+        }
+        if !mapped {
+            report.unmapped_rows += 1;
+            continue;
+        }
+        seen.extend(ends);
+        if ends[0] >= NEURONS_TOT {
+            return Err(format!(
+                "row {}: {} is a muscle and cannot be presynaptic",
+                n + 2,
+                cell_name(ends[0])
+            ));
+        }
+        let weight = row_weight(field(Some(count)), field(transmitter))
+            .map_err(|err| format!("row {}: {}", n + 2, err))?;
+        if schema.gap_junction_kinds.iter().any(|k| k == field(kind)) {
+            gap_junctions.push(Edge {
+                pre: ends[0],
+                post: ends[1],
+                weight: row_weight(field(Some(count)), "")
+                    .map_err(|err| format!("row {}: {}", n + 2, err))?,
+            });
+        }
+        let entry = edges.entry((ends[0], ends[1])).or_insert(0);
+        if weight.unsigned_abs() > entry.unsigned_abs() {
+            *entry = weight;
+        }
+    }
+    Ok(())
+}
+
+impl DatasetSpec {
+    /// Parse a dataset descriptor from TOML
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    /// The bundled `CElegansNeuronTables`: synapses and gap junctions from
+    /// `Connectome.csv` and neuromuscular junctions from `NeuronsToMuscle.csv`
+    pub fn bundled() -> Self {
+        Self {
+            name: BUNDLED.to_string(),
+            version: "CElegansNeuronTables".to_string(),
+            description: "OpenWorm CElegansNeuronTables bundled with the crate".to_string(),
+            aliases: BTreeMap::new(),
+            table: vec![
+                TableSchema {
+                    path: PathBuf::from("Connectome.csv"),
+                    delimiter: default_delimiter(),
+                    pre: default_pre(),
+                    post: default_post(),
+                    count: default_count(),
+                    transmitter: default_transmitter(),
+                    kind: "Type".to_string(),
+                    exclude_kinds: vec![],
+                    gap_junction_kinds: default_gap_junction_kinds(),
+                },
+                TableSchema {
+                    path: PathBuf::from("NeuronsToMuscle.csv"),
+                    delimiter: default_delimiter(),
+                    pre: "Neuron".to_string(),
+                    post: "Muscle".to_string(),
+                    count: default_count(),
+                    transmitter: default_transmitter(),
+                    kind: String::new(),
+                    exclude_kinds: vec![],
+                    gap_junction_kinds: vec![],
+                },
+            ],
+        }
+    }
+
+    /// Load the tables, resolving their paths against `base`. The bundled
+    /// dataset reads its tables from the binary.
+    pub fn load(&self, base: &Path) -> Result<Dataset, String> {
+        let mut edges = BTreeMap::new();
+        let mut gap_junctions = vec![];
+        let mut report = MappingReport::default();
+        let mut seen = BTreeSet::new();
+        for schema in &self.table {
+            let bundled = match (self.name.as_str(), schema.path.to_str()) {
+                (BUNDLED, Some("Connectome.csv")) => Some(CONNECTOME_CSV),
+                (BUNDLED, Some("NeuronsToMuscle.csv")) => Some(MUSCLE_CSV),
+                _ => None,
+            };
+            let path = base.join(&schema.path);
+            let text = match bundled {
+                Some(text) => text.to_string(),
+                None => std::fs::read_to_string(&path)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?,
+            };
+            read_table(
+                text.as_bytes(),
+                schema,
+                &self.aliases,
+                &mut edges,
+                &mut gap_junctions,
+                &mut report,
+                &mut seen,
+            )
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        report.missing = (0..CELLS).filter(|id| !seen.contains(id)).collect();
+        Ok(Dataset {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            edges: edges
+                .into_iter()
+                .map(|((pre, post), weight)| Edge { pre, post, weight })
+                .collect(),
+            gap_junctions,
+            report,
+        })
+    }
+}
+
+/// Known datasets by name: the bundled tables plus any descriptors added
+/// from TOML files.
+#[derive(Clone, Debug)]
+pub struct DatasetRegistry {
+    /// Each descriptor with the directory its table paths are relative to
+    entries: BTreeMap<String, (DatasetSpec, PathBuf)>,
+}
+
+impl Default for DatasetRegistry {
+    fn default() -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(
+            BUNDLED.to_string(),
+            (DatasetSpec::bundled(), PathBuf::new()),
+        );
+        Self { entries }
+    }
+}
+
+impl DatasetRegistry {
+    /// Register the descriptor in `path`; a later one replaces an earlier one
+    /// of the same name.
+    pub fn add_file(&mut self, path: &Path) -> Result<&DatasetSpec, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let spec =
+            DatasetSpec::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let base = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let name = spec.name.clone();
+        self.entries.insert(name.clone(), (spec, base));
+        Ok(&self.entries[&name].0)
+    }
+
+    /// Register every `*.toml` descriptor in `dir`
+    pub fn add_dir(&mut self, dir: &Path) -> Result<(), String> {
+        let entries =
+            std::fs::read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            self.add_file(&path)?;
+        }
+        Ok(())
+    }
+
+    pub fn specs(&self) -> impl Iterator<Item = &DatasetSpec> {
+        self.entries.values().map(|(spec, _)| spec)
+    }
+
+    /// Load a registered dataset, or the descriptor at path `name` if no
+    /// dataset is registered under that name.
+    pub fn load(&self, name: &str) -> Result<Dataset, String> {
+        match self.entries.get(name) {
+            Some((spec, base)) => spec.load(base),
+            None => {
+                let mut registry = self.clone();
+                let spec = registry
+                    .add_file(Path::new(name))
+                    .map_err(|err| format!("No dataset {} ({})", name, err))?
+                    .clone();
+                spec.load(Path::new(name).parent().unwrap_or(Path::new(".")))
+            }
+        }
+    }
+}
+
+/// Edges that differ between two datasets
+#[derive(Clone, Debug, Default)]
+pub struct DatasetDiff {
+    pub only_a: Vec<Edge>,
+    pub only_b: Vec<Edge>,
+    /// (pre, post, weight in a, weight in b) where both have the edge
+    pub changed: Vec<(u16, u16, i8, i8)>,
+    pub shared: usize,
+}
+
+impl DatasetDiff {
+    pub fn new(a: &[Edge], b: &[Edge]) -> Self {
+        let index = |edges: &[Edge]| -> BTreeMap<(u16, u16), i8> {
+            edges.iter().map(|e| ((e.pre, e.post), e.weight)).collect()
+        };
+        let (a, b) = (index(a), index(b));
+        let mut diff = Self::default();
+        for (&(pre, post), &wa) in &a {
+            match b.get(&(pre, post)) {
+                None => diff.only_a.push(Edge {
+                    pre,
+                    post,
+                    weight: wa,
+                }),
+                Some(&wb) if wb != wa => diff.changed.push((pre, post, wa, wb)),
+                Some(_) => diff.shared += 1,
+            }
+        }
+        for (&(pre, post), &wb) in &b {
+            if !a.contains_key(&(pre, post)) {
+                diff.only_b.push(Edge {
+                    pre,
+                    post,
+                    weight: wb,
+                });
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::csv_tables::{load_csv_edges, write_csv_edges};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("neuro_rust_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn map_name_tries_aliases_and_spellings() {
+        let aliases = BTreeMap::from([("AVA left".to_string(), "AVAL".to_string())]);
+        let map = |name| map_name(name, &aliases).map(|id| id.name());
+        assert_eq!(map("AVA left").as_deref(), Some("AVAL"));
+        assert_eq!(map(" AVAR ").as_deref(), Some("AVAR"));
+        assert_eq!(map("va01").as_deref(), Some("VA1"));
+        assert_eq!(map("dBWML1").as_deref(), Some("MDL01"));
+        assert_eq!(map("vBWMR24").as_deref(), Some("MVR24"));
+        assert_eq!(map("NOTACELL"), None);
+    }
+
+    #[test]
+    fn bundled_dataset_reads_the_shipped_tables() {
+        let dataset = DatasetRegistry::default().load(BUNDLED).unwrap();
+        assert_eq!(dataset.version, "CElegansNeuronTables");
+        assert!(dataset.report.unmapped.is_empty());
+        let tables = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/emulations/c_elegans/CElegansNeuronTables");
+        let from_files = load_csv_edges(
+            &tables.join("Connectome.csv"),
+            Some(&tables.join("NeuronsToMuscle.csv")),
+        )
+        .unwrap();
+        assert_eq!(dataset.edges, from_files);
+    }
+
+    #[test]
+    fn descriptor_maps_excludes_and_reports() {
+        let dir = temp_dir("dataset");
+        std::fs::write(
+            dir.join("table.tsv"),
+            "A\tB\tN\tT\nAVA left\tAVAR\t3\tChem\nAVAR\tva01\t70\tChem\n\
+             AVAR\tAVAL\t2\tEJ\nXYZ\tAVAL\t1\tChem\nAVAL\tAVAR\t5\tChem\n",
+        )
+        .unwrap();
+        let path = dir.join("small.toml");
+        std::fs::write(
+            &path,
+            "name = \"small\"\nversion = \"v1\"\n\n[aliases]\n\"AVA left\" = \"AVAL\"\n\n\
+             [[table]]\npath = \"table.tsv\"\ndelimiter = \"\\t\"\npre = \"A\"\npost = \"B\"\n\
+             count = \"N\"\ntransmitter = \"\"\nkind = \"T\"\nexclude_kinds = [\"EJ\"]\n",
+        )
+        .unwrap();
+        let mut registry = DatasetRegistry::default();
+        registry.add_file(&path).unwrap();
+        let dataset = registry.load("small").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let id = |name: &str| NeuronId::from_name(name).unwrap() as u16;
+        let edge = |pre, post, weight| Edge {
+            pre: id(pre),
+            post: id(post),
+            weight,
+        };
+        // the strongest of the two AVAL -> AVAR rows wins, 70 is clamped
+        assert_eq!(
+            dataset.edges,
+            vec![edge("AVAL", "AVAR", 5), edge("AVAR", "VA1", 63)]
+        );
+        let report = &dataset.report;
+        assert_eq!(
+            (report.rows, report.excluded, report.unmapped_rows),
+            (5, 1, 1)
+        );
+        assert_eq!(report.unmapped.get("XYZ"), Some(&1));
+        assert_eq!(report.renamed.get("va01").map(|s| s.as_str()), Some("VA1"));
+        assert_eq!(report.missing.len(), CELLS as usize - 3);
+    }
+
+    #[test]
+    fn shipped_descriptors_load() {
+        let mut registry = DatasetRegistry::default();
+        registry
+            .add_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("datasets"))
+            .unwrap();
+        let chemical = registry.load("chemical_synapses").unwrap();
+        let bundled = registry.load(BUNDLED).unwrap();
+        assert!(chemical.report.unmapped.is_empty());
+        assert!(chemical.report.excluded > 0);
+        assert!(chemical.edges.len() < bundled.edges.len());
+        assert!(chemical.gap_junctions.is_empty());
+        assert_eq!(bundled.gap_junctions.len(), 1084);
+    }
+
+    #[test]
+    fn csv_edges_round_trip() {
+        let mut edges = preset::connectome().edges();
+        edges.sort_by_key(|e| (e.pre, e.post));
+        let dir = temp_dir("csv_edges");
+        let path = dir.join("wiring.csv");
+        write_csv_edges(std::fs::File::create(&path).unwrap(), &edges).unwrap();
+        let read = load_csv_edges(&path, None);
+        std::fs::write(&path, "Origin,Target,Type,Number of Connections,Neurotransmitter\nAVAL,NOTACELL,Send,1,GABA\n").unwrap();
+        let unknown = load_csv_edges(&path, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read.unwrap(), edges);
+        assert!(unknown.unwrap_err().contains("NOTACELL"));
+    }
+
+    #[test]
+    fn diff_splits_edges() {
+        let edge = |pre, post, weight| Edge { pre, post, weight };
+        let a = [edge(0, 1, 2), edge(1, 2, 3), edge(2, 3, 4)];
+        let b = [edge(0, 1, 2), edge(1, 2, -3), edge(3, 4, 1)];
+        let diff = DatasetDiff::new(&a, &b);
+        assert_eq!(diff.shared, 1);
+        assert_eq!(diff.only_a, vec![edge(2, 3, 4)]);
+        assert_eq!(diff.only_b, vec![edge(3, 4, 1)]);
+        assert_eq!(diff.changed, vec![(1, 2, 3, -3)]);
+    }
+}
@@ -0,0 +1,534 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use plotly::common::Mode;
+use plotly::{Plot, Scatter};
+use serde::{Deserialize, Serialize};
+
+use crate::connectome::Connectome;
+use crate::connectome::decay::DecayPolicy;
+use crate::connectome::null_model::NullModel;
+use crate::connectome::observer::SaturationCounter;
+use crate::connectome::rom_image::{build_rom, read_rom_file};
+use crate::connectome::update_mode::UpdateMode;
+use crate::emulations::c_elegans::behaviour::BehaviourSummary;
+use crate::emulations::c_elegans::csv_tables::{
+    builtin_neuromodulation, connectome_from_csv, neuromodulation_from_csv,
+};
+use crate::emulations::c_elegans::dataset::{Dataset, DatasetRegistry};
+use crate::emulations::c_elegans::neuron_groups::{
+    MOTOR_NEURON_A, MOTOR_NEURON_B, resolve_targets,
+};
+use crate::emulations::c_elegans::neuron_ids::{CELLS, cell_name};
+use crate::emulations::c_elegans::preset;
+use crate::emulations::c_elegans::protocol::{Protocol, ProtocolStep};
+use crate::emulations::c_elegans::trace::DischargeTrace;
+
+/// An experiment described as data, loaded from TOML.
+///
+/// ```toml
+/// name = "chemotaxis_then_touch"
+/// seed = 0
+/// output_dir = "runs"
+/// lesions = ["AVAL", "AVAR"]
+///
+/// [connectome]            # built-in ROM when empty
+/// csv = "CElegansNeuronTables/Connectome.csv"
+/// muscle_csv = "CElegansNeuronTables/NeuronsToMuscle.csv"
+/// # or: dataset = "bundled" (or a dataset descriptor, see `DatasetSpec`)
+/// null_model = "rewire"   # optional control wiring, randomised with `seed`
+///
+/// [engine]
+/// threshold = 40
+/// idle_limit = 100
+/// mode = "sequential"
+///
+/// [burn_in]
+/// cycles = 1000
+/// targets = ["chemotaxis"]
+///
+/// [[stimulus]]
+/// cycles = 1000
+/// targets = ["nose_touch"]
+///
+/// [[modulator]]          # optional; only listed modulators are active
+/// name = "serotonin"
+/// tonic = 0.5             # e.g. on food
+///
+/// [[recorder]]
+/// name = "motor_ab"
+/// targets = ["motor_ab"]
+/// plot = true
+/// ```
+///
+/// Relative paths are resolved against the spec file's directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentSpec {
+    pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "default_output_dir")]
+    pub output_dir: PathBuf,
+    #[serde(default)]
+    pub connectome: ConnectomeSource,
+    #[serde(default)]
+    pub engine: EngineSpec,
+    #[serde(default)]
+    pub lesions: Vec<String>,
+    pub burn_in: Option<StimulusBlock>,
+    #[serde(default)]
+    pub stimulus: Vec<StimulusBlock>,
+    #[serde(default)]
+    pub modulator: Vec<ModulatorSpec>,
+    #[serde(default)]
+    pub recorder: Vec<RecorderSpec>,
+}
+
+fn default_output_dir() -> PathBuf {
+    PathBuf::from("runs")
+}
+
+/// Where the wiring comes from; the built-in ROM if none is set.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectomeSource {
+    pub rom: Option<PathBuf>,
+    pub csv: Option<PathBuf>,
+    pub muscle_csv: Option<PathBuf>,
+    /// A registered dataset name or the path of a dataset descriptor
+    pub dataset: Option<String>,
+    /// Replace the wiring by a null model of it (`rewire`, `rewire:<swaps
+    /// per edge>` or `erdos_renyi`), seeded with the spec seed
+    pub null_model: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EngineSpec {
+    #[serde(default = "default_threshold")]
+    pub threshold: i8,
+    /// Idle cycles before a neuron's state is reset; 0 keeps state forever
+    #[serde(default = "default_idle_limit")]
+    pub idle_limit: u8,
+    /// sequential, synchronous, random or event:<delay>; random uses the spec seed
+    #[serde(default = "default_mode")]
+    pub mode: String,
+}
+
+fn default_threshold() -> i8 {
+    40
+}
+
+fn default_idle_limit() -> u8 {
+    100
+}
+
+fn default_mode() -> String {
+    "sequential".to_string()
+}
+
+impl Default for EngineSpec {
+    fn default() -> Self {
+        Self {
+            threshold: default_threshold(),
+            idle_limit: default_idle_limit(),
+            mode: default_mode(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StimulusBlock {
+    pub cycles: usize,
+    /// Cells, groups or modalities pinged every cycle; none means rest
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+/// A volume-transmission channel (serotonin, dopamine, octopamine or
+/// fmrfamide); unset fields keep the built-in values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModulatorSpec {
+    pub name: String,
+    #[serde(default)]
+    pub tonic: f32,
+    pub release: Option<f32>,
+    pub tau: Option<f32>,
+    pub threshold_shift: Option<f32>,
+    pub gain_shift: Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecorderSpec {
+    pub name: String,
+    pub targets: Vec<String>,
+    /// Also write an HTML raster plot
+    #[serde(default)]
+    pub plot: bool,
+}
+
+/// Summary written next to the results as `run.toml`.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunManifest {
+    pub name: String,
+    pub seed: u64,
+    pub version: String,
+    /// Connectome reconstruction the wiring was loaded from
+    pub dataset: String,
+    pub dataset_version: String,
+    /// Null model the wiring was replaced by, if any
+    pub null_model: Option<String>,
+    pub recorded_cycles: usize,
+    pub forward: f64,
+    pub backward: f64,
+    pub stationary: f64,
+    /// Neuron states clipped to the i8 range over the whole run
+    pub saturations: usize,
+    pub recorders: Vec<String>,
+}
+
+impl ExperimentSpec {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    /// The engine's update mode; a bare `random` is seeded with `seed`.
+    pub fn update_mode(&self) -> Result<UpdateMode, String> {
+        match self.engine.mode.as_str() {
+            "random" => Ok(UpdateMode::RandomAsync { seed: self.seed }),
+            mode => mode.parse(),
+        }
+    }
+
+    /// Build the configured connectome, resolving paths against `base`.
+    pub fn build_connectome(&self, base: &Path) -> Result<Connectome, String> {
+        self.load_connectome(base).map(|(connectome, _)| connectome)
+    }
+
+    /// The wiring's source as a dataset: the one named in the spec, or a
+    /// stand-in named after the ROM or CSV file (the built-in ROM if none).
+    fn dataset(&self, base: &Path) -> Result<Dataset, String> {
+        let source = &self.connectome;
+        match (&source.rom, &source.csv, &source.dataset) {
+            (None, None, Some(name)) => {
+                let registry = DatasetRegistry::default();
+                if registry.specs().any(|spec| &spec.name == name) {
+                    registry.load(name)
+                } else {
+                    registry.load(&base.join(name).to_string_lossy())
+                }
+            }
+            (None, None, None) => Ok(Dataset::stand_in("builtin_rom", env!("CARGO_PKG_VERSION"))),
+            (Some(rom), None, None) => {
+                Ok(Dataset::stand_in(rom.display().to_string(), "unversioned"))
+            }
+            (None, Some(csv), None) => {
+                Ok(Dataset::stand_in(csv.display().to_string(), "unversioned"))
+            }
+            _ => Err(
+                "Set only one of connectome.rom, connectome.csv and connectome.dataset".to_string(),
+            ),
+        }
+    }
+
+    fn load_connectome(&self, base: &Path) -> Result<(Connectome, Dataset), String> {
+        let source = &self.connectome;
+        let dataset = self.dataset(base)?;
+        let mut connectome = match (&source.rom, &source.csv, &source.dataset) {
+            (Some(rom), _, _) => preset::with_names(Connectome::from_rom(
+                read_rom_file(&base.join(rom))?,
+                CELLS,
+            )?)?,
+            (_, Some(csv), _) => connectome_from_csv(
+                &base.join(csv),
+                source.muscle_csv.as_ref().map(|p| base.join(p)).as_deref(),
+            )?,
+            (_, _, Some(_)) => dataset.connectome()?,
+            (None, None, None) => preset::connectome(),
+        };
+        if let Some(model) = &source.null_model {
+            let model: NullModel = model.parse()?;
+            let edges = model.generate(
+                connectome.neurons_tot(),
+                CELLS,
+                &connectome.edges(),
+                self.seed,
+            )?;
+            connectome = preset::with_names(Connectome::from_rom(
+                build_rom(connectome.neurons_tot(), &edges)?,
+                CELLS,
+            )?)?;
+        }
+        if !self.modulator.is_empty() {
+            let mut modulation = match &source.csv {
+                Some(csv) => neuromodulation_from_csv(&base.join(csv))?,
+                None => builtin_neuromodulation(),
+            };
+            modulation.retain(|c| self.modulator.iter().any(|m| m.name == c.name));
+            for spec in &self.modulator {
+                let channel = modulation
+                    .channel_mut(&spec.name)
+                    .ok_or(format!("Unknown modulator {}", spec.name))?;
+                channel.tonic = spec.tonic;
+                channel.release = spec.release.unwrap_or(channel.release);
+                channel.tau = spec.tau.unwrap_or(channel.tau);
+                channel.threshold_shift = spec.threshold_shift.unwrap_or(channel.threshold_shift);
+                channel.gain_shift = spec.gain_shift.unwrap_or(channel.gain_shift);
+            }
+            modulation.refresh();
+            connectome.set_neuromodulation(Some(modulation));
+        }
+        connectome.set_update_mode(self.update_mode()?);
+        connectome.set_decay_policy(match self.engine.idle_limit {
+            0 => DecayPolicy::Persist,
+            max_idle => DecayPolicy::IdleReset { max_idle },
+        })?;
+        Ok((connectome, dataset))
+    }
+
+    /// The stimulus schedule as a protocol
+    pub fn protocol(&self) -> Result<Protocol, String> {
+        let mut steps = vec![
+            ProtocolStep::Threshold(self.engine.threshold),
+            ProtocolStep::Ablate(resolve_targets(&self.lesions)?),
+        ];
+        if let Some(burn_in) = &self.burn_in {
+            steps.push(ProtocolStep::Run {
+                cycles: burn_in.cycles,
+                stimulus: resolve_targets(&burn_in.targets)?,
+                record: false,
+            });
+        }
+        for block in &self.stimulus {
+            steps.push(ProtocolStep::Run {
+                cycles: block.cycles,
+                stimulus: resolve_targets(&block.targets)?,
+                record: true,
+            });
+        }
+        Ok(Protocol { steps })
+    }
+
+    /// Run the experiment and write spec, manifest, traces and plots into
+    /// `<output_dir>/<name>-seed<seed>`. `spec_text` is copied verbatim.
+    pub fn run(&self, base: &Path, spec_text: &str) -> Result<PathBuf, String> {
+        let dir = base
+            .join(&self.output_dir)
+            .join(format!("{}-seed{}", self.name, self.seed));
+        fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        let write = |file: &str, contents: &str| {
+            let path = dir.join(file);
+            fs::write(&path, contents).map_err(|err| format!("{}: {}", path.display(), err))
+        };
+        write("spec.toml", spec_text)?;
+
+        let (mut connectome, dataset) = self.load_connectome(base)?;
+        if self.connectome.dataset.is_some() {
+            write("dataset.txt", &format!("{}\n", dataset.report))?;
+        }
+        let protocol = self.protocol()?;
+        let saturation = connectome.subscribe(Box::<SaturationCounter>::default());
+        let mut traces = self
+            .recorder
+            .iter()
+            .map(|r| {
+                resolve_targets(&r.targets)
+                    .map(|ids| DischargeTrace::new(ids).with_dataset(&dataset))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut motor_a = DischargeTrace::new(MOTOR_NEURON_A.to_vec());
+        let mut motor_b = DischargeTrace::new(MOTOR_NEURON_B.to_vec());
+        protocol.run(&mut connectome, |c| {
+            traces.iter_mut().for_each(|t| t.record(c));
+            motor_a.record(c);
+            motor_b.record(c);
+            Ok(())
+        })?;
+
+        for (spec, trace) in self.recorder.iter().zip(traces.iter()) {
+            let mut out = vec![];
+            trace.write(&mut out).map_err(|err| err.to_string())?;
+            write(
+                &format!("{}.dat", spec.name),
+                &String::from_utf8_lossy(&out),
+            )?;
+            if spec.plot {
+                raster_plot(trace).write_html(dir.join(format!("{}.html", spec.name)));
+            }
+        }
+
+        let behaviour = BehaviourSummary::from_traces(&motor_a.rows, &motor_b.rows, 50, 0.2);
+        let manifest = RunManifest {
+            name: self.name.clone(),
+            seed: self.seed,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            dataset: dataset.name,
+            dataset_version: dataset.version,
+            null_model: self.connectome.null_model.clone(),
+            recorded_cycles: protocol.recorded_cycles(),
+            forward: behaviour.forward,
+            backward: behaviour.backward,
+            stationary: behaviour.stationary,
+            saturations: connectome
+                .observer::<SaturationCounter>(saturation)
+                .map_or(0, SaturationCounter::total),
+            recorders: self.recorder.iter().map(|r| r.name.clone()).collect(),
+        };
+        write(
+            "run.toml",
+            &toml::to_string(&manifest).map_err(|err| err.to_string())?,
+        )?;
+        Ok(dir)
+    }
+}
+
+/// Load a spec file and run it; relative paths in it are resolved against its directory.
+pub fn run_experiment(spec_path: &Path) -> Result<PathBuf, String> {
+    let text =
+        fs::read_to_string(spec_path).map_err(|err| format!("{}: {}", spec_path.display(), err))?;
+    let spec =
+        ExperimentSpec::parse(&text).map_err(|err| format!("{}: {}", spec_path.display(), err))?;
+    let base = spec_path.parent().unwrap_or(Path::new("."));
+    spec.run(base, &text)
+}
+
+/// One marker per discharge, cycles on x and recorded neurons on y
+fn raster_plot(trace: &DischargeTrace) -> Plot {
+    let mut plot = Plot::new();
+    for (column, &id) in trace.ids.iter().enumerate() {
+        let cycles: Vec<usize> = trace
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row[column] == 1)
+            .map(|(cycle, _)| cycle)
+            .collect();
+        let y = vec![column; cycles.len()];
+        plot.add_trace(
+            Scatter::new(cycles, y)
+                .mode(Mode::Markers)
+                .name(cell_name(id)),
+        );
+    }
+    plot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+name = "short"
+seed = 3
+output_dir = "out"
+lesions = ["AVAL"]
+
+[connectome]
+null_model = "rewire"
+
+[engine]
+mode = "random"
+
+[burn_in]
+cycles = 20
+targets = ["chemotaxis"]
+
+[[stimulus]]
+cycles = 30
+targets = ["nose_touch"]
+
+[[stimulus]]
+cycles = 10
+
+[[recorder]]
+name = "motor_ab"
+targets = ["motor_ab"]
+"#;
+
+    #[test]
+    fn shipped_specs_parse() {
+        let specs = Path::new(env!("CARGO_MANIFEST_DIR")).join("specs");
+        for name in ["chemotaxis_then_touch.toml", "ava_lesion_from_csv.toml"] {
+            let text = fs::read_to_string(specs.join(name)).unwrap();
+            let spec = ExperimentSpec::parse(&text).unwrap();
+            spec.protocol().unwrap();
+            spec.update_mode().unwrap();
+        }
+    }
+
+    #[test]
+    fn spec_becomes_a_protocol() {
+        let spec = ExperimentSpec::parse(SPEC).unwrap();
+        assert_eq!(spec.engine.threshold, 40);
+        assert_eq!(spec.engine.idle_limit, 100);
+        assert!(matches!(
+            spec.update_mode(),
+            Ok(UpdateMode::RandomAsync { seed: 3 })
+        ));
+        let protocol = spec.protocol().unwrap();
+        assert_eq!(protocol.recorded_cycles(), 40);
+        assert_eq!(protocol.steps.len(), 5);
+        assert!(matches!(protocol.steps[0], ProtocolStep::Threshold(40)));
+        assert!(matches!(
+            &protocol.steps[2],
+            ProtocolStep::Run {
+                cycles: 20,
+                record: false,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&protocol.steps[4], ProtocolStep::Run { stimulus, .. } if stimulus.is_empty())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        assert!(ExperimentSpec::parse("name = \"x\"\ncolour = 1\n").is_err());
+        let mut spec = ExperimentSpec::parse("name = \"x\"\n").unwrap();
+        spec.engine.mode = "sideways".to_string();
+        assert!(spec.update_mode().is_err());
+        spec.engine.mode = "random:9".to_string();
+        assert!(matches!(
+            spec.update_mode(),
+            Ok(UpdateMode::RandomAsync { seed: 9 })
+        ));
+        spec.connectome.rom = Some(PathBuf::from("rom.rs"));
+        spec.connectome.dataset = Some("bundled".to_string());
+        let err = spec.build_connectome(Path::new(".")).err().unwrap();
+        assert!(err.contains("only one"), "{}", err);
+    }
+
+    #[test]
+    fn a_seed_reproduces_the_run() {
+        let base =
+            std::env::temp_dir().join(format!("neuro_rust_experiment_{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let spec = ExperimentSpec::parse(SPEC).unwrap();
+        let read = |dir: &Path, file: &str| fs::read_to_string(dir.join(file)).unwrap();
+
+        let dir = spec.run(&base, SPEC).unwrap();
+        let (manifest, trace) = (read(&dir, "run.toml"), read(&dir, "motor_ab.dat"));
+        let again = spec.run(&base, SPEC).unwrap();
+        let outputs = (
+            read(&again, "spec.toml"),
+            read(&again, "run.toml"),
+            read(&again, "motor_ab.dat"),
+        );
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(dir, base.join("out").join("short-seed3"));
+        assert_eq!(outputs.0, SPEC);
+        assert_eq!(outputs.1, manifest);
+        assert_eq!(outputs.2, trace);
+        assert!(manifest.contains("dataset = \"builtin_rom\""));
+        assert!(manifest.contains("null_model = \"rewire\""));
+        assert!(manifest.contains("recorded_cycles = 40"));
+        assert!(trace.starts_with("## dataset builtin_rom "));
+        assert_eq!(trace.lines().count(), 2 + 40);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::json;
+
+use crate::connectome::cells::CellRegistry;
+use crate::connectome::{Connectome, Edge};
+use crate::emulations::c_elegans::neuron_groups::{CellClass, classify_cells, sensory_functions};
+use crate::emulations::c_elegans::preset::cell_registry;
+use crate::emulations::c_elegans::trace::DischargeTrace;
+
+const TEMPLATE: &str = include_str!("explorer.html");
+
+const WIDTH: f64 = 1200.;
+const HEIGHT: f64 = 800.;
+
+/// How cells are placed on the page.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Layout {
+    /// Sensory, inter, motor neurons and muscles in columns from left to right
+    Layered,
+    /// Fruchterman-Reingold springs started from the layered layout
+    Force { iterations: usize, seed: u64 },
+}
+
+fn layered(classes: &[CellClass], cells: &CellRegistry) -> Vec<(f64, f64)> {
+    let mut columns: BTreeMap<CellClass, Vec<usize>> = BTreeMap::new();
+    for (id, &class) in classes.iter().enumerate() {
+        columns.entry(class).or_default().push(id);
+    }
+    let mut positions = vec![(0., 0.); classes.len()];
+    let column_width = WIDTH / 4.;
+    for (&class, ids) in columns.iter_mut() {
+        // Sort by name so left/right pairs and numbered series sit together
+        ids.sort_by_key(|&id| cells.name(id as u16));
+        // Long columns wrap into two staggered sub-columns
+        let wrap = if ids.len() > 60 { 2 } else { 1 };
+        let rows = ids.len().div_ceil(wrap);
+        for (k, &id) in ids.iter().enumerate() {
+            let (sub, row) = (k / rows, k % rows);
+            let x = column_width * (class as usize as f64 + 0.25 + 0.4 * sub as f64);
+            let y = HEIGHT * (row as f64 + 0.5) / rows as f64;
+            positions[id] = (x, y);
+        }
+    }
+    positions
+}
+
+fn force_directed(
+    start: Vec<(f64, f64)>,
+    edges: &[Edge],
+    iterations: usize,
+    seed: u64,
+) -> Vec<(f64, f64)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut pos: Vec<(f64, f64)> = start
+        .into_iter()
+        .map(|(x, y)| {
+            (
+                x + rng.random_range(-5. ..5.),
+                y + rng.random_range(-5. ..5.),
+            )
+        })
+        .collect();
+    let n = pos.len();
+    let k = (WIDTH * HEIGHT / n.max(1) as f64).sqrt();
+    for step in 0..iterations {
+        let temperature = WIDTH / 10. * (1. - step as f64 / iterations as f64);
+        let mut shift = vec![(0., 0.); n];
+        for i in 0..n {
+            for j in i + 1..n {
+                let (dx, dy) = (pos[i].0 - pos[j].0, pos[i].1 - pos[j].1);
+                let d2 = (dx * dx + dy * dy).max(0.01);
+                let f = k * k / d2;
+                shift[i] = (shift[i].0 + dx * f, shift[i].1 + dy * f);
+                shift[j] = (shift[j].0 - dx * f, shift[j].1 - dy * f);
+            }
+        }
+        for e in edges {
+            let (a, b) = (e.pre as usize, e.post as usize);
+            let (dx, dy) = (pos[a].0 - pos[b].0, pos[a].1 - pos[b].1);
+            let d = (dx * dx + dy * dy).sqrt().max(0.1);
+            let f = d / k;
+            shift[a] = (shift[a].0 - dx * f, shift[a].1 - dy * f);
+            shift[b] = (shift[b].0 + dx * f, shift[b].1 + dy * f);
+        }
+        for (p, s) in pos.iter_mut().zip(&shift) {
+            let length = (s.0 * s.0 + s.1 * s.1).sqrt().max(1e-9);
+            let moved = length.min(temperature);
+            p.0 += s.0 / length * moved;
+            p.1 += s.1 / length * moved;
+        }
+    }
+    fit_to_page(pos)
+}
+
+fn fit_to_page(pos: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let (min_x, max_x) = pos.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.0), hi.max(p.0))
+    });
+    let (min_y, max_y) = pos.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.1), hi.max(p.1))
+    });
+    let scale = |v: f64, lo: f64, hi: f64, size: f64| {
+        size * 0.05 + (v - lo) / (hi - lo).max(1e-9) * size * 0.9
+    };
+    pos.into_iter()
+        .map(|(x, y)| {
+            (
+                scale(x, min_x, max_x, WIDTH),
+                scale(y, min_y, max_y, HEIGHT),
+            )
+        })
+        .collect()
+}
+
+/// Write a standalone HTML page showing the wiring of `connectome`.
+///
+/// Cells are labelled by name and coloured by class, sensory neurons by
+/// their modality. Clicking a cell highlights its inputs (blue) and outputs
+/// (red) and lists them with their weights. If `trace` holds recorded
+/// discharges, the page replays them cycle by cycle. Everything, data and
+/// script, is inline, so the file opens offline in any browser.
+pub fn write_explorer<W: Write>(
+    mut w: W,
+    connectome: &Connectome,
+    layout: Layout,
+    trace: Option<&DischargeTrace>,
+) -> Result<(), String> {
+    let neurons_tot = connectome.neurons_tot();
+    let cells = neurons_tot + connectome.muscles_tot();
+    let edges = connectome.edges();
+    let registry = connectome.cells();
+    // Modalities are keyed by C. elegans id, which a subcircuit renumbers
+    let modalities = sensory_functions();
+    let modality = |id: u16| {
+        cell_registry()
+            .id(&registry.name(id))
+            .and_then(|id| modalities.get(&id))
+    };
+    let classes = classify_cells(neurons_tot, cells, &edges, |id| modality(id).is_some());
+    let positions = match layout {
+        Layout::Layered => layered(&classes, registry),
+        Layout::Force { iterations, seed } => {
+            force_directed(layered(&classes, registry), &edges, iterations, seed)
+        }
+    };
+
+    let nodes: Vec<_> = (0..cells)
+        .map(|id| {
+            let (x, y) = positions[id as usize];
+            json!({
+                "name": registry.name(id),
+                "class": classes[id as usize].name(),
+                "modality": modality(id),
+                "x": (x * 10.).round() / 10.,
+                "y": (y * 10.).round() / 10.,
+            })
+        })
+        .collect();
+    let links: Vec<_> = edges
+        .iter()
+        .map(|e| json!({ "pre": e.pre, "post": e.post, "weight": e.weight }))
+        .collect();
+    let frames: Vec<Vec<u16>> = trace.map_or(vec![], |trace| {
+        trace
+            .rows
+            .iter()
+            .map(|row| {
+                trace
+                    .ids
+                    .iter()
+                    .zip(row)
+                    .filter(|&(_, &flag)| flag == 1)
+                    .map(|(&id, _)| id)
+                    .collect()
+            })
+            .collect()
+    });
+    let data = json!({
+        "width": WIDTH,
+        "height": HEIGHT,
+        "nodes": nodes,
+        "edges": links,
+        "frames": frames,
+    });
+    let page = TEMPLATE.replace("/*DATA*/", &data.to_string());
+    w.write_all(page.as_bytes()).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::edge_list::Circuit;
+
+    const CIRCUIT: &str = "neuron AVAL\nneuron AIYL\nneuron VA1\nmuscle MVL07\n\
+        AIYL AVAL 12\nAVAL VA1 30\nVA1 MVL07 20\n";
+
+    /// The JSON the page is built from
+    fn page_data(page: &str) -> serde_json::Value {
+        let start = page.find("const DATA = ").unwrap() + "const DATA = ".len();
+        let end = start + page[start..].find(";\n").unwrap();
+        serde_json::from_str(&page[start..end]).unwrap()
+    }
+
+    fn render(layout: Layout, trace: Option<&DischargeTrace>) -> serde_json::Value {
+        let connectome = Circuit::parse(CIRCUIT).unwrap().connectome().unwrap();
+        let mut page = Vec::new();
+        write_explorer(&mut page, &connectome, layout, trace).unwrap();
+        page_data(&String::from_utf8(page).unwrap())
+    }
+
+    fn positions(data: &serde_json::Value) -> Vec<(f64, f64)> {
+        data["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| (n["x"].as_f64().unwrap(), n["y"].as_f64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn lists_cells_and_edges_by_name() {
+        let data = render(Layout::Layered, None);
+        let nodes = data["nodes"].as_array().unwrap();
+        let names: Vec<_> = nodes.iter().map(|n| n["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["AVAL", "AIYL", "VA1", "MVL07"]);
+        assert_eq!(nodes[2]["class"], "motor");
+        assert_eq!(nodes[3]["class"], "muscle");
+        assert_eq!(data["edges"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            data["edges"][0],
+            json!({ "pre": 0, "post": 2, "weight": 30 })
+        );
+        assert_eq!(data["frames"], json!([]));
+    }
+
+    #[test]
+    fn layered_columns_follow_the_class() {
+        let data = render(Layout::Layered, None);
+        let x: Vec<f64> = positions(&data).iter().map(|p| p.0).collect();
+        // inter, motor and muscle columns run left to right
+        assert!(x[0] < x[2] && x[2] < x[3]);
+    }
+
+    #[test]
+    fn force_layout_is_seeded_and_fits_the_page() {
+        let layout = |seed| Layout::Force {
+            iterations: 20,
+            seed,
+        };
+        let a = positions(&render(layout(1), None));
+        assert_eq!(a, positions(&render(layout(1), None)));
+        assert_ne!(a, positions(&render(layout(2), None)));
+        for (x, y) in a {
+            assert!((0. ..=WIDTH).contains(&x) && (0. ..=HEIGHT).contains(&y));
+        }
+    }
+
+    #[test]
+    fn replays_the_trace_as_frames() {
+        let mut trace = DischargeTrace::new(vec![0, 2]);
+        trace.rows = vec![vec![1, 0], vec![1, 1], vec![0, 0]];
+        let data = render(Layout::Layered, Some(&trace));
+        assert_eq!(data["frames"], json!([[0], [0, 2], []]));
+    }
+}
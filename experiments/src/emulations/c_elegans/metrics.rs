@@ -0,0 +1,280 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+
+use crate::emulations::c_elegans::neuron_ids::cell_name;
+use crate::emulations::c_elegans::trace::DischargeTrace;
+
+/// Discharges per cycle of every column of the trace
+pub fn firing_rates(trace: &DischargeTrace) -> Vec<f64> {
+    let cycles = trace.rows.len().max(1) as f64;
+    (0..trace.ids.len())
+        .map(|c| trace.rows.iter().map(|row| row[c] as f64).sum::<f64>() / cycles)
+        .collect()
+}
+
+/// Spike trains of `ids` (in that order, missing ids skipped), smoothed with
+/// a centred box of `bin` cycles.
+pub fn smoothed_trains(trace: &DischargeTrace, ids: &[u16], bin: usize) -> Vec<Vec<f64>> {
+    let rows = trace.columns(ids);
+    let columns = rows.first().map_or(0, |r| r.len());
+    (0..columns)
+        .map(|c| {
+            let train: Vec<f64> = rows.iter().map(|row| row[c] as f64).collect();
+            box_smooth(&train, bin)
+        })
+        .collect()
+}
+
+fn box_smooth(signal: &[f64], bin: usize) -> Vec<f64> {
+    let half = bin.max(1) / 2;
+    (0..signal.len())
+        .map(|t| {
+            let window = &signal[t.saturating_sub(half)..(t + half + 1).min(signal.len())];
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .collect()
+}
+
+fn mean(signal: &[f64]) -> f64 {
+    signal.iter().sum::<f64>() / signal.len().max(1) as f64
+}
+
+fn variance(signal: &[f64]) -> f64 {
+    let m = mean(signal);
+    signal.iter().map(|x| (x - m).powi(2)).sum::<f64>() / signal.len().max(1) as f64
+}
+
+/// Pearson correlation, `None` if either signal is constant
+pub fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let (ma, mb) = (mean(a), mean(b));
+    let cov: f64 = a.iter().zip(b).map(|(x, y)| (x - ma) * (y - mb)).sum();
+    let norm = (variance(a) * variance(b)).sqrt() * a.len() as f64;
+    (norm > 0.).then(|| cov / norm)
+}
+
+/// Pairwise correlations of the smoothed trains; silent neurons give `None`
+pub fn correlation_matrix(trains: &[Vec<f64>]) -> Vec<Vec<Option<f64>>> {
+    trains
+        .iter()
+        .map(|a| trains.iter().map(|b| correlation(a, b)).collect())
+        .collect()
+}
+
+/// How together a set of neurons fires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Synchrony {
+    /// Mean correlation over pairs where both neurons fired
+    pub mean_correlation: f64,
+    /// Golomb's measure: the standard deviation of the population mean over
+    /// the root mean variance of the individual trains, 1 for identical
+    /// trains and near 0 for independent ones
+    pub chi: f64,
+}
+
+impl Synchrony {
+    pub fn from_trains(trains: &[Vec<f64>]) -> Self {
+        let pairs: Vec<f64> = (0..trains.len())
+            .flat_map(|i| (i + 1..trains.len()).map(move |j| (i, j)))
+            .filter_map(|(i, j)| correlation(&trains[i], &trains[j]))
+            .collect();
+        let cycles = trains.first().map_or(0, |t| t.len());
+        let population: Vec<f64> = (0..cycles)
+            .map(|t| trains.iter().map(|train| train[t]).sum::<f64>() / trains.len() as f64)
+            .collect();
+        let individual =
+            trains.iter().map(|t| variance(t)).sum::<f64>() / trains.len().max(1) as f64;
+        Self {
+            mean_correlation: mean(&pairs),
+            chi: if individual > 0. {
+                (variance(&population) / individual).sqrt()
+            } else {
+                0.
+            },
+        }
+    }
+}
+
+fn spectrum(signal: &[f64]) -> Vec<Complex<f64>> {
+    let n = signal.len();
+    let m = mean(signal);
+    // Hann window keeps the edges of the recording from leaking into every bin
+    let mut buffer: Vec<Complex<f64>> = signal
+        .iter()
+        .enumerate()
+        .map(|(t, x)| {
+            let hann = 0.5 - 0.5 * (2. * PI * t as f64 / n.max(2) as f64).cos();
+            Complex::new((x - m) * hann, 0.)
+        })
+        .collect();
+    FftPlanner::new().plan_fft_forward(n).process(&mut buffer);
+    buffer
+}
+
+/// Fewest full oscillations a recording must contain for a frequency to
+/// count, so slow drifts such as a stimulus change are not taken as rhythm
+pub const MIN_OSCILLATIONS: usize = 4;
+
+/// Strongest frequency of a signal with at least `MIN_OSCILLATIONS` periods
+/// in the recording, in oscillations per cycle, with its share of the power
+/// at those frequencies. `None` for constant or too short signals.
+pub fn dominant_frequency(signal: &[f64]) -> Option<(f64, f64)> {
+    let n = signal.len();
+    if n / 2 <= MIN_OSCILLATIONS {
+        return None;
+    }
+    let power: Vec<f64> = spectrum(signal)[MIN_OSCILLATIONS..n / 2]
+        .iter()
+        .map(|c| c.norm_sqr())
+        .collect();
+    let total: f64 = power.iter().sum();
+    let (k, &peak) = power.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+    (total > 0.).then(|| ((k + MIN_OSCILLATIONS) as f64 / n as f64, peak / total))
+}
+
+/// Phase lags along a chain of neurons at the chain's dominant frequency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TravellingWave {
+    pub chain: Vec<u16>,
+    /// Oscillations per cycle of the chain's summed activity
+    pub frequency: f64,
+    /// Lag in cycles from each neuron to the next; positive when the wave
+    /// runs along the chain in the given order
+    pub lags: Vec<f64>,
+    /// Fraction of lags that share the sign of the mean lag
+    pub consistency: f64,
+}
+
+impl TravellingWave {
+    /// `None` if the chain is silent or has fewer than two recorded neurons.
+    pub fn from_trace(trace: &DischargeTrace, chain: &[u16], bin: usize) -> Option<Self> {
+        let chain: Vec<u16> = chain
+            .iter()
+            .copied()
+            .filter(|id| trace.ids.contains(id))
+            .collect();
+        let trains = smoothed_trains(trace, &chain, bin);
+        if trains.len() < 2 {
+            return None;
+        }
+        let cycles = trains[0].len();
+        let summed: Vec<f64> = (0..cycles)
+            .map(|t| trains.iter().map(|train| train[t]).sum())
+            .collect();
+        let (frequency, _) = dominant_frequency(&summed)?;
+        let k = (frequency * cycles as f64).round() as usize;
+        let phases: Vec<Complex<f64>> = trains.iter().map(|t| spectrum(t)[k]).collect();
+        let lags: Vec<f64> = phases
+            .windows(2)
+            .map(|pair| (pair[0] * pair[1].conj()).arg() / (2. * PI * frequency))
+            .collect();
+        let mean_lag = mean(&lags);
+        let consistency = lags
+            .iter()
+            .filter(|l| l.signum() == mean_lag.signum())
+            .count() as f64
+            / lags.len() as f64;
+        Some(Self {
+            chain,
+            frequency,
+            lags,
+            consistency,
+        })
+    }
+
+    pub fn mean_lag(&self) -> f64 {
+        mean(&self.lags)
+    }
+}
+
+impl fmt::Display for TravellingWave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, last) = (self.chain[0], self.chain[self.chain.len() - 1]);
+        writeln!(
+            f,
+            "{}->{}  frequency {:.4}/cycle (period {:.1})  mean lag {:+.2}  consistency {:.2}",
+            cell_name(first),
+            cell_name(last),
+            self.frequency,
+            1. / self.frequency,
+            self.mean_lag(),
+            self.consistency
+        )?;
+        let lags: Vec<String> = self.lags.iter().map(|l| format!("{:+.2}", l)).collect();
+        write!(f, "  lags {}", lags.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square waves of period 20 on `ids`, each `shift` cycles after the last
+    fn wave(ids: &[u16], shift: usize, cycles: usize) -> DischargeTrace {
+        let mut trace = DischargeTrace::new(ids.to_vec());
+        trace.rows = (0..cycles)
+            .map(|t| {
+                (0..ids.len())
+                    .map(|k| u8::from((t + 20 - k * shift % 20) % 20 < 10))
+                    .collect()
+            })
+            .collect();
+        trace
+    }
+
+    #[test]
+    fn rates_and_smoothing() {
+        let mut trace = DischargeTrace::new(vec![0, 1]);
+        trace.rows = vec![vec![1, 0], vec![1, 0], vec![0, 0], vec![1, 0]];
+        assert_eq!(firing_rates(&trace), [0.75, 0.]);
+        let trains = smoothed_trains(&trace, &[1, 0, 7], 3);
+        assert_eq!(trains.len(), 2);
+        assert_eq!(trains[1], [1., 2. / 3., 2. / 3., 0.5]);
+    }
+
+    #[test]
+    fn correlation_and_synchrony() {
+        let a = [0., 1., 0., 1.];
+        let b = [1., 0., 1., 0.];
+        assert!((correlation(&a, &a).unwrap() - 1.).abs() < 1e-12);
+        assert!((correlation(&a, &b).unwrap() + 1.).abs() < 1e-12);
+        assert_eq!(correlation(&a, &[1.; 4]), None);
+        let matrix = correlation_matrix(&[a.to_vec(), vec![0.; 4]]);
+        assert_eq!(matrix[0][1], None);
+
+        let same = Synchrony::from_trains(&[a.to_vec(), a.to_vec(), a.to_vec()]);
+        assert!((same.chi - 1.).abs() < 1e-12);
+        assert!((same.mean_correlation - 1.).abs() < 1e-12);
+        let opposed = Synchrony::from_trains(&[a.to_vec(), b.to_vec()]);
+        assert!(opposed.chi < 1e-12);
+    }
+
+    #[test]
+    fn finds_the_rhythm() {
+        let signal: Vec<f64> = (0..200).map(|t| (2. * PI * t as f64 / 20.).sin()).collect();
+        let (frequency, share) = dominant_frequency(&signal).unwrap();
+        assert!((frequency - 0.05).abs() < 1e-9);
+        assert!(share > 0.5);
+        assert_eq!(dominant_frequency(&[1.; 200]), None);
+        assert_eq!(dominant_frequency(&[0., 1., 0., 1.]), None);
+    }
+
+    #[test]
+    fn travelling_wave_lags_follow_the_chain() {
+        let trace = wave(&[10, 11, 12, 13], 3, 200);
+        let forward = TravellingWave::from_trace(&trace, &[10, 11, 12, 13], 1).unwrap();
+        assert!((forward.frequency - 0.05).abs() < 1e-9);
+        assert!(
+            forward.lags.iter().all(|l| (l - 3.).abs() < 0.5),
+            "{:?}",
+            forward.lags
+        );
+        assert_eq!(forward.consistency, 1.);
+        let backward = TravellingWave::from_trace(&trace, &[13, 12, 11, 10], 1).unwrap();
+        assert!((backward.mean_lag() + forward.mean_lag()).abs() < 1e-9);
+        // ids missing from the trace are skipped
+        assert!(TravellingWave::from_trace(&trace, &[10, 99], 1).is_none());
+    }
+}
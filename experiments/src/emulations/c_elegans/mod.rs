@@ -1,3 +1,16 @@
-pub mod c_elegans_nematode;
+pub mod behaviour;
+pub mod csv_tables;
+pub mod dashboard;
+pub mod dataset;
+pub mod experiment;
+pub mod explorer;
+pub mod metrics;
+pub mod neuron_groups;
 pub mod neuron_ids;
+pub mod preset;
+pub mod protocol;
 pub mod rom;
+pub mod screen;
+pub mod server;
+pub mod trace;
+pub mod worm_robot;
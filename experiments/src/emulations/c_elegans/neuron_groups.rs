@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::connectome::Edge;
+use crate::connectome::muscle_model::{MuscleModel, MuscleParams};
+use crate::emulations::c_elegans::neuron_ids::{CELLS, NeuronId};
+
+pub const MOTOR_NEURON_B: [u16; 18] = [
+    NeuronId::DB1 as u16,
+    NeuronId::DB2 as u16,
+    NeuronId::DB3 as u16,
+    NeuronId::DB4 as u16,
+    NeuronId::DB5 as u16,
+    NeuronId::DB6 as u16,
+    NeuronId::DB7 as u16,
+    NeuronId::VB1 as u16,
+    NeuronId::VB2 as u16,
+    NeuronId::VB3 as u16,
+    NeuronId::VB4 as u16,
+    NeuronId::VB5 as u16,
+    NeuronId::VB6 as u16,
+    NeuronId::VB7 as u16,
+    NeuronId::VB8 as u16,
+    NeuronId::VB9 as u16,
+    NeuronId::VB10 as u16,
+    NeuronId::VB11 as u16,
+];
+
+pub const MOTOR_NEURON_A: [u16; 21] = [
+    NeuronId::DA1 as u16,
+    NeuronId::DA2 as u16,
+    NeuronId::DA3 as u16,
+    NeuronId::DA4 as u16,
+    NeuronId::DA5 as u16,
+    NeuronId::DA6 as u16,
+    NeuronId::DA7 as u16,
+    NeuronId::DA8 as u16,
+    NeuronId::DA9 as u16,
+    NeuronId::VA1 as u16,
+    NeuronId::VA2 as u16,
+    NeuronId::VA3 as u16,
+    NeuronId::VA4 as u16,
+    NeuronId::VA5 as u16,
+    NeuronId::VA6 as u16,
+    NeuronId::VA7 as u16,
+    NeuronId::VA8 as u16,
+    NeuronId::VA9 as u16,
+    NeuronId::VA10 as u16,
+    NeuronId::VA11 as u16,
+    NeuronId::VA12 as u16,
+];
+
+pub const NOSE_TOUCH: [u16; 10] = [
+    NeuronId::FLPR as u16,
+    NeuronId::FLPL as u16,
+    NeuronId::ASHL as u16,
+    NeuronId::ASHR as u16,
+    NeuronId::IL1VL as u16,
+    NeuronId::IL1VR as u16,
+    NeuronId::OLQDL as u16,
+    NeuronId::OLQDR as u16,
+    NeuronId::OLQVR as u16,
+    NeuronId::OLQVL as u16,
+];
+
+pub const CHEMOTAXIS: [u16; 8] = [
+    NeuronId::ADFL as u16,
+    NeuronId::ADFR as u16,
+    NeuronId::ASGR as u16,
+    NeuronId::ASGL as u16,
+    NeuronId::ASIL as u16,
+    NeuronId::ASIR as u16,
+    NeuronId::ASJR as u16,
+    NeuronId::ASJL as u16,
+];
+
+const SENSORY_CSV: &str = include_str!("CElegansNeuronTables/Sensory.csv");
+
+/// Named groups usable wherever a stimulus or recording target is expected
+pub fn named_group(name: &str) -> Option<Vec<u16>> {
+    match name {
+        "chemotaxis" => Some(CHEMOTAXIS.to_vec()),
+        "nose_touch" => Some(NOSE_TOUCH.to_vec()),
+        "motor_a" => Some(MOTOR_NEURON_A.to_vec()),
+        "motor_b" => Some(MOTOR_NEURON_B.to_vec()),
+        "motor_ab" => Some([MOTOR_NEURON_A.as_slice(), MOTOR_NEURON_B.as_slice()].concat()),
+        "db_chain" => Some(motor_chain("DB")),
+        "vb_chain" => Some(motor_chain("VB")),
+        "da_chain" => Some(motor_chain("DA")),
+        "va_chain" => Some(motor_chain("VA")),
+        _ => None,
+    }
+}
+
+/// Sensory neurons whose function in `Sensory.csv` mentions `modality`
+/// (e.g. "mechanosensory", "thermosensory"), matched case-insensitively.
+pub fn sensory_modality(modality: &str) -> Vec<u16> {
+    let modality = modality.to_lowercase();
+    let mut reader = csv::Reader::from_reader(SENSORY_CSV.as_bytes());
+    reader
+        .records()
+        .filter_map(|record| record.ok())
+        .filter(|record| {
+            record.get(6).is_some_and(|function| {
+                function
+                    .split(',')
+                    .any(|f| f.trim().trim_matches(['(', ')']).to_lowercase() == modality)
+            })
+        })
+        .filter_map(|record| record.get(0).and_then(NeuronId::from_name))
+        .map(|id| id as u16)
+        .collect()
+}
+
+/// Sensory neurons whose neuropeptide notes in `Sensory.csv` mention `peptide`
+/// (e.g. "FMRFamide"), matched case-insensitively.
+pub fn neuropeptide_expressing(peptide: &str) -> Vec<u16> {
+    let peptide = peptide.to_lowercase();
+    let mut reader = csv::Reader::from_reader(SENSORY_CSV.as_bytes());
+    reader
+        .records()
+        .filter_map(|record| record.ok())
+        .filter(|record| {
+            record
+                .get(5)
+                .is_some_and(|p| p.to_lowercase().contains(&peptide))
+        })
+        .filter_map(|record| record.get(0).and_then(NeuronId::from_name))
+        .map(|id| id as u16)
+        .collect()
+}
+
+/// Every neuron listed in `Sensory.csv` with the first modality of its
+/// function, lowercased (e.g. "chemosensory"; "sensory" if none is given)
+pub fn sensory_functions() -> BTreeMap<u16, String> {
+    let mut reader = csv::Reader::from_reader(SENSORY_CSV.as_bytes());
+    reader
+        .records()
+        .filter_map(|record| record.ok())
+        .filter_map(|record| {
+            let id = NeuronId::from_name(record.get(0)?)? as u16;
+            let function = record
+                .get(6)
+                .and_then(|f| f.split(',').next())
+                .map(|f| f.trim().trim_matches(['(', ')', '"']).to_lowercase())
+                .filter(|f| !f.is_empty())
+                .unwrap_or_else(|| "sensory".to_string());
+            Some((id, function))
+        })
+        .collect()
+}
+
+/// Role of a cell in the circuit
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CellClass {
+    Sensory,
+    Inter,
+    Motor,
+    Muscle,
+}
+
+impl CellClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            CellClass::Sensory => "sensory",
+            CellClass::Inter => "inter",
+            CellClass::Motor => "motor",
+            CellClass::Muscle => "muscle",
+        }
+    }
+}
+
+/// Class of every cell: neurons listed in `Sensory.csv` are sensory, other
+/// neurons that synapse onto a muscle are motor neurons, the rest are
+/// interneurons.
+pub fn cell_classes(neurons_tot: u16, cells: u16, edges: &[Edge]) -> Vec<CellClass> {
+    let sensory = sensory_functions();
+    classify_cells(neurons_tot, cells, edges, |id| sensory.contains_key(&id))
+}
+
+/// Like `cell_classes`, with the sensory neurons picked by `is_sensory`,
+/// e.g. by name for a wiring that does not use C. elegans ids.
+pub fn classify_cells(
+    neurons_tot: u16,
+    cells: u16,
+    edges: &[Edge],
+    is_sensory: impl Fn(u16) -> bool,
+) -> Vec<CellClass> {
+    let motor: BTreeSet<u16> = edges
+        .iter()
+        .filter(|e| e.post >= neurons_tot)
+        .map(|e| e.pre)
+        .collect();
+    (0..cells)
+        .map(|id| {
+            if id >= neurons_tot {
+                CellClass::Muscle
+            } else if is_sensory(id) {
+                CellClass::Sensory
+            } else if motor.contains(&id) {
+                CellClass::Motor
+            } else {
+                CellClass::Inter
+            }
+        })
+        .collect()
+}
+
+/// Motor neurons of one class ordered head to tail, e.g. DB1..DB7 for `DB`
+pub fn motor_chain(class: &str) -> Vec<u16> {
+    (1..)
+        .map_while(|n| NeuronId::from_name(&format!("{}{}", class, n)))
+        .map(|id| id as u16)
+        .collect()
+}
+
+/// Body-wall muscles of one quadrant (`MDL`, `MDR`, `MVL` or `MVR`),
+/// ordered head to tail
+pub fn body_wall_quadrant(quadrant: &str) -> Vec<u16> {
+    (1..=24)
+        .filter_map(|n| NeuronId::from_name(&format!("{}{:02}", quadrant, n)))
+        .map(|id| id as u16)
+        .collect()
+}
+
+/// Dorsal and ventral muscles of the same side and segment, e.g. MDL05 and MVL05
+pub fn dorsal_ventral_pairs() -> Vec<(u16, u16)> {
+    [("MDL", "MVL"), ("MDR", "MVR")]
+        .iter()
+        .flat_map(|(dorsal, ventral)| {
+            (1..=24).filter_map(move |n| {
+                let d = NeuronId::from_name(&format!("{}{:02}", dorsal, n))?;
+                let v = NeuronId::from_name(&format!("{}{:02}", ventral, n))?;
+                Some((d as u16, v as u16))
+            })
+        })
+        .collect()
+}
+
+/// Muscle model for every muscle cell, with dorsal and ventral body-wall
+/// muscles of each segment as antagonists
+pub fn body_wall_muscle_model(params: MuscleParams) -> MuscleModel {
+    let first = NeuronId::MANAL as u16;
+    let pairs: Vec<(usize, usize)> = dorsal_ventral_pairs()
+        .iter()
+        .map(|&(d, v)| ((d - first) as usize, (v - first) as usize))
+        .collect();
+    MuscleModel::new((CELLS - first) as usize, params).with_antagonists(&pairs)
+}
+
+/// Resolve a cell name, named group or sensory modality to cell ids.
+pub fn resolve_target(target: &str) -> Result<Vec<u16>, String> {
+    if let Some(id) = NeuronId::from_name(target) {
+        return Ok(vec![id as u16]);
+    }
+    if let Some(group) = named_group(target) {
+        return Ok(group);
+    }
+    let modality = sensory_modality(target);
+    if modality.is_empty() {
+        Err(format!(
+            "{} is not a cell, group or sensory modality",
+            target
+        ))
+    } else {
+        Ok(modality)
+    }
+}
+
+/// Resolve and concatenate several targets
+pub fn resolve_targets<S: AsRef<str>>(targets: &[S]) -> Result<Vec<u16>, String> {
+    let mut ids = vec![];
+    for t in targets {
+        ids.extend(resolve_target(t.as_ref())?);
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_ids::cell_name;
+
+    fn names(ids: &[u16]) -> Vec<String> {
+        ids.iter().map(|&id| cell_name(id)).collect()
+    }
+
+    #[test]
+    fn chains_and_quadrants_run_head_to_tail() {
+        assert_eq!(
+            names(&motor_chain("DB")),
+            ["DB1", "DB2", "DB3", "DB4", "DB5", "DB6", "DB7"]
+        );
+        let quadrant = body_wall_quadrant("MDL");
+        assert_eq!(names(&quadrant[..2]), ["MDL01", "MDL02"]);
+        assert!(quadrant.len() >= 23);
+        let pairs = dorsal_ventral_pairs();
+        assert!(pairs.contains(&(NeuronId::MDL05 as u16, NeuronId::MVL05 as u16)));
+        assert!(
+            pairs
+                .iter()
+                .all(|&(d, v)| cell_name(d)[3..] == cell_name(v)[3..])
+        );
+    }
+
+    #[test]
+    fn resolves_cells_groups_and_modalities() {
+        assert_eq!(names(&resolve_target("AVAL").unwrap()), ["AVAL"]);
+        assert_eq!(resolve_target("chemotaxis").unwrap(), CHEMOTAXIS);
+        assert_eq!(
+            resolve_targets(&["motor_a", "motor_b"]).unwrap(),
+            named_group("motor_ab").unwrap()
+        );
+        let touch = resolve_target("Mechanosensory").unwrap();
+        assert!(!touch.is_empty());
+        let functions = sensory_functions();
+        assert!(touch.iter().all(|id| functions.contains_key(id)));
+        assert!(resolve_target("nonsense").is_err());
+        assert!(resolve_targets(&["AVAL", "nonsense"]).is_err());
+    }
+
+    #[test]
+    fn classes_sensory_motor_inter_and_muscle() {
+        let sensory = CHEMOTAXIS[0];
+        let edges = [
+            Edge {
+                pre: 0,
+                post: 2,
+                weight: 5,
+            },
+            Edge {
+                pre: sensory,
+                post: 0,
+                weight: 5,
+            },
+        ];
+        let classes = classify_cells(2, 3, &edges, |id| id == 1);
+        assert_eq!(
+            classes,
+            [CellClass::Motor, CellClass::Sensory, CellClass::Muscle]
+        );
+        let classes = cell_classes(CELLS - 1, CELLS, &edges);
+        assert_eq!(classes[sensory as usize], CellClass::Sensory);
+        assert_eq!(classes[1], CellClass::Inter);
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+
 #[repr(u16)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum NeuronId {
@@ -399,11 +402,44 @@ pub enum NeuronId {
     MVR24 = 395,
     MVULVA = 396,
 }
+/// Neurons and muscles in the C. elegans connectome
+pub const CELLS: u16 = NeuronId::MVULVA as u16 + 1;
+
 impl NeuronId {
     #[inline]
     pub fn as_usize(self) -> usize {
         self as usize
     }
+
+    /// Look up a cell by its name, e.g. "AVAL" or "MDL05"
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..CELLS)
+            .filter_map(|id| NeuronId::try_from(id).ok())
+            .find(|n| n.name() == name)
+    }
+
+    pub fn name(self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl fmt::Display for NeuronId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for NeuronId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NeuronId::from_name(s).ok_or_else(|| format!("Unknown cell name: {}", s))
+    }
+}
+
+/// Name of a cell id, falling back to `#<id>` outside the C. elegans range
+pub fn cell_name(id: u16) -> String {
+    NeuronId::try_from(id).map_or_else(|_| format!("#{}", id), |n| n.name())
 }
 
 impl TryFrom<u16> for NeuronId {
@@ -411,7 +447,7 @@ impl TryFrom<u16> for NeuronId {
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         if value <= NeuronId::MVULVA as u16 {
-            Ok(unsafe { std::mem::transmute(value) })
+            Ok(unsafe { std::mem::transmute::<u16, NeuronId>(value) })
         } else {
             Err(())
         }
@@ -0,0 +1,78 @@
+use std::sync::OnceLock;
+
+use crate::connectome::Connectome;
+use crate::connectome::cells::{CellInfo, CellRegistry};
+use crate::emulations::c_elegans::neuron_groups::{CellClass, cell_classes};
+use crate::emulations::c_elegans::neuron_ids::{CELLS, cell_name};
+use crate::emulations::c_elegans::rom::ROM;
+
+/// The 397 C. elegans cells by `NeuronId`, typed by their class in the
+/// built-in wiring (sensory, inter, motor, muscle).
+pub fn cell_registry() -> &'static CellRegistry {
+    static REGISTRY: OnceLock<CellRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let wiring = Connectome::from_rom(ROM.to_vec(), CELLS).expect("built-in ROM is valid");
+        let cells = cell_classes(wiring.neurons_tot(), CELLS, &wiring.edges())
+            .into_iter()
+            .zip(0..)
+            .map(|(class, id)| match class {
+                CellClass::Muscle => CellInfo::muscle(&cell_name(id), class.name()),
+                _ => CellInfo::neuron(&cell_name(id), class.name()),
+            })
+            .collect();
+        CellRegistry::new(cells).expect("C. elegans cell names are unique")
+    })
+}
+
+/// The built-in C. elegans ROM with its named cells
+pub fn connectome() -> Connectome {
+    Connectome::from_rom(ROM.to_vec(), CELLS)
+        .and_then(|c| c.with_cells(cell_registry().clone()))
+        .expect("built-in ROM is valid")
+}
+
+/// Name the cells of a connectome built on the C. elegans layout, e.g. from
+/// a ROM file or CSV tables. A connectome that splits neurons and muscles
+/// differently is not on that layout, so its cells are named by id.
+pub fn with_names(connectome: Connectome) -> Result<Connectome, String> {
+    let registry = cell_registry();
+    if connectome.neurons_tot() == registry.neurons_tot()
+        && connectome.muscles_tot() == registry.muscles_tot()
+    {
+        connectome.with_cells(registry.clone())
+    } else {
+        let (neurons_tot, cells) = (
+            connectome.neurons_tot(),
+            connectome.neurons_tot() + connectome.muscles_tot(),
+        );
+        connectome.with_cells(CellRegistry::anonymous(neurons_tot, cells))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::rom_image::build_rom;
+
+    #[test]
+    fn registry_types_the_builtin_cells() {
+        let cells = cell_registry();
+        assert_eq!((cells.neurons_tot(), cells.len()), (299, CELLS));
+        assert_eq!(cells.name(0), cell_name(0));
+        assert_eq!(cells.kind(cells.id("MDL01").unwrap()), Some("muscle"));
+        assert!(cells.resolve(&["motor"]).unwrap().len() > 50);
+        assert_eq!(connectome().cells().name(42), cell_name(42));
+    }
+
+    #[test]
+    fn other_layouts_are_named_by_id() {
+        let rom = build_rom(10, &[]).unwrap();
+        let connectome = with_names(Connectome::from_rom(rom, CELLS).unwrap()).unwrap();
+        assert_eq!(connectome.cells().name(0), "cell0");
+        assert_eq!(connectome.cells().neurons_tot(), 10);
+
+        let rom = build_rom(299, &[]).unwrap();
+        let connectome = with_names(Connectome::from_rom(rom, CELLS).unwrap()).unwrap();
+        assert_eq!(connectome.cells().name(0), cell_name(0));
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::connectome::Connectome;
+use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+use crate::emulations::c_elegans::neuron_ids::cell_name;
+
+/// One line of a stimulus protocol.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtocolStep {
+    Threshold(i8),
+    Ablate(Vec<u16>),
+    /// Run `cycles` neural cycles pinging `stimulus` each cycle
+    Run {
+        cycles: usize,
+        stimulus: Vec<u16>,
+        record: bool,
+    },
+}
+
+/// A sequence of engine settings, lesions and stimulus blocks.
+///
+/// The text format has one step per line, `#` starts a comment:
+///
+/// ```text
+/// threshold 40
+/// ablate AVAL AVAR
+/// burn 1000 chemotaxis        # run without recording
+/// stimulate 1000 chemotaxis   # run and record
+/// stimulate 1000 nose_touch
+/// rest 500                    # record without stimulus
+/// ```
+///
+/// Targets are cell names, groups (`chemotaxis`, `nose_touch`, `motor_a`,
+/// `motor_b`, `db_chain`, ...) or sensory modalities from `Sensory.csv`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Protocol {
+    pub steps: Vec<ProtocolStep>,
+}
+
+impl Protocol {
+    /// The original experiment: burn in and run under chemotaxis, then nose touch.
+    pub fn chemotaxis_then_touch() -> Self {
+        let chemotaxis = resolve_targets(&["chemotaxis"]).expect("built-in group");
+        let nose_touch = resolve_targets(&["nose_touch"]).expect("built-in group");
+        Self {
+            steps: vec![
+                ProtocolStep::Run {
+                    cycles: 1000,
+                    stimulus: chemotaxis.clone(),
+                    record: false,
+                },
+                ProtocolStep::Run {
+                    cycles: 1000,
+                    stimulus: chemotaxis,
+                    record: true,
+                },
+                ProtocolStep::Run {
+                    cycles: 1000,
+                    stimulus: nose_touch,
+                    record: true,
+                },
+            ],
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut steps = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut words = line.split_whitespace();
+            let Some(command) = words.next() else {
+                continue;
+            };
+            let args: Vec<&str> = words.collect();
+            let step = Self::parse_step(command, &args)
+                .map_err(|err| format!("line {}: {}", n + 1, err))?;
+            steps.push(step);
+        }
+        Ok(Self { steps })
+    }
+
+    fn parse_step(command: &str, args: &[&str]) -> Result<ProtocolStep, String> {
+        let cycles = || -> Result<usize, String> {
+            args.first()
+                .ok_or(format!("{} needs a cycle count", command))?
+                .parse::<usize>()
+                .map_err(|err| err.to_string())
+        };
+        let rest = || args.get(1..).unwrap_or(&[]);
+        match command {
+            "threshold" => Ok(ProtocolStep::Threshold(
+                args.first()
+                    .ok_or("threshold needs a value")?
+                    .parse::<i8>()
+                    .map_err(|err| err.to_string())?,
+            )),
+            "ablate" => Ok(ProtocolStep::Ablate(resolve_targets(args)?)),
+            "burn" | "stimulate" => Ok(ProtocolStep::Run {
+                cycles: cycles()?,
+                stimulus: resolve_targets(rest())?,
+                record: command == "stimulate",
+            }),
+            "rest" => Ok(ProtocolStep::Run {
+                cycles: cycles()?,
+                stimulus: vec![],
+                record: true,
+            }),
+            _ => Err(format!("unknown step {}", command)),
+        }
+    }
+
+    /// Recorded cycles in the protocol
+    pub fn recorded_cycles(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                ProtocolStep::Run {
+                    cycles,
+                    record: true,
+                    ..
+                } => *cycles,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Run every step on `connectome`, calling `on_record` after each recorded cycle.
+    pub fn run(
+        &self,
+        connectome: &mut Connectome,
+        mut on_record: impl FnMut(&Connectome) -> Result<(), String>,
+    ) -> Result<(), String> {
+        for step in &self.steps {
+            match step {
+                ProtocolStep::Threshold(threshold) => connectome.set_threshold(*threshold),
+                ProtocolStep::Ablate(ids) => ids.iter().for_each(|&id| connectome.ablate(id)),
+                ProtocolStep::Run {
+                    cycles,
+                    stimulus,
+                    record,
+                } => {
+                    let stim = (!stimulus.is_empty()).then_some(stimulus.as_slice());
+                    for _ in 0..*cycles {
+                        connectome.neural_cycle(stim);
+                        if *record {
+                            on_record(connectome)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The text format read by `Protocol::parse`, one step per line with the
+/// targets spelled out as cell names
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |ids: &[u16]| {
+            ids.iter()
+                .map(|&id| format!(" {}", cell_name(id)))
+                .collect::<String>()
+        };
+        for step in &self.steps {
+            match step {
+                ProtocolStep::Threshold(threshold) => writeln!(f, "threshold {}", threshold)?,
+                ProtocolStep::Ablate(ids) => writeln!(f, "ablate{}", names(ids))?,
+                ProtocolStep::Run {
+                    cycles,
+                    stimulus,
+                    record: true,
+                } if stimulus.is_empty() => writeln!(f, "rest {}", cycles)?,
+                ProtocolStep::Run {
+                    cycles,
+                    stimulus,
+                    record,
+                } => writeln!(
+                    f,
+                    "{} {}{}",
+                    if *record { "stimulate" } else { "burn" },
+                    cycles,
+                    names(stimulus)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trips() {
+        let text = "threshold 30\nablate AVAL AVAR\nburn 10 chemotaxis\nburn 5\nstimulate 20 nose_touch\nrest 7\n";
+        let protocol = Protocol::parse(text).unwrap();
+        assert_eq!(protocol.steps.len(), 6);
+        assert_eq!(Protocol::parse(&protocol.to_string()).unwrap(), protocol);
+        let builtin = Protocol::chemotaxis_then_touch();
+        assert_eq!(Protocol::parse(&builtin.to_string()).unwrap(), builtin);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_counts_recorded_cycles() {
+        let protocol = Protocol::parse(
+            "# setup\n\nburn 100 chemotaxis # unrecorded\nrest 50\nstimulate 25 AVAL\n",
+        )
+        .unwrap();
+        assert_eq!(protocol.steps.len(), 3);
+        assert_eq!(protocol.recorded_cycles(), 75);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let err = Protocol::parse("rest 10\nwiggle 5\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{}", err);
+        assert!(Protocol::parse("stimulate many AVAL").is_err());
+        assert!(Protocol::parse("ablate NOTACELL").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+
+use rayon::prelude::*;
+
+use crate::connectome::Connectome;
+use crate::emulations::c_elegans::behaviour::BehaviourSummary;
+use crate::emulations::c_elegans::neuron_groups::{
+    MOTOR_NEURON_A, MOTOR_NEURON_B, body_wall_quadrant,
+};
+use crate::emulations::c_elegans::neuron_ids::cell_name;
+use crate::emulations::c_elegans::protocol::Protocol;
+use crate::emulations::c_elegans::trace::DischargeTrace;
+
+/// Behavioural readouts of one protocol run, each in `-1..=1`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Readout {
+    /// (B - A) / (B + A) of the per-neuron motor discharge rates; positive is forward
+    pub ab_balance: f64,
+    /// (left - right) / (left + right) of the summed positive body-wall muscle input
+    pub muscle_asymmetry: f64,
+    /// Forward minus backward fraction of behaviour windows, a proxy for how
+    /// well the stimulus drives the animal forward towards it
+    pub chemotaxis_index: f64,
+}
+
+impl Readout {
+    /// Run `protocol` on `connectome` and measure the recorded cycles.
+    pub fn measure(
+        connectome: &mut Connectome,
+        protocol: &Protocol,
+        window: usize,
+        margin: f64,
+    ) -> Result<Self, String> {
+        let mut motor_a = DischargeTrace::new(MOTOR_NEURON_A.to_vec());
+        let mut motor_b = DischargeTrace::new(MOTOR_NEURON_B.to_vec());
+        let left = [body_wall_quadrant("MDL"), body_wall_quadrant("MVL")].concat();
+        let right = [body_wall_quadrant("MDR"), body_wall_quadrant("MVR")].concat();
+        let mut values = vec![0i16; left.len().max(right.len())];
+        let (mut left_sum, mut right_sum) = (0f64, 0f64);
+        let mut positive_sum = |c: &Connectome, ids: &[u16]| {
+            c.muscle_query(ids, &mut values[..ids.len()]);
+            values[..ids.len()]
+                .iter()
+                .map(|&v| v.max(0) as f64)
+                .sum::<f64>()
+        };
+        protocol.run(connectome, |c| {
+            motor_a.record(c);
+            motor_b.record(c);
+            left_sum += positive_sum(c, &left);
+            right_sum += positive_sum(c, &right);
+            Ok(())
+        })?;
+
+        let rate = |trace: &DischargeTrace| {
+            trace.rows.iter().flatten().map(|&d| d as f64).sum::<f64>()
+                / (trace.rows.len() * trace.ids.len()).max(1) as f64
+        };
+        let (a, b) = (rate(&motor_a), rate(&motor_b));
+        let behaviour = BehaviourSummary::from_traces(&motor_a.rows, &motor_b.rows, window, margin);
+        let contrast = |x: f64, y: f64| if x + y > 0. { (x - y) / (x + y) } else { 0. };
+        Ok(Self {
+            ab_balance: contrast(b, a),
+            muscle_asymmetry: contrast(left_sum, right_sum),
+            chemotaxis_index: behaviour.forward - behaviour.backward,
+        })
+    }
+
+    fn delta(&self, baseline: &Readout) -> Readout {
+        Readout {
+            ab_balance: self.ab_balance - baseline.ab_balance,
+            muscle_asymmetry: self.muscle_asymmetry - baseline.muscle_asymmetry,
+            chemotaxis_index: self.chemotaxis_index - baseline.chemotaxis_index,
+        }
+    }
+}
+
+/// Which readout the screen is ranked by.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RankBy {
+    /// Sum of the absolute changes of all readouts
+    #[default]
+    Combined,
+    AbBalance,
+    MuscleAsymmetry,
+    ChemotaxisIndex,
+}
+
+/// Outcome of one lesion relative to the intact baseline.
+#[derive(Clone, Debug)]
+pub struct LesionEffect {
+    pub lesion: Vec<u16>,
+    pub readout: Readout,
+    pub delta: Readout,
+}
+
+impl LesionEffect {
+    pub fn effect(&self, rank_by: RankBy) -> f64 {
+        let d = &self.delta;
+        match rank_by {
+            RankBy::Combined => {
+                d.ab_balance.abs() + d.muscle_asymmetry.abs() + d.chemotaxis_index.abs()
+            }
+            RankBy::AbBalance => d.ab_balance.abs(),
+            RankBy::MuscleAsymmetry => d.muscle_asymmetry.abs(),
+            RankBy::ChemotaxisIndex => d.chemotaxis_index.abs(),
+        }
+    }
+}
+
+impl fmt::Display for LesionEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.lesion.iter().map(|&id| cell_name(id)).collect();
+        write!(
+            f,
+            "{:<14} ab {:+.3} ({:+.3})  asymmetry {:+.3} ({:+.3})  chemotaxis {:+.3} ({:+.3})",
+            names.join("+"),
+            self.readout.ab_balance,
+            self.delta.ab_balance,
+            self.readout.muscle_asymmetry,
+            self.delta.muscle_asymmetry,
+            self.readout.chemotaxis_index,
+            self.delta.chemotaxis_index,
+        )
+    }
+}
+
+/// Result of a perturbation screen, ranked by effect size.
+#[derive(Clone, Debug)]
+pub struct Screen {
+    pub baseline: Readout,
+    pub rank_by: RankBy,
+    pub effects: Vec<LesionEffect>,
+}
+
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "baseline       ab {:+.3}  asymmetry {:+.3}  chemotaxis {:+.3}",
+            self.baseline.ab_balance,
+            self.baseline.muscle_asymmetry,
+            self.baseline.chemotaxis_index
+        )?;
+        for (rank, effect) in self.effects.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>4} {:.3}  {}",
+                rank + 1,
+                effect.effect(self.rank_by),
+                effect
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Lesion each of `lesions` on a fresh connectome from `build`, rerun
+/// `protocol` and rank the lesions by how much they move the readouts away
+/// from the unlesioned baseline. Lesions run in parallel.
+pub fn perturbation_screen(
+    build: &(dyn Fn() -> Result<Connectome, String> + Sync),
+    protocol: &Protocol,
+    lesions: &[Vec<u16>],
+    rank_by: RankBy,
+    window: usize,
+    margin: f64,
+) -> Result<Screen, String> {
+    let baseline = Readout::measure(&mut build()?, protocol, window, margin)?;
+    let mut effects = lesions
+        .par_iter()
+        .map(|lesion| {
+            let mut connectome = build()?;
+            lesion.iter().for_each(|&id| connectome.ablate(id));
+            let readout = Readout::measure(&mut connectome, protocol, window, margin)?;
+            Ok(LesionEffect {
+                lesion: lesion.clone(),
+                delta: readout.delta(&baseline),
+                readout,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    effects.sort_by(|a, b| b.effect(rank_by).total_cmp(&a.effect(rank_by)));
+    Ok(Screen {
+        baseline,
+        rank_by,
+        effects,
+    })
+}
+
+/// Every single lesion of `candidates`
+pub fn single_lesions(candidates: &[u16]) -> Vec<Vec<u16>> {
+    candidates.iter().map(|&id| vec![id]).collect()
+}
+
+/// Every unordered pair of `candidates`
+pub fn pair_lesions(candidates: &[u16]) -> Vec<Vec<u16>> {
+    (0..candidates.len())
+        .flat_map(|i| (i + 1..candidates.len()).map(move |j| vec![candidates[i], candidates[j]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::preset;
+    use crate::emulations::c_elegans::protocol::ProtocolStep;
+
+    fn protocol() -> Protocol {
+        Protocol {
+            steps: vec![ProtocolStep::Run {
+                cycles: 200,
+                stimulus: resolve_targets(&["nose_touch"]).unwrap(),
+                record: true,
+            }],
+        }
+    }
+
+    #[test]
+    fn lesion_sets() {
+        assert_eq!(single_lesions(&[4, 5]), [vec![4], vec![5]]);
+        assert_eq!(
+            pair_lesions(&[1, 2, 3]),
+            [vec![1, 2], vec![1, 3], vec![2, 3]]
+        );
+        assert!(pair_lesions(&[1]).is_empty());
+    }
+
+    #[test]
+    fn screen_ranks_lesions_by_effect() {
+        let build = || Ok(preset::connectome());
+        let candidates = resolve_targets(&["AVAL", "AVAR", "AVBL", "FLPL"]).unwrap();
+        let mut lesions = single_lesions(&candidates);
+        lesions.push(candidates.clone());
+        let screen =
+            perturbation_screen(&build, &protocol(), &lesions, RankBy::Combined, 50, 0.2).unwrap();
+        assert_eq!(screen.effects.len(), 5);
+        let effects: Vec<f64> = screen
+            .effects
+            .iter()
+            .map(|e| e.effect(RankBy::Combined))
+            .collect();
+        assert!(effects.windows(2).all(|w| w[0] >= w[1]));
+        // lesioning all four changes behaviour
+        assert!(effects[0] > 0.);
+        for effect in &screen.effects {
+            let r = effect.readout;
+            assert!(
+                [r.ab_balance, r.muscle_asymmetry, r.chemotaxis_index]
+                    .iter()
+                    .all(|v| (-1. ..=1.).contains(v))
+            );
+            assert_eq!(effect.delta, r.delta(&screen.baseline));
+        }
+
+        // the parallel screen is deterministic
+        let again =
+            perturbation_screen(&build, &protocol(), &lesions, RankBy::Combined, 50, 0.2).unwrap();
+        assert_eq!(screen.baseline, again.baseline);
+        let lesion_order = |s: &Screen| {
+            s.effects
+                .iter()
+                .map(|e| e.lesion.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lesion_order(&screen), lesion_order(&again));
+        assert!(screen.to_string().starts_with("baseline"));
+    }
+}
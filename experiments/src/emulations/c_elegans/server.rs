@@ -0,0 +1,568 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tungstenite::{Message, WebSocket};
+
+use crate::connectome::{Connectome, ConnectomeSnapshot};
+use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+use crate::emulations::c_elegans::neuron_ids::cell_name;
+
+/// A client command, one JSON object per line (TCP) or text message
+/// (WebSocket), e.g. `{"cmd": "step", "cycles": 10}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    /// Targets pinged every following cycle; none clears the stimulus
+    Stimulate {
+        #[serde(default)]
+        targets: Vec<String>,
+    },
+    /// Run cycles, streaming a frame after each one if streaming is on
+    Step {
+        #[serde(default = "one")]
+        cycles: usize,
+    },
+    /// Discharge flags and muscle values of the last cycle; all cells if empty
+    Query {
+        #[serde(default)]
+        neurons: Vec<String>,
+        #[serde(default)]
+        muscles: Vec<String>,
+    },
+    /// Select what `step` streams back and in which format
+    Stream {
+        format: StreamFormat,
+        #[serde(default)]
+        neurons: Vec<String>,
+        #[serde(default)]
+        muscles: Vec<String>,
+    },
+    /// Save the engine state; the reply carries the snapshot id
+    Snapshot,
+    Restore {
+        id: usize,
+    },
+    /// End this client's session
+    Close,
+    /// End the session and stop the server
+    Shutdown,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamFormat {
+    Off,
+    Json,
+    Binary,
+}
+
+/// What a session does after a request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Close,
+    Shutdown,
+}
+
+/// Sends replies and per-cycle frames to a client.
+pub trait Transport {
+    /// Next text command, `None` once the client has gone
+    fn recv(&mut self) -> io::Result<Option<String>>;
+    fn send_text(&mut self, text: &str) -> io::Result<()>;
+    fn send_binary(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+/// Newline-delimited JSON over a raw TCP stream. Binary frames are written
+/// as a `0xFF` marker byte, a little-endian `u32` length and the payload, so
+/// they cannot be mistaken for a JSON line.
+pub struct LineTransport {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl LineTransport {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+}
+
+impl Transport for LineTransport {
+    fn recv(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    fn send_text(&mut self, text: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", text)
+    }
+
+    fn send_binary(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(&[0xFF])?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)
+    }
+}
+
+impl Transport for WebSocket<TcpStream> {
+    fn recv(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read() {
+                Ok(Message::Text(text)) => return Ok(Some(text.to_string())),
+                Ok(Message::Close(_)) => return Ok(None),
+                Ok(_) => continue,
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(None),
+                Err(err) => return Err(io::Error::other(err)),
+            }
+        }
+    }
+
+    fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.send(Message::text(text)).map_err(io::Error::other)
+    }
+
+    fn send_binary(&mut self, data: &[u8]) -> io::Result<()> {
+        self.send(Message::binary(data.to_vec()))
+            .map_err(io::Error::other)
+    }
+}
+
+/// A hosted connectome and the per-client settings around it.
+///
+/// Replies are `{"ok": ...}` or `{"error": "..."}`. A JSON stream frame is
+/// `{"cycle": n, "discharged": [ids], "muscles": [values]}`; a binary frame
+/// is the cycle as `u64`, the number of discharged ids as `u16`, the ids as
+/// `u16` and the muscle values as `i16`, all little-endian, with muscles in
+/// the order returned by the `stream` command.
+pub struct Session {
+    pub connectome: Connectome,
+    stimulus: Vec<u16>,
+    format: StreamFormat,
+    stream_neurons: Vec<u16>,
+    stream_muscles: Vec<u16>,
+    snapshots: Vec<ConnectomeSnapshot>,
+}
+
+impl Session {
+    pub fn new(connectome: Connectome) -> Self {
+        Self {
+            connectome,
+            stimulus: vec![],
+            format: StreamFormat::Off,
+            stream_neurons: vec![],
+            stream_muscles: vec![],
+            snapshots: vec![],
+        }
+    }
+
+    /// Serve one client until it closes the connection or asks to stop.
+    pub fn run(&mut self, transport: &mut impl Transport) -> io::Result<Flow> {
+        while let Some(line) = transport.recv()? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (reply, flow) = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => match self.handle(request, transport) {
+                    Ok((value, flow)) => (json!({ "ok": value }), flow),
+                    Err(err) => (json!({ "error": err }), Flow::Continue),
+                },
+                Err(err) => (json!({ "error": err.to_string() }), Flow::Continue),
+            };
+            transport.send_text(&reply.to_string())?;
+            if flow != Flow::Continue {
+                self.format = StreamFormat::Off;
+                return Ok(flow);
+            }
+        }
+        self.format = StreamFormat::Off;
+        Ok(Flow::Close)
+    }
+
+    pub fn handle(
+        &mut self,
+        request: Request,
+        transport: &mut impl Transport,
+    ) -> Result<(Value, Flow), String> {
+        let value = match request {
+            Request::Stimulate { targets } => {
+                self.stimulus = resolve_targets(&targets)?;
+                json!({ "stimulus": self.stimulus })
+            }
+            Request::Step { cycles } => {
+                for _ in 0..cycles {
+                    let stim = (!self.stimulus.is_empty()).then_some(self.stimulus.as_slice());
+                    self.connectome.neural_cycle(stim);
+                    self.send_frame(transport).map_err(|err| err.to_string())?;
+                }
+                json!({ "cycle": self.connectome.cycle() })
+            }
+            Request::Query { neurons, muscles } => {
+                let neurons = self.neurons_or_all(&neurons)?;
+                let muscles = self.muscles_or_all(&muscles)?;
+                let mut discharges = vec![0u8; neurons.len()];
+                let mut values = vec![0i16; muscles.len()];
+                self.connectome.discharge_query(&neurons, &mut discharges);
+                self.connectome.muscle_query(&muscles, &mut values);
+                json!({
+                    "cycle": self.connectome.cycle(),
+                    "neurons": neurons,
+                    "discharges": discharges,
+                    "muscles": muscles,
+                    "values": values,
+                })
+            }
+            Request::Stream {
+                format,
+                neurons,
+                muscles,
+            } => {
+                self.stream_neurons = self.neurons_or_all(&neurons)?;
+                self.stream_muscles = self.muscles_or_all(&muscles)?;
+                self.format = format;
+                json!({
+                    "neurons": self.stream_neurons,
+                    "muscles": self.stream_muscles,
+                    "muscle_names": self.stream_muscles.iter().map(|&id| cell_name(id)).collect::<Vec<_>>(),
+                })
+            }
+            Request::Snapshot => {
+                self.snapshots.push(self.connectome.snapshot());
+                json!({ "id": self.snapshots.len() - 1, "cycle": self.connectome.cycle() })
+            }
+            Request::Restore { id } => {
+                let snapshot = self
+                    .snapshots
+                    .get(id)
+                    .ok_or(format!("No snapshot {}", id))?;
+                self.connectome.restore(snapshot)?;
+                json!({ "cycle": self.connectome.cycle() })
+            }
+            Request::Close => return Ok((Value::Null, Flow::Close)),
+            Request::Shutdown => return Ok((Value::Null, Flow::Shutdown)),
+        };
+        Ok((value, Flow::Continue))
+    }
+
+    fn neurons_or_all(&self, targets: &[String]) -> Result<Vec<u16>, String> {
+        if targets.is_empty() {
+            return Ok((0..self.connectome.neurons_tot()).collect());
+        }
+        let ids = resolve_targets(targets)?;
+        match ids.iter().find(|&&id| id >= self.connectome.neurons_tot()) {
+            Some(&id) => Err(format!("{} is not a neuron", cell_name(id))),
+            None => Ok(ids),
+        }
+    }
+
+    fn muscles_or_all(&self, targets: &[String]) -> Result<Vec<u16>, String> {
+        let first = self.connectome.neurons_tot();
+        if targets.is_empty() {
+            return Ok((first..first + self.connectome.muscles_tot()).collect());
+        }
+        let ids = resolve_targets(targets)?;
+        match ids.iter().find(|&&id| id < first) {
+            Some(&id) => Err(format!("{} is not a muscle", cell_name(id))),
+            None => Ok(ids),
+        }
+    }
+
+    fn send_frame(&self, transport: &mut impl Transport) -> io::Result<()> {
+        if self.format == StreamFormat::Off {
+            return Ok(());
+        }
+        let mut flags = vec![0u8; self.stream_neurons.len()];
+        let mut muscles = vec![0i16; self.stream_muscles.len()];
+        self.connectome
+            .discharge_query(&self.stream_neurons, &mut flags);
+        self.connectome
+            .muscle_query(&self.stream_muscles, &mut muscles);
+        let discharged: Vec<u16> = self
+            .stream_neurons
+            .iter()
+            .zip(&flags)
+            .filter(|&(_, &flag)| flag == 1)
+            .map(|(&id, _)| id)
+            .collect();
+        // The frame reports the cycle that just completed
+        let cycle = self.connectome.cycle() - 1;
+        match self.format {
+            StreamFormat::Json => transport.send_text(
+                &json!({ "cycle": cycle, "discharged": discharged, "muscles": muscles })
+                    .to_string(),
+            ),
+            _ => {
+                let mut frame = Vec::with_capacity(10 + 2 * (discharged.len() + muscles.len()));
+                frame.extend(cycle.to_le_bytes());
+                frame.extend((discharged.len() as u16).to_le_bytes());
+                discharged
+                    .iter()
+                    .for_each(|id| frame.extend(id.to_le_bytes()));
+                muscles.iter().for_each(|m| frame.extend(m.to_le_bytes()));
+                transport.send_binary(&frame)
+            }
+        }
+    }
+}
+
+/// Host `connectome` on `addr`, serving one client at a time until a client
+/// sends `shutdown`. Connections starting with an HTTP `GET` are upgraded to
+/// WebSocket, anything else speaks newline-delimited JSON.
+pub fn serve(addr: impl ToSocketAddrs, connectome: Connectome) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|err| err.to_string())?;
+    if let Ok(local) = listener.local_addr() {
+        eprintln!("Listening on {}", local);
+    }
+    let mut session = Session::new(connectome);
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|err| err.to_string())?;
+        let flow = serve_client(&mut session, stream);
+        match flow {
+            Ok(Flow::Shutdown) => return Ok(()),
+            Ok(_) => {}
+            Err(err) => eprintln!("Client error: {}", err),
+        }
+    }
+    Ok(())
+}
+
+fn serve_client(session: &mut Session, stream: TcpStream) -> io::Result<Flow> {
+    if opens_with_get(&stream)? {
+        let mut socket = tungstenite::accept(stream).map_err(io::Error::other)?;
+        let flow = session.run(&mut socket);
+        let _ = socket.close(None);
+        flow
+    } else {
+        session.run(&mut LineTransport::new(stream)?)
+    }
+}
+
+/// Whether the client's first bytes are `GET `, waiting until four bytes have
+/// arrived, the bytes can no longer spell it or the client has stalled for
+/// a second (a half-closed socket still peeks the bytes it sent)
+fn opens_with_get(stream: &TcpStream) -> io::Result<bool> {
+    const GET: &[u8] = b"GET ";
+    let mut start = [0u8; 4];
+    for _ in 0..1000 {
+        let peeked = stream.peek(&mut start)?;
+        if peeked == 0 || start[..peeked] != GET[..peeked] {
+            return Ok(false);
+        }
+        if peeked == GET.len() {
+            return Ok(true);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::net::Shutdown;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::emulations::c_elegans::preset;
+
+    /// Feeds scripted commands and keeps everything sent back
+    #[derive(Default)]
+    struct Script {
+        incoming: VecDeque<String>,
+        texts: Vec<Value>,
+        binaries: Vec<Vec<u8>>,
+    }
+
+    impl Script {
+        fn new(lines: &[&str]) -> Self {
+            Self {
+                incoming: lines.iter().map(|l| l.to_string()).collect(),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Transport for Script {
+        fn recv(&mut self) -> io::Result<Option<String>> {
+            Ok(self.incoming.pop_front())
+        }
+
+        fn send_text(&mut self, text: &str) -> io::Result<()> {
+            self.texts.push(serde_json::from_str(text).unwrap());
+            Ok(())
+        }
+
+        fn send_binary(&mut self, data: &[u8]) -> io::Result<()> {
+            self.binaries.push(data.to_vec());
+            Ok(())
+        }
+    }
+
+    fn run(lines: &[&str]) -> (Session, Script, Flow) {
+        let mut session = Session::new(preset::connectome());
+        let mut script = Script::new(lines);
+        let flow = session.run(&mut script).unwrap();
+        (session, script, flow)
+    }
+
+    #[test]
+    fn requests_parse_with_defaults_and_reject_unknown_fields() {
+        let step: Request = serde_json::from_str(r#"{"cmd": "step"}"#).unwrap();
+        assert!(matches!(step, Request::Step { cycles: 1 }));
+        let query: Request = serde_json::from_str(r#"{"cmd": "query"}"#).unwrap();
+        assert!(
+            matches!(query, Request::Query { neurons, muscles } if neurons.is_empty() && muscles.is_empty())
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "step", "cycle": 2}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "wiggle"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "stream"}"#).is_err());
+    }
+
+    #[test]
+    fn each_command_gets_one_reply_and_errors_keep_the_session() {
+        let (session, script, flow) = run(&[
+            r#"{"cmd": "stimulate", "targets": ["AVAL"]}"#,
+            "",
+            r#"{"cmd": "step", "cycles": 5}"#,
+            "not json",
+            r#"{"cmd": "stimulate", "targets": ["NOTACELL"]}"#,
+            r#"{"cmd": "query", "neurons": ["MDL01"]}"#,
+            r#"{"cmd": "query", "neurons": ["AVAL", "AVAR"], "muscles": ["MDL01"]}"#,
+            r#"{"cmd": "close"}"#,
+            r#"{"cmd": "step"}"#,
+        ]);
+        assert_eq!(flow, Flow::Close);
+        let replies = &script.texts;
+        assert_eq!(replies.len(), 7);
+        assert_eq!(
+            replies[0]["ok"]["stimulus"],
+            json!(resolve_targets(&["AVAL"]).unwrap())
+        );
+        assert_eq!(replies[1]["ok"]["cycle"], 5);
+        assert!(replies[2]["error"].is_string());
+        assert!(replies[3]["error"].is_string());
+        assert_eq!(replies[4]["error"], "MDL01 is not a neuron");
+        assert_eq!(replies[5]["ok"]["cycle"], 5);
+        assert_eq!(replies[5]["ok"]["discharges"].as_array().unwrap().len(), 2);
+        assert_eq!(replies[5]["ok"]["values"].as_array().unwrap().len(), 1);
+        assert_eq!(replies[6], json!({ "ok": null }));
+        // The command after close is left unread
+        assert_eq!(script.incoming.len(), 1);
+        assert_eq!(session.connectome.cycle(), 5);
+    }
+
+    #[test]
+    fn shutdown_ends_the_session_and_a_dropped_client_closes_it() {
+        assert_eq!(run(&[r#"{"cmd": "shutdown"}"#]).2, Flow::Shutdown);
+        assert_eq!(run(&[r#"{"cmd": "step"}"#]).2, Flow::Close);
+    }
+
+    #[test]
+    fn json_frames_follow_each_cycle_before_the_reply() {
+        let (_, script, _) = run(&[
+            r#"{"cmd": "stimulate", "targets": ["chemotaxis"]}"#,
+            r#"{"cmd": "stream", "format": "json", "muscles": ["MDL01", "MDL02"]}"#,
+            r#"{"cmd": "step", "cycles": 3}"#,
+        ]);
+        let texts = &script.texts;
+        assert_eq!(texts[1]["ok"]["muscle_names"], json!(["MDL01", "MDL02"]));
+        for cycle in 0..3 {
+            let frame = &texts[2 + cycle];
+            assert_eq!(frame["cycle"], cycle);
+            assert_eq!(frame["muscles"].as_array().unwrap().len(), 2);
+        }
+        assert_eq!(texts[5]["ok"]["cycle"], 3);
+    }
+
+    #[test]
+    fn binary_frames_match_the_query_reply() {
+        let (_, script, _) = run(&[
+            r#"{"cmd": "stimulate", "targets": ["chemotaxis"]}"#,
+            r#"{"cmd": "step", "cycles": 30}"#,
+            r#"{"cmd": "stream", "format": "binary", "muscles": ["MDL01", "MVL01"]}"#,
+            r#"{"cmd": "step"}"#,
+            r#"{"cmd": "query", "muscles": ["MDL01", "MVL01"]}"#,
+        ]);
+        let [frame] = script.binaries.as_slice() else {
+            panic!("expected one frame, got {}", script.binaries.len());
+        };
+        let query = &script.texts[4]["ok"];
+        let u16_at = |k: usize| u16::from_le_bytes([frame[k], frame[k + 1]]);
+        assert_eq!(u64::from_le_bytes(frame[..8].try_into().unwrap()), 30);
+        let count = u16_at(8) as usize;
+        let ids: Vec<u16> = (0..count).map(|k| u16_at(10 + 2 * k)).collect();
+        let discharged: Vec<u16> = query["neurons"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .zip(query["discharges"].as_array().unwrap())
+            .filter(|(_, d)| *d == 1)
+            .map(|(id, _)| id.as_u64().unwrap() as u16)
+            .collect();
+        assert_eq!(ids, discharged);
+        let muscles: Vec<i16> = (0..2)
+            .map(|k| u16_at(10 + 2 * (count + k)) as i16)
+            .collect();
+        assert_eq!(json!(muscles), query["values"]);
+        assert_eq!(frame.len(), 10 + 2 * (count + 2));
+    }
+
+    #[test]
+    fn restore_replays_from_the_snapshot() {
+        let (_, script, _) = run(&[
+            r#"{"cmd": "stimulate", "targets": ["chemotaxis"]}"#,
+            r#"{"cmd": "step", "cycles": 10}"#,
+            r#"{"cmd": "snapshot"}"#,
+            r#"{"cmd": "step", "cycles": 20}"#,
+            r#"{"cmd": "query"}"#,
+            r#"{"cmd": "restore", "id": 0}"#,
+            r#"{"cmd": "step", "cycles": 20}"#,
+            r#"{"cmd": "query"}"#,
+            r#"{"cmd": "restore", "id": 1}"#,
+        ]);
+        let texts = &script.texts;
+        assert_eq!(texts[2]["ok"], json!({ "id": 0, "cycle": 10 }));
+        assert_eq!(texts[5]["ok"]["cycle"], 10);
+        assert_eq!(texts[4], texts[7]);
+        assert_eq!(texts[8]["error"], "No snapshot 1");
+    }
+
+    #[test]
+    fn get_is_detected_even_when_it_arrives_in_pieces() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let send = |chunks: Vec<&'static [u8]>| {
+            std::thread::spawn(move || {
+                let mut client = TcpStream::connect(addr).unwrap();
+                for chunk in chunks {
+                    client.write_all(chunk).unwrap();
+                    client.flush().unwrap();
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+                client.shutdown(Shutdown::Write).unwrap();
+                // Keep the socket open until the server has looked
+                std::thread::sleep(Duration::from_millis(100));
+            })
+        };
+        for (chunks, expected) in [
+            (vec![b"G".as_slice(), b"E", b"T / HTTP/1.1\r\n"], true),
+            (vec![b"{\"cmd\": \"close\"}\n".as_slice()], false),
+            (vec![b"GE".as_slice()], false),
+        ] {
+            let client = send(chunks);
+            let (stream, _) = listener.accept().unwrap();
+            assert_eq!(opens_with_get(&stream).unwrap(), expected);
+            client.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{BufRead, Write};
+
+use crate::connectome::Connectome;
+use crate::emulations::c_elegans::dataset::Dataset;
+use crate::emulations::c_elegans::neuron_ids::{NeuronId, cell_name};
+
+/// Per-cycle discharge flags of a fixed set of neurons.
+///
+/// Written as whitespace-separated rows (the `motor_ab.dat` layout) under a
+/// `#` header naming the columns, so gnuplot and `read` both understand it.
+/// A `## dataset <name> <version>` line above it records where the wiring
+/// came from.
+#[derive(Clone, Debug, Default)]
+pub struct DischargeTrace {
+    pub ids: Vec<u16>,
+    pub rows: Vec<Vec<u8>>,
+    /// Name and version of the dataset the wiring was loaded from
+    pub dataset: Option<(String, String)>,
+}
+
+impl DischargeTrace {
+    pub fn new(ids: Vec<u16>) -> Self {
+        Self {
+            ids,
+            rows: vec![],
+            dataset: None,
+        }
+    }
+
+    pub fn with_dataset(mut self, dataset: &Dataset) -> Self {
+        self.dataset = Some((dataset.name.clone(), dataset.version.clone()));
+        self
+    }
+
+    /// Append the discharges of the last cycle
+    pub fn record(&mut self, connectome: &Connectome) {
+        let mut row = vec![0; self.ids.len()];
+        connectome.discharge_query(&self.ids, &mut row);
+        self.rows.push(row);
+    }
+
+    /// Rows restricted to the given ids, in their order
+    pub fn columns(&self, ids: &[u16]) -> Vec<Vec<u8>> {
+        let index: Vec<usize> = ids
+            .iter()
+            .filter_map(|id| self.ids.iter().position(|x| x == id))
+            .collect();
+        self.rows
+            .iter()
+            .map(|row| index.iter().map(|&i| row[i]).collect())
+            .collect()
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> Result<(), std::io::Error> {
+        if let Some((name, version)) = &self.dataset {
+            writeln!(w, "## dataset {} {}", name, version)?;
+        }
+        let names: Vec<String> = self.ids.iter().map(|&id| cell_name(id)).collect();
+        writeln!(w, "# {}", names.join(" "))?;
+        for row in &self.rows {
+            write_row(&mut w, row)?;
+        }
+        Ok(())
+    }
+
+    /// Read a trace written by `write`
+    pub fn read<R: BufRead>(r: R) -> Result<Self, String> {
+        let mut trace = DischargeTrace::default();
+        for line in r.lines() {
+            let line = line.map_err(|err| err.to_string())?;
+            if let Some(comment) = line.strip_prefix("##") {
+                if let Some((name, version)) = comment
+                    .strip_prefix(" dataset ")
+                    .and_then(|d| d.trim().rsplit_once(' '))
+                {
+                    trace.dataset = Some((name.to_string(), version.to_string()));
+                }
+            } else if let Some(header) = line.strip_prefix('#') {
+                trace.ids = header
+                    .split_whitespace()
+                    .map(|name| name.parse::<NeuronId>().map(|id| id as u16))
+                    .collect::<Result<_, _>>()?;
+            } else if !line.trim().is_empty() {
+                let row = line
+                    .split_whitespace()
+                    .map(|v| v.parse::<u8>().map_err(|err| err.to_string()))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                if row.len() != trace.ids.len() {
+                    return Err(format!(
+                        "Trace row has {} columns, header names {}",
+                        row.len(),
+                        trace.ids.len()
+                    ));
+                }
+                trace.rows.push(row);
+            }
+        }
+        Ok(trace)
+    }
+}
+
+/// One row of space-separated values
+pub fn write_row<W: Write>(mut w: W, row: &[u8]) -> Result<(), std::io::Error> {
+    let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+    writeln!(w, "{}", values.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulations::c_elegans::neuron_groups::resolve_targets;
+    use crate::emulations::c_elegans::preset;
+
+    #[test]
+    fn written_trace_reads_back() {
+        let mut connectome = preset::connectome();
+        let stimulus = resolve_targets(&["chemotaxis"]).unwrap();
+        let dataset = Dataset::stand_in("builtin rom", "0.1.0");
+        let mut trace =
+            DischargeTrace::new(resolve_targets(&["motor_ab"]).unwrap()).with_dataset(&dataset);
+        for _ in 0..200 {
+            connectome.neural_cycle(Some(&stimulus));
+            trace.record(&connectome);
+        }
+        assert!(trace.rows.iter().flatten().any(|&d| d == 1));
+
+        let mut text = vec![];
+        trace.write(&mut text).unwrap();
+        let read = DischargeTrace::read(text.as_slice()).unwrap();
+        assert_eq!(read.ids, trace.ids);
+        assert_eq!(read.rows, trace.rows);
+        assert_eq!(
+            read.dataset,
+            Some(("builtin rom".to_string(), "0.1.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn read_rejects_ragged_rows() {
+        let err = DischargeTrace::read("# AVAL AVAR\n0 1\n1\n".as_bytes()).unwrap_err();
+        assert!(err.contains("1 columns"), "{}", err);
+        assert!(DischargeTrace::read("# NOTACELL\n0\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::connectome::engine::NeuralEngine;
+use crate::emulations::c_elegans::neuron_groups::{
+    CHEMOTAXIS, MOTOR_NEURON_A, MOTOR_NEURON_B, NOSE_TOUCH, body_wall_quadrant,
+};
+use crate::robot::{RobotSample, RobotSim};
+
+/// Mapping between the worm connectome and a differential-drive robot.
+#[derive(Clone, Debug)]
+pub struct WormRobotParams {
+    /// Range reading below which the nose-touch neurons are stimulated
+    /// instead of the chemosensory ones
+    pub touch_range: f32,
+    /// Wheel speed per unit of mean body-wall muscle force
+    pub speed_scale: f32,
+    /// Wheel speed difference per unit of left/right force difference
+    pub turn_gain: f32,
+    /// Wheel speed difference per unit of dorsal/ventral force difference
+    pub dorsal_ventral_gain: f32,
+    /// Cycles over which the A/B motor balance is smoothed
+    pub balance_tau: f32,
+    /// Lead of the A-class over the B-class discharge fraction needed to back
+    /// up. The built-in ROM keeps A about 0.04 ahead under chemotaxis and
+    /// about 0.07 ahead under nose touch.
+    pub reverse_margin: f32,
+    /// Simulated seconds per neural cycle
+    pub dt: f32,
+}
+
+impl Default for WormRobotParams {
+    fn default() -> Self {
+        Self {
+            touch_range: 0.2,
+            speed_scale: 3.,
+            turn_gain: 3.,
+            dorsal_ventral_gain: 1.,
+            balance_tau: 20.,
+            reverse_margin: 0.055,
+            dt: 0.05,
+        }
+    }
+}
+
+/// Drives a robot from the connectome, as on the original worm robot:
+/// left (MDL, MVL) and right (MDR, MVR) body-wall muscle sums set the wheel
+/// speeds, the dorsal/ventral difference adds steering, and the robot backs
+/// up while A-class motor neurons clearly outfire B-class ones. The forward
+/// range sensor stimulates nose touch when something is close and
+/// chemotaxis (food seeking) otherwise.
+#[derive(Clone, Debug)]
+pub struct WormRobot {
+    pub params: WormRobotParams,
+    quadrants: [Vec<u16>; 4],
+    balance: f32,
+}
+
+impl WormRobot {
+    pub fn new(params: WormRobotParams) -> Self {
+        Self {
+            params,
+            quadrants: ["MDL", "MVL", "MDR", "MVR"].map(body_wall_quadrant),
+            balance: 0.,
+        }
+    }
+
+    /// Neurons to ping for a range reading
+    pub fn stimulus(&self, range: f32) -> &'static [u16] {
+        if range < self.params.touch_range {
+            &NOSE_TOUCH
+        } else {
+            &CHEMOTAXIS
+        }
+    }
+
+    /// Smoothed fraction of A minus B motor discharges
+    pub fn balance(&self) -> f32 {
+        self.balance
+    }
+
+    /// Left and right wheel speeds from the engine's last cycle
+    pub fn wheel_velocities<E: NeuralEngine + ?Sized>(&mut self, engine: &E) -> (f32, f32) {
+        let [dl, vl, dr, vr] = self.quadrants.each_ref().map(|ids| {
+            let mut forces = vec![0.; ids.len()];
+            engine.muscle_force_query(ids, &mut forces);
+            forces.iter().sum::<f32>() / ids.len().max(1) as f32
+        });
+        let discharges = |ids: &[u16]| {
+            let mut result = vec![0u8; ids.len()];
+            engine.discharge_query(ids, &mut result);
+            result.iter().map(|&r| r as f32).sum::<f32>() / ids.len() as f32
+        };
+        let a_minus_b = discharges(&MOTOR_NEURON_A) - discharges(&MOTOR_NEURON_B);
+        self.balance += (a_minus_b - self.balance) / self.params.balance_tau.max(1.);
+
+        let p = &self.params;
+        let direction = if self.balance > p.reverse_margin {
+            -1.
+        } else {
+            1.
+        };
+        let speed = direction * p.speed_scale * (dl + vl + dr + vr) / 4.;
+        let turn = p.turn_gain * ((dl + vl) - (dr + vr)) / 2.
+            + p.dorsal_ventral_gain * ((dl + dr) - (vl + vr)) / 2.;
+        (speed - turn, speed + turn)
+    }
+
+    /// Close the loop for `cycles` neural cycles: sense, stimulate, cycle, drive.
+    pub fn run<E: NeuralEngine + ?Sized>(
+        &mut self,
+        engine: &mut E,
+        sim: &mut RobotSim,
+        cycles: usize,
+    ) -> Vec<RobotSample> {
+        (0..cycles)
+            .map(|cycle| {
+                let range = sim.range();
+                engine.neural_cycle(Some(self.stimulus(range)));
+                let (left, right) = self.wheel_velocities(engine);
+                sim.step(left, right, self.params.dt);
+                RobotSample {
+                    cycle,
+                    pose: sim.pose,
+                    range,
+                    left,
+                    right,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectome::muscle_model::{MuscleModel, MuscleParams};
+    use crate::emulations::c_elegans::preset;
+    use crate::robot::{Arena, Pose, RobotParams};
+
+    #[test]
+    fn close_range_means_nose_touch() {
+        let robot = WormRobot::new(WormRobotParams::default());
+        assert_eq!(robot.stimulus(0.1), &NOSE_TOUCH[..]);
+        assert_eq!(robot.stimulus(1.), &CHEMOTAXIS[..]);
+    }
+
+    #[test]
+    fn closed_loop_stays_in_the_arena_and_repeats() {
+        let run = || {
+            let mut connectome = preset::connectome();
+            let muscles = connectome.muscles_tot() as usize;
+            connectome
+                .set_muscle_model(Some(MuscleModel::new(muscles, MuscleParams::default())))
+                .unwrap();
+            let pose = Pose {
+                x: 1.,
+                y: 1.,
+                heading: 0.3,
+            };
+            let mut sim = RobotSim::new(Arena::new(2., 2.), RobotParams::default(), pose);
+            let mut robot = WormRobot::new(WormRobotParams::default());
+            robot.run(&mut connectome, &mut sim, 300)
+        };
+        let samples = run();
+        assert_eq!(samples.len(), 300);
+        assert_eq!(samples.last().unwrap().cycle, 299);
+        let arena = Arena::new(2., 2.);
+        assert!(
+            samples
+                .iter()
+                .all(|s| arena.is_free(s.pose.x, s.pose.y, 0.1))
+        );
+        assert!(samples.iter().any(|s| s.left != 0. || s.right != 0.));
+        assert_eq!(samples, run());
+    }
+}
@@ -26,12 +26,14 @@ fn plot_synapse_example() -> Result<(), Box<dyn std::error::Error>> {
     let mut neuron = FirstOrderLif::new(300., 0.5, 1);
 
     // Synapse
-    let mut synapse = FirstOrderSynapse::default();
-    synapse.tau_s = 0.15;
+    let _synapse = FirstOrderSynapse {
+        tau_s: 0.15,
+        ..Default::default()
+    };
 
     // Run simulation
-    for idx in 0..times.len() {
-        neuron.step(input[idx], T_STEP, None);
+    for &i in input.iter() {
+        neuron.step(i, T_STEP, None);
         // synapse.step(neuron.output, T_STEP);
     }
 
@@ -47,7 +49,7 @@ fn plot_synapse_example() -> Result<(), Box<dyn std::error::Error>> {
     // synapse.add_output_history_to_plot(&times, &mut plot);
 
     // Decode output to determine input
-    let phi = neuron.decoder(-2., 2., 0.1);
+    let _phi = neuron.decoder(-2., 2., 0.1);
     // synapse.add_decoded_output_to_plot(&times, phi, &mut plot);
 
     plot.write_html("out.html");
@@ -1 +1,2 @@
+#[allow(dead_code, clippy::module_inception)]
 mod lif_sample;
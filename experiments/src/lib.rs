@@ -0,0 +1,4 @@
+pub mod connectome;
+pub mod emulations;
+pub mod lify_stuff;
+pub mod robot;
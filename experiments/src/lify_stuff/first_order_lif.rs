@@ -25,7 +25,7 @@ impl FirstOrderLif {
             refractory_time: 0.,
             bias: 0.,
             gain: 0.,
-            encoder: encoder,
+            encoder,
         };
 
         let (gain, bias) = lif.gain_bias(max_rate, intercept);
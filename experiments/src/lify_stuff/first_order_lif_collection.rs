@@ -1,9 +1,10 @@
-use nalgebra::{DMatrix, DVector};
+use nalgebra::DMatrix;
 use rand::seq::IndexedRandom;
 use rand::{Rng, SeedableRng};
 
 use crate::lify_stuff::first_order_lif::FirstOrderLif;
 use crate::lify_stuff::simulation::NeuronSimulationCollection;
+use crate::lify_stuff::utils::ridge_regression;
 
 pub struct FirstOrderLifCollection {
     pub neurons: Vec<FirstOrderLif>,
@@ -60,12 +61,12 @@ impl FirstOrderLifCollection {
         }
     }
 
-    pub fn get_tuning_curves(&self, inputs: &Vec<f64>) -> Vec<Vec<f64>> {
+    pub fn get_tuning_curves(&self, inputs: &[f64]) -> Vec<Vec<f64>> {
         self.neurons
             .iter()
             .map(|n| {
                 let mut curve: Vec<f64> = vec![];
-                for input in inputs.clone() {
+                for &input in inputs {
                     curve.push(n.analytical_rate(input * n.gain * n.encoder as f64 + n.bias));
                 }
                 curve
@@ -73,7 +74,7 @@ impl FirstOrderLifCollection {
             .collect()
     }
 
-    pub fn get_decoders(&self, inputs: &Vec<f64>) -> Result<Vec<f64>, String> {
+    pub fn get_decoders(&self, inputs: &[f64]) -> Result<Vec<f64>, String> {
         let tuning_curves = self.get_tuning_curves(inputs);
         let n_neurons = tuning_curves.len();
         let n_inputs = inputs.len();
@@ -86,13 +87,8 @@ impl FirstOrderLifCollection {
             }
         }
 
-        let value = DVector::<f64>::from_vec(inputs.clone());
-        let gamma = &neuron_cross_inputs_mat * neuron_cross_inputs_mat.transpose()
-            + DMatrix::<f64>::identity(n_neurons, n_neurons);
-        let gamma_inv = gamma.try_inverse().ok_or("Unable to get the inverseof the matrix when computing decoders for first order lif collection.")?;
-        let upsilon = &neuron_cross_inputs_mat * value;
-        let phi: DVector<f64> = gamma_inv * upsilon;
-
-        Ok(phi.iter().cloned().collect::<Vec<f64>>())
+        ridge_regression(&neuron_cross_inputs_mat, inputs, 1.).map_err(|_| {
+            "Unable to get the inverse of the matrix when computing decoders for first order lif collection.".to_string()
+        })
     }
 }
@@ -17,9 +17,8 @@ impl Default for FirstOrderSynapse {
 impl FirstOrderSynapse {
     pub fn step(&mut self, i: f64, t_step: f64, simulation: Option<&mut SynapseSimulation>) -> f64 {
         self.output = self.output * (1.0 - t_step / self.tau_s) + i * t_step / self.tau_s;
-        match simulation {
-            Some(sim) => sim.output.record(self.output),
-            None => (),
+        if let Some(sim) = simulation {
+            sim.output.record(self.output)
         }
         self.output
     }
@@ -9,10 +9,9 @@ pub struct FirstOrderSynapsesCollection {
 impl FirstOrderSynapsesCollection {
     pub fn new(num_synapses: usize, tau_s: f64) -> Self {
         let synapses = (0..num_synapses)
-            .map(|_| {
-                let mut s = FirstOrderSynapse::default();
-                s.tau_s = tau_s;
-                s
+            .map(|_| FirstOrderSynapse {
+                tau_s,
+                ..Default::default()
             })
             .collect();
         FirstOrderSynapsesCollection { synapses }
@@ -20,7 +19,7 @@ impl FirstOrderSynapsesCollection {
 
     pub fn step(
         &mut self,
-        inputs: &Vec<f64>,
+        inputs: &[f64],
         t_step: f64,
         mut sim_collection: Option<&mut SynapseSimulationCollection>,
     ) -> Result<Vec<f64>, String> {
@@ -41,7 +40,7 @@ impl FirstOrderSynapsesCollection {
             .collect())
     }
 
-    fn reset(&mut self) {
+    pub fn reset(&mut self) {
         for syn in self.synapses.iter_mut() {
             syn.reset();
         }
@@ -1,7 +1,36 @@
+use nalgebra::{DMatrix, DVector};
 use rand::Rng;
 
 use crate::lify_stuff::first_order_lif::FirstOrderLif;
 
+/// Regularized least squares: the weights `phi` minimising
+/// `|A^T phi - target|^2 + ridge |phi|^2`, where `activities` is A with one
+/// row per feature (neuron) and one column per sample.
+pub fn ridge_regression(
+    activities: &DMatrix<f64>,
+    target: &[f64],
+    ridge: f64,
+) -> Result<Vec<f64>, String> {
+    let features = activities.nrows();
+    if activities.ncols() != target.len() {
+        return Err(format!(
+            "{} samples of activity but {} target values",
+            activities.ncols(),
+            target.len()
+        ));
+    }
+    let value = DVector::<f64>::from_column_slice(target);
+    let gamma =
+        activities * activities.transpose() + DMatrix::<f64>::identity(features, features) * ridge;
+    let gamma_inv = gamma
+        .try_inverse()
+        .ok_or("Unable to invert the regularized activity matrix")?;
+    let upsilon = activities * value;
+    let phi: DVector<f64> = gamma_inv * upsilon;
+
+    Ok(phi.iter().cloned().collect::<Vec<f64>>())
+}
+
 pub fn square_wave_at_t(t: &f64, off_time_interval: i32, on_time_interval: i32) -> f64 {
     if t.rem_euclid((off_time_interval + on_time_interval) as f64) < off_time_interval as f64 {
         1.0
@@ -10,7 +39,7 @@ pub fn square_wave_at_t(t: &f64, off_time_interval: i32, on_time_interval: i32)
     }
 }
 
-pub fn scale_to_range(data: &Vec<f64>, min_val: f64, max_val: f64) -> Vec<f64> {
+pub fn scale_to_range(data: &[f64], min_val: f64, max_val: f64) -> Vec<f64> {
     let current_min = data.iter().cloned().fold(f64::INFINITY, f64::min);
     let current_max = data.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
     data.iter()
@@ -35,7 +64,7 @@ pub fn random_color_hex() -> String {
     )
 }
 
-pub fn weird_waveform(times: &Vec<f64>) -> Vec<f64> {
+pub fn weird_waveform(times: &[f64]) -> Vec<f64> {
     times
         .iter()
         .map(|&t| {
@@ -49,7 +78,7 @@ pub fn weird_waveform(times: &Vec<f64>) -> Vec<f64> {
 // TODO 12/5/25 - Find a better place for this
 pub fn compute_tuning_curve(
     neuron: &mut FirstOrderLif,
-    inputs: &Vec<f64>,
+    inputs: &[f64],
     time_limit: f64,
     t_step: f64,
 ) -> Result<Vec<f64>, String> {
@@ -58,7 +87,7 @@ pub fn compute_tuning_curve(
     let mut count;
 
     let num_steps: i32 = (time_limit / t_step) as i32;
-    for (_, input) in inputs.iter().enumerate() {
+    for input in inputs.iter() {
         // Simulate neuron for each input
         count = 0.;
         for _ in 0..num_steps {
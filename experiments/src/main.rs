@@ -1,8 +1,103 @@
-mod connectome;
-pub mod emulations;
-pub mod lify_stuff;
-use crate::emulations::c_elegans::c_elegans_nematode::test;
+mod commands;
+
+use clap::{Parser, Subcommand};
+
+use commands::{
+    analyze, circuit, communities, compare_modes, dashboard, datasets, evolve, experiment, explore,
+    export, null_models, reach, reservoir, robot, run, screen, serve, spectral,
+};
+
+/// Run C. elegans connectome experiments without recompiling.
+///
+/// Targets are cell names (AVAL), groups (chemotaxis, nose_touch, motor_a,
+/// motor_b, motor_ab, db_chain, vb_chain, da_chain, va_chain) or sensory modalities from Sensory.csv (thermosensory).
+#[derive(Parser)]
+#[command(name = "neuro-rust", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a TOML experiment spec into its output directory
+    Experiment(experiment::ExperimentArgs),
+    /// Run a protocol file and write the discharge trace
+    Run(run::RunArgs),
+    /// Stimulate targets for a number of cycles and write the discharge trace
+    Stimulate(run::StimulateArgs),
+    /// Lesion targets, then run a protocol (the built-in one by default)
+    Ablate(run::AblateArgs),
+    /// Classify locomotion from A/B motor neurons and report graph statistics
+    Analyze(analyze::AnalyzeArgs),
+    /// Export the wiring as GraphML or as a ROM image
+    Export(export::ExportArgs),
+    /// Report how much behaviour changes between update orderings
+    CompareModes(compare_modes::CompareModesArgs),
+    /// Ablate candidates one at a time (and optionally in pairs) and rank them
+    /// by their effect on behaviour
+    Screen(screen::ScreenArgs),
+    /// Evolve the connection weights towards a behaviour, keeping the wiring,
+    /// and save the best one as `wiring.csv` and `rom.rs`
+    Evolve(evolve::EvolveArgs),
+    /// Drive a random signal into input neurons, train a ridge readout on the
+    /// neuron states and report its weights and train/test error
+    Reservoir(reservoir::ReservoirArgs),
+    /// Write an ensemble of seeded null-model wirings as `<model>-<seed>.csv`
+    /// and `.rs` ROM images, each loadable with `--rom`
+    NullModels(null_models::NullModelsArgs),
+    /// Cycles from each sensory neuron to each motor neuron and muscle, by
+    /// synaptic hops and by first discharge, with the critical paths
+    Reach(reach::ReachArgs),
+    /// Eigenvalues, dominant eigenvectors, gap-junction Laplacian and
+    /// controllability/observability Gramians of the linearised wiring
+    Spectral(spectral::SpectralArgs),
+    /// List the known connectome datasets, report how one maps onto the cell
+    /// names, or compare two
+    Datasets(datasets::DatasetsArgs),
+    /// Louvain modules of the wiring and the connections between them
+    Communities(communities::CommunitiesArgs),
+    /// Cut out the wiring among a list of cells or one module as a
+    /// standalone, renumbered circuit (run circuit.txt with `circuit`, or
+    /// with --keep-ids the ROM with --rom)
+    Subcircuit(communities::SubcircuitArgs),
+    /// Run a small nervous system from an edge-list file (see
+    /// specs/ring_cpg.txt): stimulate, then write one row per cycle with the
+    /// discharges of the recorded neurons and the states of the muscles
+    Circuit(circuit::CircuitArgs),
+    /// Write a standalone HTML page to explore the wiring and replay a run
+    Explore(explore::ExploreArgs),
+    /// Run a protocol in a live terminal dashboard with pause, step and
+    /// stimulus keys
+    Dashboard(dashboard::DashboardArgs),
+    /// Host the connectome for TCP (JSON lines) and WebSocket clients
+    Serve(serve::ServeArgs),
+    /// Drive a simulated differential-drive robot in a square arena and write its trajectory
+    Robot(robot::RobotArgs),
+}
 
 pub fn main() -> Result<(), String> {
-    Ok(test()?)
+    match Cli::parse().command {
+        Command::Experiment(args) => experiment::experiment(args),
+        Command::Run(args) => run::run(args),
+        Command::Stimulate(args) => run::stimulate(args),
+        Command::Ablate(args) => run::ablate(args),
+        Command::Analyze(args) => analyze::analyze(args),
+        Command::Export(args) => export::export(args),
+        Command::CompareModes(args) => compare_modes::compare_modes(args),
+        Command::Screen(args) => screen::screen(args),
+        Command::Evolve(args) => evolve::evolve(args),
+        Command::Reservoir(args) => reservoir::reservoir(args),
+        Command::NullModels(args) => null_models::null_models(args),
+        Command::Reach(args) => reach::reach(args),
+        Command::Spectral(args) => spectral::spectral(args),
+        Command::Datasets(args) => datasets::datasets(args),
+        Command::Communities(args) => communities::communities(args),
+        Command::Subcircuit(args) => communities::subcircuit(args),
+        Command::Circuit(args) => circuit::circuit(args),
+        Command::Explore(args) => explore::explore(args),
+        Command::Dashboard(args) => dashboard::dashboard(args),
+        Command::Serve(args) => serve::serve(args),
+        Command::Robot(args) => robot::robot(args),
+    }
 }
@@ -0,0 +1,256 @@
+use std::f32::consts::PI;
+use std::io::{self, Write};
+
+/// Position and heading (radians, counter-clockwise from +x) of the robot.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub heading: f32,
+}
+
+/// A round obstacle in the arena.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Obstacle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+/// Rectangular walled arena from `(0, 0)` to `(width, height)`.
+#[derive(Clone, Debug)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl Arena {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            obstacles: vec![],
+        }
+    }
+
+    pub fn with_obstacle(mut self, x: f32, y: f32, radius: f32) -> Self {
+        self.obstacles.push(Obstacle { x, y, radius });
+        self
+    }
+
+    /// Distance from `(x, y)` along `heading` to the first wall or obstacle
+    pub fn ray_distance(&self, x: f32, y: f32, heading: f32) -> f32 {
+        let (dx, dy) = (heading.cos(), heading.sin());
+        let mut nearest = f32::INFINITY;
+        let mut consider = |t: f32| {
+            if t >= 0. && t < nearest {
+                nearest = t;
+            }
+        };
+        if dx > 0. {
+            consider((self.width - x) / dx);
+        } else if dx < 0. {
+            consider(-x / dx);
+        }
+        if dy > 0. {
+            consider((self.height - y) / dy);
+        } else if dy < 0. {
+            consider(-y / dy);
+        }
+        for o in &self.obstacles {
+            // Solve |p + t d - c| = r for the nearest non-negative t
+            let (ox, oy) = (x - o.x, y - o.y);
+            let b = ox * dx + oy * dy;
+            let c = ox * ox + oy * oy - o.radius * o.radius;
+            let disc = b * b - c;
+            if disc >= 0. {
+                let t = -b - disc.sqrt();
+                consider(if t >= 0. { t } else { -b + disc.sqrt() });
+            }
+        }
+        nearest
+    }
+
+    /// Whether a robot of `radius` centred on `(x, y)` fits without touching anything
+    pub fn is_free(&self, x: f32, y: f32, radius: f32) -> bool {
+        x >= radius
+            && y >= radius
+            && x <= self.width - radius
+            && y <= self.height - radius
+            && self
+                .obstacles
+                .iter()
+                .all(|o| (x - o.x).hypot(y - o.y) >= o.radius + radius)
+    }
+}
+
+/// Geometry and limits of the differential-drive robot.
+#[derive(Clone, Debug)]
+pub struct RobotParams {
+    /// Distance between the wheels
+    pub wheel_base: f32,
+    /// Body radius used for collisions
+    pub radius: f32,
+    /// Wheel speed limit, in arena units per second
+    pub max_speed: f32,
+    /// Farthest reading of the forward range sensor
+    pub sensor_range: f32,
+}
+
+impl Default for RobotParams {
+    fn default() -> Self {
+        Self {
+            wheel_base: 0.12,
+            radius: 0.1,
+            max_speed: 0.3,
+            sensor_range: 2.,
+        }
+    }
+}
+
+/// Headless 2D simulator of a differential-drive robot with one forward
+/// range sensor, e.g. the sonar of the original worm robot.
+#[derive(Clone, Debug)]
+pub struct RobotSim {
+    pub arena: Arena,
+    pub params: RobotParams,
+    pub pose: Pose,
+    /// Set when the last step was blocked by a wall or obstacle
+    pub bumped: bool,
+}
+
+impl RobotSim {
+    pub fn new(arena: Arena, params: RobotParams, pose: Pose) -> Self {
+        Self {
+            arena,
+            params,
+            pose,
+            bumped: false,
+        }
+    }
+
+    /// Forward range reading, capped at the sensor range
+    pub fn range(&self) -> f32 {
+        self.arena
+            .ray_distance(self.pose.x, self.pose.y, self.pose.heading)
+            .min(self.params.sensor_range)
+    }
+
+    /// Drive the wheels at `left` and `right` (clamped to the speed limit)
+    /// for `dt` seconds. A move that would collide is not taken.
+    pub fn step(&mut self, left: f32, right: f32, dt: f32) {
+        let max = self.params.max_speed;
+        let (left, right) = (left.clamp(-max, max), right.clamp(-max, max));
+        let v = (left + right) / 2.;
+        let omega = (right - left) / self.params.wheel_base;
+        let heading = self.pose.heading + omega * dt / 2.;
+        let x = self.pose.x + v * heading.cos() * dt;
+        let y = self.pose.y + v * heading.sin() * dt;
+        self.pose.heading = (self.pose.heading + omega * dt).rem_euclid(2. * PI);
+        self.bumped = !self.arena.is_free(x, y, self.params.radius);
+        if !self.bumped {
+            self.pose.x = x;
+            self.pose.y = y;
+        }
+    }
+}
+
+/// One row of a robot run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RobotSample {
+    pub cycle: usize,
+    pub pose: Pose,
+    pub range: f32,
+    pub left: f32,
+    pub right: f32,
+}
+
+/// Write samples as whitespace-separated columns with a `#` header.
+pub fn write_trajectory(w: &mut impl Write, samples: &[RobotSample]) -> io::Result<()> {
+    writeln!(w, "# cycle x y heading range left right")?;
+    for s in samples {
+        writeln!(
+            w,
+            "{} {:.4} {:.4} {:.4} {:.4} {:.4} {:.4}",
+            s.cycle, s.pose.x, s.pose.y, s.pose.heading, s.range, s.left, s.right
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn rays_stop_at_walls_and_obstacles() {
+        let arena = Arena::new(4., 2.).with_obstacle(3., 1., 0.5);
+        assert!(close(arena.ray_distance(1., 1., 0.), 1.5));
+        assert!(close(arena.ray_distance(1., 1., PI), 1.));
+        assert!(close(arena.ray_distance(1., 1., PI / 2.), 1.));
+        assert!(close(arena.ray_distance(1., 0.5, -PI / 2.), 0.5));
+        // from inside the obstacle the ray leaves through its far side
+        assert!(close(arena.ray_distance(3., 1., 0.), 0.5));
+        assert!(arena.is_free(1., 1., 0.1));
+        assert!(!arena.is_free(0.05, 1., 0.1));
+        assert!(!arena.is_free(2.45, 1., 0.1));
+    }
+
+    #[test]
+    fn wheels_drive_and_turn() {
+        let arena = Arena::new(4., 4.);
+        let pose = Pose {
+            x: 2.,
+            y: 2.,
+            heading: 0.,
+        };
+        let mut sim = RobotSim::new(arena, RobotParams::default(), pose);
+        sim.step(0.2, 0.2, 1.);
+        assert!(close(sim.pose.x, 2.2) && close(sim.pose.y, 2.));
+        // speeds are clamped to 0.3
+        sim.step(1., 1., 1.);
+        assert!(close(sim.pose.x, 2.5));
+        // opposite wheels spin in place
+        sim.step(-0.03, 0.03, 1.);
+        assert!(close(sim.pose.x, 2.5) && close(sim.pose.heading, 0.5));
+        assert!(close(sim.range(), 1.5 / 0.5f32.cos()));
+    }
+
+    #[test]
+    fn blocked_moves_are_not_taken() {
+        let pose = Pose {
+            x: 0.5,
+            y: 0.5,
+            heading: PI,
+        };
+        let mut sim = RobotSim::new(Arena::new(1., 1.), RobotParams::default(), pose);
+        sim.step(0.3, 0.3, 2.);
+        assert!(sim.bumped);
+        assert_eq!((sim.pose.x, sim.pose.y), (0.5, 0.5));
+        sim.step(-0.3, -0.3, 1.);
+        assert!(!sim.bumped && close(sim.pose.x, 0.8));
+    }
+
+    #[test]
+    fn trajectory_has_a_header_and_one_row_per_sample() {
+        let sample = RobotSample {
+            cycle: 3,
+            pose: Pose::default(),
+            range: 1.,
+            left: 0.5,
+            right: -0.5,
+        };
+        let mut out = vec![];
+        write_trajectory(&mut out, &[sample, sample]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "# cycle x y heading range left right");
+        assert_eq!(lines[1], "3 0.0000 0.0000 0.0000 1.0000 0.5000 -0.5000");
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,117 @@
+//! Cross-check of the allocation-free `neuro-rust-core` engine against the
+//! std engine: the same ROM and stimuli must give the same discharges and
+//! states, cycle for cycle.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use neuro_rust::connectome::Connectome;
+use neuro_rust::connectome::edge_list::Circuit;
+use neuro_rust::connectome::null_model::NullModel;
+use neuro_rust::connectome::rom_image::build_rom;
+use neuro_rust::emulations::c_elegans::neuron_groups::named_group;
+use neuro_rust::emulations::c_elegans::neuron_ids::CELLS;
+use neuro_rust::emulations::c_elegans::preset;
+use neuro_rust::emulations::c_elegans::rom::ROM;
+use neuro_rust_core::{Connectome as CoreConnectome, RomError};
+
+const NEURONS: usize = 299;
+const MUSCLES: usize = CELLS as usize - NEURONS;
+
+/// Run both engines under `stimulus(cycle)` and compare every cell each cycle
+fn cross_check<const N: usize, const M: usize>(
+    host: &mut Connectome,
+    rom: &[u16],
+    cycles: usize,
+    mut stimulus: impl FnMut(usize) -> Option<Vec<u16>>,
+) {
+    let mut core = CoreConnectome::<N, M>::from_rom(rom).expect("ROM fits the core");
+    core.set_threshold(host.threshold());
+    let ids: Vec<u16> = (0..(N + M) as u16).collect();
+    let neurons = &ids[..N];
+    let (mut host_states, mut core_states) = (vec![0; N + M], vec![0; N + M]);
+    let (mut host_fired, mut core_fired) = (vec![0; N], vec![0; N]);
+    let mut active = 0;
+    for cycle in 0..cycles {
+        let stim = stimulus(cycle);
+        host.neural_cycle(stim.as_deref());
+        core.neural_cycle(stim.as_deref());
+
+        host.state_query(&ids, &mut host_states);
+        core.state_query(&ids, &mut core_states);
+        assert_eq!(host_states, core_states, "states differ at cycle {}", cycle);
+        host.discharge_query(neurons, &mut host_fired);
+        core.discharge_query(neurons, &mut core_fired);
+        assert_eq!(
+            host_fired, core_fired,
+            "discharges differ at cycle {}",
+            cycle
+        );
+        active += host_fired.iter().filter(|&&d| d == 1).count();
+    }
+    assert!(active > 0, "nothing discharged, the comparison is vacuous");
+}
+
+#[test]
+fn builtin_rom_matches_host_engine() {
+    let chemotaxis = named_group("chemotaxis").unwrap();
+    let nose_touch = named_group("nose_touch").unwrap();
+    // Rest long enough for idle neurons to be reset
+    cross_check::<NEURONS, MUSCLES>(&mut preset::connectome(), &ROM, 1200, |cycle| match cycle {
+        0..400 => Some(chemotaxis.clone()),
+        400..800 => None,
+        _ => Some(nose_touch.clone()),
+    });
+}
+
+#[test]
+fn random_stimuli_and_threshold_match_host_engine() {
+    let mut host = preset::connectome();
+    host.set_threshold(25);
+    let mut rng = StdRng::seed_from_u64(7);
+    cross_check::<NEURONS, MUSCLES>(&mut host, &ROM, 1000, |_| {
+        let count = rng.random_range(0..6);
+        Some(
+            (0..count)
+                .map(|_| rng.random_range(0..NEURONS as u16))
+                .collect(),
+        )
+    });
+}
+
+#[test]
+fn null_model_wiring_matches_host_engine() {
+    let wiring = preset::connectome();
+    let edges = NullModel::ErdosRenyi
+        .generate(wiring.neurons_tot(), CELLS, &wiring.edges(), 3)
+        .unwrap();
+    let rom = build_rom(wiring.neurons_tot(), &edges).unwrap();
+    let mut host = Connectome::from_rom(rom.clone(), CELLS).unwrap();
+    let chemotaxis = named_group("chemotaxis").unwrap();
+    cross_check::<NEURONS, MUSCLES>(&mut host, &rom, 500, |cycle| {
+        (cycle % 50 < 10).then(|| chemotaxis.clone())
+    });
+}
+
+#[test]
+fn edge_list_circuit_matches_host_engine() {
+    let circuit = Circuit::parse(include_str!("../specs/ring_cpg.txt")).unwrap();
+    let mut host = circuit.connectome().unwrap();
+    let rom = host.rom().to_vec();
+    cross_check::<5, 2>(&mut host, &rom, 300, |cycle| (cycle == 0).then(|| vec![0]));
+}
+
+#[test]
+fn core_rejects_mismatched_rom() {
+    assert_eq!(
+        CoreConnectome::<300, 97>::from_rom(&ROM).err(),
+        Some(RomError::Neurons {
+            rom: 299,
+            engine: 300
+        })
+    );
+    assert!(matches!(
+        CoreConnectome::<NEURONS, 10>::from_rom(&ROM),
+        Err(RomError::Target { .. })
+    ));
+}